@@ -0,0 +1,32 @@
+//! Time `learn_randomly` on an MNIST sized dataset against copying the dataset, which it used to do on every call
+//!
+//! Run with `cargo bench --bench learn_randomly`
+use neuralib::{network::NeuralNetwork, activation::Activation, training::DataValue};
+use rand::Rng;
+use std::time::Instant;
+
+fn main() {
+    let mut network = NeuralNetwork::new(&[10], 784, vec![Activation::Sigmoid]).unwrap();
+
+    let mut rng = rand::rng();
+    let data: Vec<DataValue> = (0..60000)
+        .map(|idx| DataValue { input: (0..784).map(|_| rng.random::<f64>()).collect(), expected_output: DataValue::one_hot(idx % 10, 10).unwrap() })
+        .collect();
+
+    let calls = 20;
+
+    let start = Instant::now();
+    for _ in 0..calls {
+        network.learn_randomly(&data, 0.1, 10).unwrap();
+    }
+    let learn_time = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..calls {
+        std::hint::black_box(data.to_vec());
+    }
+    let clone_time = start.elapsed();
+
+    println!("learn_randomly: {:?} per call on {} samples", learn_time / calls, data.len());
+    println!("Copying the data: {:?} per call", clone_time / calls);
+}
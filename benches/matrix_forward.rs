@@ -0,0 +1,30 @@
+//! Compare the neuron-by-neuron forward pass with the ndarray one on an MNIST sized network
+//!
+//! Run with `cargo bench --features ndarray`
+use neuralib::{network::NeuralNetwork, activation::Activation};
+use rand::Rng;
+use std::time::Instant;
+
+fn main() {
+    let network = NeuralNetwork::new(&[100, 10], 784, vec![Activation::Sigmoid, Activation::Sigmoid]).unwrap();
+    let matrix = network.to_matrix();
+
+    let mut rng = rand::rng();
+    let inputs: Vec<Vec<f64>> = (0..1000).map(|_| (0..784).map(|_| rng.random::<f64>()).collect()).collect();
+
+    let start = Instant::now();
+    let scalar_outputs: Vec<Vec<f64>> = inputs.iter().map(|input| network.predict(input).unwrap()).collect();
+    let scalar_time = start.elapsed();
+
+    let start = Instant::now();
+    let matrix_outputs = matrix.predict_batch(&inputs).unwrap();
+    let matrix_time = start.elapsed();
+
+    let max_difference = scalar_outputs.iter().flatten().zip(matrix_outputs.iter().flatten())
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f64::max);
+
+    println!("Scalar: {scalar_time:?} for {} samples", inputs.len());
+    println!("Matrix: {matrix_time:?} for {} samples", inputs.len());
+    println!("Speedup: {:.1}x (max difference {max_difference:e})", scalar_time.as_secs_f64() / matrix_time.as_secs_f64());
+}
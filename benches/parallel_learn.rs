@@ -0,0 +1,34 @@
+//! Time `learn` on an MNIST sized batch with one rayon thread against all of them
+//!
+//! Run with `cargo bench --bench parallel_learn --features rayon`
+use neuralib::{network::NeuralNetwork, activation::Activation, training::DataValue};
+use rand::Rng;
+use std::time::Instant;
+
+fn main() {
+    let mut network = NeuralNetwork::new(&[100, 10], 784, vec![Activation::Sigmoid, Activation::Sigmoid]).unwrap();
+
+    let mut rng = rand::rng();
+    let data: Vec<DataValue> = (0..2000)
+        .map(|idx| DataValue { input: (0..784).map(|_| rng.random::<f64>()).collect(), expected_output: DataValue::one_hot(idx % 10, 10).unwrap() })
+        .collect();
+
+    let calls = 5;
+
+    let one_thread = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let start = Instant::now();
+    for _ in 0..calls {
+        one_thread.install(|| network.learn(&data, 0.1)).unwrap();
+    }
+    let serial_time = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..calls {
+        network.learn(&data, 0.1).unwrap();
+    }
+    let parallel_time = start.elapsed();
+
+    println!("1 thread: {:?} per call on {} samples", serial_time / calls, data.len());
+    println!("{} threads: {:?} per call on {} samples", rayon::current_num_threads(), parallel_time / calls, data.len());
+    println!("Speedup: {:.1}x", serial_time.as_secs_f64() / parallel_time.as_secs_f64());
+}
@@ -1,41 +1,67 @@
-use neuralib::{network::NeuralNetwork, activation::Activation, training::DataValue};
+use neuralib::{network::NetworkBuilder, activation::Activation, init::Init, loss::Loss, optimizer::LrSchedule, training::{DataValue, TrainConfig}};
 use std::{io::BufReader, fs::File};
 use indicatif::ProgressBar;
 
 fn main() {
-    let epochs = 650;
-    
+    let epochs = 10;
+    let checkpoint_every = 2;
+    let gamma = 0.8;
+
     let mut input_idx = BufReader::new(File::open("src/train-images-idx3-ubyte").unwrap());
     let mut labels_idx = BufReader::new(File::open("src/train-labels-idx1-ubyte").unwrap());
 
     let mut test_input = BufReader::new(File::open("src/t10k-images-idx3-ubyte").unwrap());
     let mut test_labels = BufReader::new(File::open("src/t10k-labels-idx1-ubyte").unwrap());
     
-    let data: Vec<DataValue> = DataValue::from_data_label_idx(&mut input_idx, &mut labels_idx, None).unwrap();
-    let test_data: Vec<DataValue> = DataValue::from_data_label_idx(&mut test_input, &mut test_labels, None).unwrap();
-
-    // Network with 784 inputs, 100 hidden, and 10 outputs. Both the hidden layer and the output have sigmoid activation
-    let mut network = NeuralNetwork::new(&[100, 10], 784, vec![Activation::Sigmoid, Activation::Sigmoid]).unwrap();
-
-    let bar = ProgressBar::new(epochs);
-
-    let epoch_size = data.len() / 10;
-
-    println!("Learning... (Epoch size: {epoch_size})");
-    for epoch in 1..=epochs {
-        bar.inc(1);
-        network.learn_randomly(&data, 0.5, epoch_size).unwrap();
-        if epoch % 100 == 0 {
-            network.save(&mut File::create(format!("save-epoch-{epoch}.mp")).unwrap()).unwrap();
-            println!("Epoch: {epoch}. (Saved). Loss: {}", network.loss(&test_data).unwrap());
-        } else if epoch % 20 == 0 {
-            network.save(&mut File::create(format!("save-epoch-{epoch}.mp")).unwrap()).unwrap();
-            println!("Epoch: {epoch}. (Saved)");
-        } else {
-            println!("Epoch: {epoch}.");
-        }
+    let data: Vec<DataValue> = DataValue::from_data_label_idx(&mut input_idx, &mut labels_idx, None, None).unwrap();
+    let test_data: Vec<DataValue> = DataValue::from_data_label_idx(&mut test_input, &mut test_labels, None, None).unwrap();
+
+    // Network with 784 inputs, three hidden layers of 100, and 10 outputs. The hidden layers have sigmoid activation, so Xavier initialization is used.
+    // Softmax outputs with cross-entropy loss are fused, which keeps the loss and gradients stable
+    let mut network = NetworkBuilder::new(784)
+        .layer(100, Activation::Sigmoid)
+        .layer(100, Activation::Sigmoid)
+        .layer(100, Activation::Sigmoid)
+        .layer(10, Activation::Softmax)
+        .weight_init(Init::Xavier)
+        .loss(Loss::CrossEntropy)
+        .build()
+        .unwrap();
+
+    // Batch normalization keeps the deeper sigmoid layers out of saturation
+    for layer in 0..3 {
+        network.set_batch_norm(layer, true).unwrap();
     }
 
+    println!("{}", network.summary());
+
+    let bar = ProgressBar::new(epochs as u64);
+
+    println!("Learning...");
+    // Training is split into runs of `checkpoint_every` epochs so the network can be saved between them.
+    // Each run starts where the last one's exponential schedule left off
+    for start in (0..epochs).step_by(checkpoint_every) {
+        let config = TrainConfig {
+            learn_rate: 0.5 * f64::powi(gamma, start as i32),
+            epochs: checkpoint_every.min(epochs - start),
+            seed: None,
+            batch_size: Some(100),
+            lr_schedule: LrSchedule::Exponential { gamma },
+        };
+
+        network.fit_with_callback(&data, config, Some(&test_data), |info| {
+            bar.inc(1);
+            bar.println(format!("Epoch: {}/{epochs}. Train loss: {}. Test loss: {}", start + info.epoch + 1, info.train_loss, info.validation_loss.unwrap_or_default()));
+        }).unwrap();
+
+        let epoch = bar.position();
+        network.save(&mut File::create(format!("save-epoch-{epoch}.mp")).unwrap()).unwrap();
+        bar.println(format!("Epoch: {epoch}. (Saved). Accuracy: {}", network.accuracy(&test_data).unwrap()));
+    }
+    bar.finish();
+
+    println!("Accuracy: {}", network.accuracy(&test_data).unwrap());
+
     network.save(&mut File::create("final.mp").unwrap()).unwrap();
 
     //println!("{network:#?}");
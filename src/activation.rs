@@ -2,46 +2,162 @@
 //!
 //! This module provides many different activation functions for a neural network.
 
+use std::fmt;
+use std::sync::Arc;
+
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
+/// A trait for activation functions that aren't built into neuralib.
+///
+/// Use `Activation::custom` to use one in a network. Networks using custom activation functions can't be saved.
+pub trait ActivationFn: Send + Sync {
+	/// Call the activation function
+	fn call(&self, x: f64) -> f64;
+	/// The derivative of the activation function
+	fn derivative(&self, x: f64) -> f64;
+}
+
+/// A shared custom activation function. Create one with `Activation::custom`
+#[derive(Clone)]
+pub struct CustomActivation(Arc<dyn ActivationFn>);
+
+impl fmt::Debug for CustomActivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CustomActivation")
+    }
+}
+
+/// Custom activation functions are only equal to clones of themselves
+impl PartialEq for CustomActivation {
+    fn eq(&self, other: &CustomActivation) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 /// The activation functions this library supports
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+///
+/// Activations are equal if they are the same function with the same parameters
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Activation {
 	/// A linear activation function. The output is the same as the input
 	#[default]
 	Linear,
 	/// The step activation function. The output is 0 if x<0 otherwise, it's 1
+	///
+	/// Its derivative is 0 everywhere, so networks using it can't be trained. Use `StepSTE` for training
 	Step,
+	/// The step activation function with a straight-through estimator: <https://arxiv.org/abs/1308.3432>
+	///
+	/// The output is the same as `Step`, but the derivative is treated as 1 so gradients pass through it
+	StepSTE,
 	/// The sigmoid activation function: <https://en.wikipedia.org/wiki/Sigmoid_function>
 	Sigmoid,
 	/// The Hyperbolic Tangent activation function.
 	HyperTan,
 	/// The SiLU (Swish) activation function: <https://en.wikipedia.org/wiki/Rectified_linear_unit#SiLU>
-	SiLU,
+	///
+	/// `beta` controls how sharp the gate is: `x * sigmoid(beta * x)`. `Activation::DEFAULT_SI_LU_BETA` (1.0) is the usual SiLU
+	SiLU {
+		beta: f64
+	},
 	/// The ReLU activation function: <https://en.wikipedia.org/wiki/Rectified_linear_unit>
 	ReLU,
 	/// The Leaky ReLU activation function: <https://en.wikipedia.org/wiki/Rectified_linear_unit#Piecewise-linear_variants>
-	LeakyReLU,
+	///
+	/// Negative inputs are multiplied by `slope`. `Activation::DEFAULT_LEAKY_SLOPE` (0.01) is a good default
+	LeakyReLU {
+		slope: f64
+	},
 	/// The Swish activation function: <https://en.wikipedia.org/wiki/Swish_function>
 	#[deprecated(since="0.0.2", note="Please use SiLU instead")]
-	Swish
+	Swish,
+	/// The ELU activation function: <https://en.wikipedia.org/wiki/Rectified_linear_unit#ELU>
+	///
+	/// `alpha` controls the value negative inputs saturate to. 1.0 is a good default
+	ELU {
+		alpha: f64
+	},
+	/// The GELU activation function (tanh approximation): <https://en.wikipedia.org/wiki/Rectified_linear_unit#Gaussian-error_linear_unit_(GELU)>
+	GELU,
+	/// The Mish activation function: <https://arxiv.org/abs/1908.08681>
+	Mish,
+	/// The PReLU activation function: <https://arxiv.org/abs/1502.01852>
+	///
+	/// Like Leaky ReLU, but every neuron learns its own slope during training. `initial_slope` is the slope neurons start with
+	PReLU {
+		initial_slope: f64
+	},
+	/// The softplus activation function: <https://en.wikipedia.org/wiki/Softplus>
+	Softplus,
+	/// The softsign activation function: `x / (1 + |x|)`
+	Softsign,
+	/// A piecewise linear approximation of sigmoid: `clamp((x + 3) / 6, 0, 1)`. It's cheaper than sigmoid since it doesn't need `exp`
+	HardSigmoid,
+	/// A piecewise linear approximation of tanh: `clamp(x, -1, 1)`
+	HardTanh,
+	/// A piecewise approximation of SiLU from MobileNetV3: <https://arxiv.org/abs/1905.02244>
+	///
+	/// `x * hard_sigmoid(x)`, which is 0 below -3 and the identity above 3
+	HardSwish,
+	/// A custom activation function. These can't be saved
+	#[cfg_attr(feature = "serde", serde(skip))]
+	Custom(CustomActivation),
+	/// The softmax activation function: <https://en.wikipedia.org/wiki/Softmax_function>
+	///
+	/// Softmax normalizes a whole layer so its outputs sum to 1. Layers compute it jointly with `Activation::softmax`.
+	/// Called on a single value, it behaves like the softmax of a one neuron layer.
+	Softmax,
 }
 
 
 impl Activation {
+	/// The usual slope for `Activation::LeakyReLU`
+	pub const DEFAULT_LEAKY_SLOPE: f64 = 0.01;
+
+	/// The usual beta for `Activation::SiLU`
+	pub const DEFAULT_SI_LU_BETA: f64 = 1.0;
+
+	/// SiLU with the default beta
+	pub const fn si_lu_default() -> Activation {
+		Activation::SiLU { beta: Activation::DEFAULT_SI_LU_BETA }
+	}
+
+	/// Use a custom activation function
+	pub fn custom(function: impl ActivationFn + 'static) -> Activation {
+		Activation::Custom(CustomActivation(Arc::new(function)))
+	}
+
+	/// Leaky ReLU with the default slope
+	pub const fn leaky_re_lu_default() -> Activation {
+		Activation::LeakyReLU { slope: Activation::DEFAULT_LEAKY_SLOPE }
+	}
+
 	/// Call the selected activation function
 	pub fn call(&self, x: f64) -> f64 {
 		match self {
 			Activation::Linear    => Activation::linear(x),
 			Activation::Step      => Activation::step(x),
+			Activation::StepSTE   => Activation::step(x),
 			Activation::Sigmoid   => Activation::sigmoid(x),
 			Activation::HyperTan  => Activation::hypertan(x),
-			Activation::SiLU      => Activation::si_lu(x),
+			Activation::SiLU { beta } => Activation::si_lu(x, *beta),
 			Activation::ReLU      => Activation::re_lu(x),
-			Activation::LeakyReLU => Activation::leaky_re_lu(x),
+			Activation::LeakyReLU { slope } => Activation::leaky_re_lu(x, *slope),
 			#[allow(deprecated)]
 			Activation::Swish     => Activation::swish(x),
+			Activation::Softmax   => Activation::softmax(&[x])[0],
+			Activation::ELU { alpha } => Activation::elu(x, *alpha),
+			Activation::GELU      => Activation::gelu(x),
+			Activation::Mish      => Activation::mish(x),
+			Activation::PReLU { initial_slope } => Activation::leaky_re_lu(x, *initial_slope),
+			Activation::Softplus  => Activation::softplus(x),
+			Activation::Softsign  => Activation::softsign(x),
+			Activation::HardSigmoid => Activation::hard_sigmoid(x),
+			Activation::HardTanh  => Activation::hard_tanh(x),
+			Activation::HardSwish => Activation::hard_swish(x),
+			Activation::Custom(custom) => custom.0.call(x),
 		}
 	}
 
@@ -49,16 +165,94 @@ impl Activation {
         match self {
 			Activation::Linear    => Activation::deriv_linear(x),
 			Activation::Step      => Activation::deriv_step(x),
+			// Pretend the step was linear when going backwards
+			Activation::StepSTE   => Activation::deriv_linear(x),
 			Activation::Sigmoid   => Activation::deriv_sigmoid(x),
 			Activation::HyperTan  => Activation::deriv_hypertan(x),
-			Activation::SiLU      => Activation::deriv_si_lu(x),
+			Activation::SiLU { beta } => Activation::deriv_si_lu(x, *beta),
 			Activation::ReLU      => Activation::deriv_re_lu(x),
-			Activation::LeakyReLU => Activation::deriv_leaky_re_lu(x),
+			Activation::LeakyReLU { slope } => Activation::deriv_leaky_re_lu(x, *slope),
 			#[allow(deprecated)]
-			Activation::Swish     => Activation::deriv_si_lu(x),
+			Activation::Swish     => Activation::deriv_si_lu(x, Activation::DEFAULT_SI_LU_BETA),
+			// The softmax of a single value is always 1
+			Activation::Softmax   => 0.0,
+			Activation::ELU { alpha } => Activation::deriv_elu(x, *alpha),
+			Activation::GELU      => Activation::deriv_gelu(x),
+			Activation::Mish      => Activation::deriv_mish(x),
+			Activation::PReLU { initial_slope } => Activation::deriv_leaky_re_lu(x, *initial_slope),
+			// The derivative of softplus is sigmoid
+			Activation::Softplus  => Activation::sigmoid(x),
+			Activation::Softsign  => Activation::deriv_softsign(x),
+			Activation::HardSigmoid => Activation::deriv_hard_sigmoid(x),
+			Activation::HardTanh  => Activation::deriv_hard_tanh(x),
+			Activation::HardSwish => Activation::deriv_hard_swish(x),
+			Activation::Custom(custom) => custom.0.derivative(x),
+		}
+	}
+
+	/// Whether the derivative of this activation function is 0 everywhere, which stops gradients from flowing through it
+	pub fn blocks_gradients(&self) -> bool {
+		matches!(self, Activation::Step)
+	}
+
+	/// Call the selected activation function with a neuron's learnable parameter.
+	/// Only PReLU has a learnable parameter (its slope). Every other function ignores it
+	pub fn call_with_param(&self, x: f64, param: f64) -> f64 {
+		match self {
+			Activation::PReLU { .. } => Activation::leaky_re_lu(x, param),
+			_ => self.call(x),
+		}
+	}
+
+	/// The derivative of the selected activation function with a neuron's learnable parameter
+	pub fn derivative_with_param(&self, x: f64, param: f64) -> f64 {
+		match self {
+			Activation::PReLU { .. } => Activation::deriv_leaky_re_lu(x, param),
+			_ => self.derivative(x),
+		}
+	}
+
+	/// The derivative of the selected activation function with respect to its learnable parameter.
+	/// This is 0 for functions without one
+	pub fn param_derivative(&self, x: f64) -> f64 {
+		match self {
+			Activation::PReLU { .. } if x < 0.0 => x,
+			_ => 0.0,
+		}
+	}
+
+	/// Whether this activation function has a learnable parameter
+	pub fn has_param(&self) -> bool {
+		matches!(self, Activation::PReLU { .. })
+	}
+
+	/// The value a neuron's learnable parameter starts at
+	pub fn initial_param(&self) -> f64 {
+		match self {
+			Activation::PReLU { initial_slope } => *initial_slope,
+			_ => 0.0,
 		}
 	}
 
+	/// Calculate the softmax of a whole layer's pre-activation values.
+	///
+	/// The largest value is subtracted before exponentiating so large inputs don't overflow.
+	pub fn softmax(values: &[f64]) -> Vec<f64> {
+		let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+		let exps: Vec<f64> = values.iter().map(|x| (x - max).exp()).collect();
+		let sum: f64 = exps.iter().sum();
+
+		exps.iter().map(|x| x / sum).collect()
+	}
+
+	/// The natural log of `softmax`, computed with the log-sum-exp trick so tiny probabilities don't round to ln(0)
+	pub fn log_softmax(values: &[f64]) -> Vec<f64> {
+		let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+		let log_sum = values.iter().map(|x| (x - max).exp()).sum::<f64>().ln();
+
+		values.iter().map(|x| x - max - log_sum).collect()
+	}
+
 	fn deriv_linear(_x: f64) -> f64 {
 	    1.0
 	}
@@ -94,15 +288,14 @@ impl Activation {
 		x.tanh()
 	}
 
-	fn deriv_si_lu(x: f64) -> f64 {
-	    // This calculates x * Activation::deriv_sigmoid(x) + Activation::sigmoid(x) but only calculates the sigmoid once
-	    let sigm = Activation::sigmoid(x);
+	fn deriv_si_lu(x: f64, beta: f64) -> f64 {
+	    // This calculates beta * x * Activation::deriv_sigmoid(beta * x) + Activation::sigmoid(beta * x) but only calculates the sigmoid once
+	    let sigm = Activation::sigmoid(beta * x);
 	    // Use mul_add to reduce error
-	    x.mul_add(sigm * (1.0 - sigm), sigm)
+	    (beta * x).mul_add(sigm * (1.0 - sigm), sigm)
 	}
 	
-	fn si_lu(x: f64) -> f64 {
-		let beta = 1.0;
+	fn si_lu(x: f64, beta: f64) -> f64 {
 		x * Activation::sigmoid(beta * x)
 	}
 
@@ -115,16 +308,86 @@ impl Activation {
 		x.max(0.0)
 	}
 
-	fn deriv_leaky_re_lu(x: f64) -> f64 {
-	    if x < 0.0 {0.15} else {1.0}
+	fn deriv_leaky_re_lu(x: f64, slope: f64) -> f64 {
+	    if x < 0.0 {slope} else {1.0}
 	}
 
-	fn leaky_re_lu(x: f64) -> f64 {
-		x.max(x * 0.15)
+	fn leaky_re_lu(x: f64, slope: f64) -> f64 {
+		if x < 0.0 {x * slope} else {x}
 	}
 
 	fn swish(x: f64) -> f64 {
-		Activation::si_lu(x)
+		Activation::si_lu(x, Activation::DEFAULT_SI_LU_BETA)
+	}
+
+	fn deriv_elu(x: f64, alpha: f64) -> f64 {
+	    if x >= 0.0 {1.0} else {alpha * x.exp()}
+	}
+
+	fn elu(x: f64, alpha: f64) -> f64 {
+		if x >= 0.0 {x} else {alpha * x.exp_m1()}
+	}
+
+	fn deriv_gelu(x: f64) -> f64 {
+	    let scale = (2.0 / std::f64::consts::PI).sqrt();
+	    let tanh = (scale * x.mul_add(0.044715 * x * x, x)).tanh();
+	    // Product rule on 0.5 * x * (1 + tanh(...))
+	    0.5 * (1.0 + tanh) + 0.5 * x * (1.0 - tanh * tanh) * scale * (3.0 * 0.044715 * x).mul_add(x, 1.0)
+	}
+
+	fn gelu(x: f64) -> f64 {
+		let scale = (2.0 / std::f64::consts::PI).sqrt();
+		0.5 * x * (1.0 + (scale * x.mul_add(0.044715 * x * x, x)).tanh())
+	}
+
+	fn deriv_mish(x: f64) -> f64 {
+	    let tanh = Activation::softplus(x).tanh();
+	    // The derivative of softplus is sigmoid
+	    x.mul_add((1.0 - tanh * tanh) * Activation::sigmoid(x), tanh)
+	}
+
+	fn mish(x: f64) -> f64 {
+		x * Activation::softplus(x).tanh()
+	}
+
+	/// ln(1 + e^x), rearranged so e^x can't overflow for large x
+	fn softplus(x: f64) -> f64 {
+		x.max(0.0) + (-x.abs()).exp().ln_1p()
+	}
+
+	fn deriv_softsign(x: f64) -> f64 {
+	    (1.0 + x.abs()).powi(2).recip()
+	}
+
+	fn softsign(x: f64) -> f64 {
+		x / (1.0 + x.abs())
+	}
+
+	// The hard functions use the derivative of the flat side at their corners
+
+	fn deriv_hard_sigmoid(x: f64) -> f64 {
+	    if x > -3.0 && x < 3.0 {1.0 / 6.0} else {0.0}
+	}
+
+	fn hard_sigmoid(x: f64) -> f64 {
+		((x + 3.0) / 6.0).clamp(0.0, 1.0)
+	}
+
+	fn deriv_hard_tanh(x: f64) -> f64 {
+	    if x > -1.0 && x < 1.0 {1.0} else {0.0}
+	}
+
+	fn hard_tanh(x: f64) -> f64 {
+		x.clamp(-1.0, 1.0)
+	}
+
+	fn deriv_hard_swish(x: f64) -> f64 {
+	    // Product rule on x * (x + 3) / 6 in the middle
+	    if x <= -3.0 {0.0} else if x >= 3.0 {1.0} else {(2.0 * x + 3.0) / 6.0}
+	}
+
+	fn hard_swish(x: f64) -> f64 {
+		x * Activation::hard_sigmoid(x)
 	}
 }
 
@@ -159,6 +422,20 @@ mod tests {
     	for i in 1..=100 {
     		assert_eq!(act.call(i as f64), 1.0);
     	}
+
+    	assert!(act.blocks_gradients());
+    }
+
+    #[test]
+    fn step_ste() {
+    	let act = Activation::StepSTE;
+
+    	for i in -100..=100 {
+    		assert_eq!(act.call(i as f64), Activation::Step.call(i as f64));
+    		assert_eq!(act.derivative(i as f64), 1.0);
+    	}
+
+    	assert!(!act.blocks_gradients());
     }
 
     #[test]
@@ -182,7 +459,7 @@ mod tests {
     
     #[test]
     fn si_lu() {
-    	let act = Activation::SiLU;
+    	let act = Activation::si_lu_default();
     	// This is the low point in the dip.
     	let si_lu_point = (-1.278465, -0.278465);
 
@@ -193,6 +470,20 @@ mod tests {
     	assert!(floating_equal(act.call(100.0), 100.0));
     	// Test the swish/SiLU point
     	assert!(floating_equal(act.call(si_lu_point.0), si_lu_point.1));
+    	assert!(floating_equal(act.derivative(si_lu_point.0), 0.0));
+
+    	// A larger beta makes it closer to ReLU
+    	let act = Activation::SiLU { beta: 10.0 };
+    	assert!(floating_equal(act.call(-2.0), 0.0));
+    	assert!(floating_equal(act.call(2.0), 2.0));
+
+    	for beta in [0.5, 1.0, 2.5] {
+    		let act = Activation::SiLU { beta };
+    		for x in [-2.0, -0.5, 0.3, 1.7] {
+    			let numerical = (act.call(x + 1e-6) - act.call(x - 1e-6)) / 2e-6;
+    			assert!(floating_equal(act.derivative(x), numerical));
+    		}
+    	}
     }
 
     #[test]
@@ -209,15 +500,236 @@ mod tests {
 
     #[test]
     fn leaky_re_lu() {
-    	let act = Activation::LeakyReLU;
+    	let act = Activation::LeakyReLU { slope: 0.15 };
 
     	for i in -100..=0 {
     		assert_eq!(act.call(i as f64), (i as f64) * 0.15);
+    		assert_eq!(act.derivative(i as f64 - 0.5), 0.15);
     	}
     	for i in 0..=100 {
     		assert_eq!(act.call(i as f64), i as f64);
+    		assert_eq!(act.derivative(i as f64), 1.0);
     	}
+
+    	let act = Activation::leaky_re_lu_default();
+    	assert_eq!(act.call(-2.0), -2.0 * Activation::DEFAULT_LEAKY_SLOPE);
+
+    	assert_eq!(act, Activation::LeakyReLU { slope: Activation::DEFAULT_LEAKY_SLOPE });
+    	assert_ne!(act, Activation::LeakyReLU { slope: 0.15 });
     }
 
     // Swish just calls SiLU and so doesn't need it's own test
+
+    #[test]
+    fn elu() {
+    	let act = Activation::ELU { alpha: 1.0 };
+
+    	for i in 0..=100 {
+    		assert_eq!(act.call(i as f64), i as f64);
+    		assert_eq!(act.derivative(i as f64), 1.0);
+    	}
+    	assert!(floating_equal(act.call(-1.0), -0.632120));
+    	assert!(floating_equal(act.derivative(-1.0), 0.367879));
+    	// Negative values saturate to -alpha
+    	assert!(floating_equal(act.call(-100.0), -1.0));
+
+    	let act = Activation::ELU { alpha: 0.5 };
+    	assert!(floating_equal(act.call(-100.0), -0.5));
+    	assert!(floating_equal(act.derivative(-1.0), 0.183939));
+    }
+
+    #[test]
+    fn gelu() {
+    	let act = Activation::GELU;
+    	assert_eq!(act.call(0.0), 0.0);
+    	assert_eq!(act.derivative(0.0), 0.5);
+
+    	// Large positive inputs approach the identity and large negative ones approach 0
+    	assert!(floating_equal(act.call(100.0), 100.0));
+    	assert!(floating_equal(act.call(-100.0), 0.0));
+    	assert!(floating_equal(act.derivative(100.0), 1.0));
+
+    	// Compare the derivative with a numerical estimate
+    	for x in [-2.0, -0.5, 0.3, 1.7] {
+    		let numerical = (act.call(x + 1e-6) - act.call(x - 1e-6)) / 2e-6;
+    		assert!(floating_equal(act.derivative(x), numerical));
+    	}
+    }
+
+    #[test]
+    fn mish() {
+    	let act = Activation::Mish;
+    	// This is the low point in the dip.
+    	let mish_point = (-1.192401, -0.308843);
+
+    	assert_eq!(act.call(0.0), 0.0);
+    	assert!(floating_equal(act.call(mish_point.0), mish_point.1));
+    	assert!(floating_equal(act.derivative(mish_point.0), 0.0));
+    	// It isn't monotonic: it dips below the values on both sides
+    	assert!(act.call(-3.0) > mish_point.1);
+    	assert!(act.call(-0.5) > mish_point.1);
+
+    	// Large inputs shouldn't overflow
+    	assert!(floating_equal(act.call(1000.0), 1000.0));
+    	assert!(floating_equal(act.call(-1000.0), 0.0));
+
+    	// It's smooth, so the derivative should match a numerical estimate everywhere
+    	for i in -50..=50 {
+    		let x = i as f64 / 10.0;
+    		let numerical = (act.call(x + 1e-6) - act.call(x - 1e-6)) / 2e-6;
+    		assert!(floating_equal(act.derivative(x), numerical));
+    	}
+    }
+
+    #[test]
+    fn p_re_lu() {
+    	let act = Activation::PReLU { initial_slope: 0.25 };
+
+    	assert_eq!(act.initial_param(), 0.25);
+    	assert_eq!(act.call(-4.0), -1.0);
+    	assert_eq!(act.call_with_param(-4.0, 0.5), -2.0);
+    	assert_eq!(act.call_with_param(4.0, 0.5), 4.0);
+    	assert_eq!(act.derivative_with_param(-4.0, 0.5), 0.5);
+    	assert_eq!(act.param_derivative(-4.0), -4.0);
+    	assert_eq!(act.param_derivative(4.0), 0.0);
+
+    	// Other functions ignore the parameter
+    	assert_eq!(Activation::ReLU.call_with_param(-4.0, 0.5), 0.0);
+    	assert_eq!(Activation::ReLU.param_derivative(-4.0), 0.0);
+    }
+
+    #[test]
+    fn softplus() {
+    	let act = Activation::Softplus;
+    	assert!(floating_equal(act.call(0.0), 2.0_f64.ln()));
+    	assert_eq!(act.derivative(0.0), 0.5);
+
+    	// Large inputs shouldn't overflow
+    	assert_eq!(act.call(1000.0), 1000.0);
+    	assert!(floating_equal(act.call(-1000.0), 0.0));
+    	assert!(act.call(-1000.0) >= 0.0);
+    }
+
+    #[test]
+    fn softsign() {
+    	let act = Activation::Softsign;
+    	assert_eq!(act.call(0.0), 0.0);
+    	assert_eq!(act.call(1.0), 0.5);
+    	assert_eq!(act.call(-3.0), -0.75);
+    	assert_eq!(act.derivative(0.0), 1.0);
+    	assert_eq!(act.derivative(-1.0), 0.25);
+
+    	assert!(act.call(9999.0) > 0.999);
+    	assert!(act.call(-9999.0) < -0.999);
+    }
+
+    #[test]
+    fn hard_sigmoid() {
+    	let act = Activation::HardSigmoid;
+    	assert_eq!(act.call(0.0), 0.5);
+    	assert_eq!(act.derivative(0.0), 1.0 / 6.0);
+
+    	// The clamp boundaries
+    	assert_eq!(act.call(-3.0), 0.0);
+    	assert_eq!(act.call(3.0), 1.0);
+    	assert_eq!(act.derivative(-3.0), 0.0);
+    	assert_eq!(act.derivative(3.0), 0.0);
+    	assert_eq!(act.derivative(-2.999), 1.0 / 6.0);
+    	assert_eq!(act.derivative(2.999), 1.0 / 6.0);
+    	assert_eq!(act.call(-100.0), 0.0);
+    	assert_eq!(act.call(100.0), 1.0);
+    }
+
+    #[test]
+    fn hard_tanh() {
+    	let act = Activation::HardTanh;
+    	assert_eq!(act.call(0.5), 0.5);
+    	assert_eq!(act.derivative(0.5), 1.0);
+
+    	// The clamp boundaries
+    	assert_eq!(act.call(-1.0), -1.0);
+    	assert_eq!(act.call(1.0), 1.0);
+    	assert_eq!(act.derivative(-1.0), 0.0);
+    	assert_eq!(act.derivative(1.0), 0.0);
+    	assert_eq!(act.derivative(-0.999), 1.0);
+    	assert_eq!(act.derivative(0.999), 1.0);
+    	assert_eq!(act.call(-100.0), -1.0);
+    	assert_eq!(act.call(100.0), 1.0);
+    }
+
+    #[test]
+    fn hard_swish() {
+    	let act = Activation::HardSwish;
+    	assert_eq!(act.call(0.0), 0.0);
+    	assert_eq!(act.derivative(0.0), 0.5);
+    	assert_eq!(act.call(-1.5), -0.375);
+
+    	// The clamp boundaries
+    	assert_eq!(act.call(-3.0), 0.0);
+    	assert_eq!(act.call(3.0), 3.0);
+    	assert_eq!(act.derivative(-3.0), 0.0);
+    	assert_eq!(act.derivative(3.0), 1.0);
+    	assert_eq!(act.call(-100.0), 0.0);
+    	assert_eq!(act.call(100.0), 100.0);
+
+    	// The derivative should match a numerical estimate away from the corners
+    	for x in [-2.5, -1.0, 0.3, 2.7] {
+    		let numerical = (act.call(x + 1e-6) - act.call(x - 1e-6)) / 2e-6;
+    		assert!(floating_equal(act.derivative(x), numerical));
+    	}
+    }
+
+    struct Square;
+
+    impl ActivationFn for Square {
+    	fn call(&self, x: f64) -> f64 {
+    		x * x
+    	}
+
+    	fn derivative(&self, x: f64) -> f64 {
+    		2.0 * x
+    	}
+    }
+
+    #[test]
+    fn custom() {
+    	let act = Activation::custom(Square);
+
+    	for i in -100..=100 {
+    		assert_eq!(act.call(i as f64), (i * i) as f64);
+    		assert_eq!(act.derivative(i as f64), (2 * i) as f64);
+    	}
+    	assert_eq!(act.clone().call(3.0), 9.0);
+
+    	assert_eq!(act.clone(), act);
+    	assert_ne!(Activation::custom(Square), act);
+    }
+
+    #[test]
+    fn softmax() {
+    	let outputs = Activation::softmax(&[1.0, 2.0, 3.0]);
+    	assert!(floating_equal(outputs.iter().sum(), 1.0));
+    	assert!(outputs[0] < outputs[1] && outputs[1] < outputs[2]);
+    	assert!(floating_equal(outputs[2], 0.665241));
+
+    	// Huge values shouldn't overflow
+    	let outputs = Activation::softmax(&[1000.0, 1000.0]);
+    	assert!(floating_equal(outputs[0], 0.5));
+    	assert!(floating_equal(outputs[1], 0.5));
+
+    	assert_eq!(Activation::Softmax.call(123.0), 1.0);
+    }
+
+    #[test]
+    fn log_softmax() {
+    	let values = [1.0, 2.0, 3.0];
+    	for (log, output) in Activation::log_softmax(&values).iter().zip(Activation::softmax(&values)) {
+    		assert!(floating_equal(*log, output.ln()));
+    	}
+
+    	// The softmax of the first value rounds to 0, but its log is still finite
+    	let outputs = Activation::log_softmax(&[0.0, 1000.0]);
+    	assert!(floating_equal(outputs[0], -1000.0));
+    	assert!(floating_equal(outputs[1], 0.0));
+    }
 }
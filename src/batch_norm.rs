@@ -0,0 +1,393 @@
+use crate::optimizer::ParamState;
+
+#[cfg(feature = "training")]
+use crate::optimizer::Optimizer;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// How much each training batch moves the running statistics
+#[cfg(feature = "training")]
+const MOMENTUM: f64 = 0.1;
+/// Added to the variance so constant values don't divide by 0
+const EPSILON: f64 = 1e-5;
+
+/// Batch normalization of a layer's weighted sums: <https://arxiv.org/abs/1502.03167>
+///
+/// While training on a batch, every weighted sum is normalized with the mean and variance of that neuron over the batch,
+/// then scaled and shifted by learnable parameters. Running averages of the statistics are used everywhere else.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BatchNorm {
+	scale: Vec<f64>,
+	shift: Vec<f64>,
+	running_mean: Vec<f64>,
+	running_variance: Vec<f64>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	scale_gradient: Vec<f64>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	shift_gradient: Vec<f64>,
+	// The optimizer state for each scale, then each shift. Empty until the first update
+	#[cfg_attr(feature = "serde", serde(skip))]
+	optimizer_state: Vec<ParamState>,
+	// The last sample from `activate` normalized with the running statistics (before scaling and shifting),
+	// and the derivatives with respect to its outputs from `calculate_derivs`
+	#[cfg_attr(feature = "serde", serde(skip))]
+	last_normalized: Vec<f64>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	last_derivs: Vec<f64>,
+}
+
+/// What the backward pass needs from normalizing a batch
+#[cfg(feature = "training")]
+#[derive(Debug, Default)]
+pub struct BatchStatistics {
+	// The weighted sums after normalizing but before scaling and shifting, one Vec per sample
+	normalized: Vec<Vec<f64>>,
+	// 1 / sqrt(variance + epsilon) for each neuron
+	inverse_std: Vec<f64>,
+}
+
+/// Clones copy the parameters, running statistics, and optimizer state. The accumulated gradients start empty
+impl Clone for BatchNorm {
+	fn clone(&self) -> BatchNorm {
+		BatchNorm {
+			optimizer_state: self.optimizer_state.clone(),
+			..self.without_training_state()
+		}
+	}
+}
+
+impl BatchNorm {
+	/// Batch normalization for `size` neurons that starts out as the identity
+	pub fn new(size: usize) -> BatchNorm {
+		BatchNorm {
+			scale: vec![1.0; size],
+			shift: vec![0.0; size],
+			running_mean: vec![0.0; size],
+			running_variance: vec![1.0; size],
+			scale_gradient: vec![0.0; size],
+			shift_gradient: vec![0.0; size],
+			optimizer_state: Vec::new(),
+			last_normalized: Vec::new(),
+			last_derivs: Vec::new(),
+		}
+	}
+
+	/// Normalization with the running statistics is an affine map. Returns `(multiplier, offset)` for a neuron
+	pub fn affine(&self, neuronidx: usize) -> (f64, f64) {
+		let multiplier = self.scale[neuronidx] / (self.running_variance[neuronidx] + EPSILON).sqrt();
+		(multiplier, self.shift[neuronidx] - self.running_mean[neuronidx] * multiplier)
+	}
+
+	/// Append the scales, shifts, running means, and running variances to `parameters`
+	pub fn export_parameters(&self, parameters: &mut Vec<f64>) {
+		for values in [&self.scale, &self.shift, &self.running_mean, &self.running_variance] {
+			parameters.extend(values);
+		}
+	}
+
+	/// Read the values in the order of `export_parameters`. There have to be enough
+	pub fn load_parameters<'a>(&mut self, parameters: &mut impl Iterator<Item = &'a f64>) {
+		for values in [&mut self.scale, &mut self.shift, &mut self.running_mean, &mut self.running_variance] {
+			for value in values.iter_mut() {
+				*value = *parameters.next().expect("Length was already checked. This should not fail. (BatchNorm)");
+			}
+		}
+	}
+
+	/// Normalize one sample's weighted sums in place with the running statistics
+	pub fn normalize(&self, weighted: &mut [f64]) {
+		for (neuronidx, value) in weighted.iter_mut().enumerate() {
+			let (multiplier, offset) = self.affine(neuronidx);
+			*value = *value * multiplier + offset;
+		}
+	}
+
+	/// Normalize one sample's weighted sums in place with the running statistics and cache it for `calculate_derivs`
+	pub fn activate(&mut self, weighted: &mut [f64]) {
+		self.last_normalized = weighted.iter().enumerate()
+			.map(|(neuronidx, value)| (value - self.running_mean[neuronidx]) / (self.running_variance[neuronidx] + EPSILON).sqrt())
+			.collect();
+		self.normalize(weighted);
+	}
+
+	/// Turn the derivatives with respect to the outputs of the last sample from `activate` into derivatives with respect to its weighted sums, in place.
+	/// The derivatives with respect to the outputs are cached for `update_gradients`
+	pub fn calculate_derivs(&mut self, derivs: &mut [f64]) {
+		self.last_derivs = derivs.to_vec();
+		for (neuronidx, deriv) in derivs.iter_mut().enumerate() {
+			*deriv *= self.affine(neuronidx).0;
+		}
+	}
+
+	/// Accumulate the gradients of the scale and shift for the last sample from `calculate_derivs`
+	#[cfg(feature = "training")]
+	pub fn update_gradients(&mut self) {
+		for (neuronidx, (deriv, normalized)) in self.last_derivs.iter().zip(&self.last_normalized).enumerate() {
+			self.scale_gradient[neuronidx] += deriv * normalized;
+			self.shift_gradient[neuronidx] += deriv;
+		}
+	}
+
+	/// Normalize a batch of weighted sums (one Vec per sample) in place with the batch's own statistics, and move the running statistics towards them
+	#[cfg(feature = "training")]
+	pub fn normalize_batch(&mut self, weighted: &mut [Vec<f64>]) -> BatchStatistics {
+		let batch_size = weighted.len() as f64;
+		let mut statistics = BatchStatistics {
+			normalized: weighted.to_vec(),
+			inverse_std: Vec::with_capacity(self.scale.len()),
+		};
+
+		for neuronidx in 0..self.scale.len() {
+			let mean = weighted.iter().map(|sample| sample[neuronidx]).sum::<f64>() / batch_size;
+			let variance = weighted.iter().map(|sample| (sample[neuronidx] - mean).powi(2)).sum::<f64>() / batch_size;
+			let inverse_std = 1.0 / (variance + EPSILON).sqrt();
+
+			for (sample, normalized) in weighted.iter_mut().zip(&mut statistics.normalized) {
+				normalized[neuronidx] = (sample[neuronidx] - mean) * inverse_std;
+				sample[neuronidx] = self.scale[neuronidx] * normalized[neuronidx] + self.shift[neuronidx];
+			}
+			statistics.inverse_std.push(inverse_std);
+
+			// The running variance is unbiased, like in the paper
+			let unbiased_variance = if batch_size > 1.0 { variance * batch_size / (batch_size - 1.0) } else { variance };
+			self.running_mean[neuronidx] += MOMENTUM * (mean - self.running_mean[neuronidx]);
+			self.running_variance[neuronidx] += MOMENTUM * (unbiased_variance - self.running_variance[neuronidx]);
+		}
+
+		statistics
+	}
+
+	/// Turn the derivatives with respect to the normalized outputs of a batch into derivatives with respect to the weighted sums, in place.
+	/// The gradients of the scale and shift are accumulated on the way
+	#[cfg(feature = "training")]
+	pub fn backpropagate_batch(&mut self, derivs: &mut [Vec<f64>], statistics: &BatchStatistics) {
+		let batch_size = derivs.len() as f64;
+
+		for neuronidx in 0..self.scale.len() {
+			let deriv_sum: f64 = derivs.iter().map(|sample| sample[neuronidx]).sum();
+			let deriv_normalized_sum: f64 = derivs.iter().zip(&statistics.normalized)
+				.map(|(sample, normalized)| sample[neuronidx] * normalized[neuronidx])
+				.sum();
+
+			self.shift_gradient[neuronidx] += deriv_sum;
+			self.scale_gradient[neuronidx] += deriv_normalized_sum;
+
+			// Every sample's weighted sum also moves the batch mean and variance, which is where the two sums come from
+			let multiplier = self.scale[neuronidx] * statistics.inverse_std[neuronidx];
+			for (sample, normalized) in derivs.iter_mut().zip(&statistics.normalized) {
+				sample[neuronidx] = multiplier * (sample[neuronidx] - deriv_sum / batch_size - normalized[neuronidx] * deriv_normalized_sum / batch_size);
+			}
+		}
+	}
+
+	/// The sum of the squares of the accumulated gradients
+	#[cfg(feature = "training")]
+	pub fn gradient_norm_squared(&self) -> f64 {
+		self.scale_gradient.iter().chain(&self.shift_gradient).map(|gradient| gradient * gradient).sum()
+	}
+
+	/// Multiply the accumulated gradients by `scale`
+	#[cfg(feature = "training")]
+	pub fn scale_gradients(&mut self, scale: f64) {
+		self.scale_gradient.iter_mut().chain(&mut self.shift_gradient).for_each(|gradient| *gradient *= scale);
+	}
+
+	/// Zero the accumulated gradients without applying them
+	pub fn reset_gradients(&mut self) {
+		self.scale_gradient.iter_mut().chain(&mut self.shift_gradient).for_each(|gradient| *gradient = 0.0);
+	}
+
+	/// Apply the gradients accumulated over `batch_size` samples with an optimizer, then reset them
+	#[cfg(feature = "training")]
+	pub fn apply_gradients(&mut self, learn_rate: f64, batch_size: usize, optimizer: &Optimizer) {
+		let size = self.scale.len();
+		// The state is skipped when saving, so this also covers loaded networks
+		self.optimizer_state.resize(2 * size, ParamState::default());
+		let (scale_states, shift_states) = self.optimizer_state.split_at_mut(size);
+
+		for (neuronidx, (scale_state, shift_state)) in scale_states.iter_mut().zip(shift_states).enumerate() {
+			self.scale[neuronidx] += optimizer.step(scale_state, self.scale_gradient[neuronidx] / batch_size as f64, learn_rate);
+			self.shift[neuronidx] += optimizer.step(shift_state, self.shift_gradient[neuronidx] / batch_size as f64, learn_rate);
+		}
+
+		self.reset_gradients();
+	}
+
+	/// Add the gradients accumulated by another copy of this batch normalization
+	#[cfg(feature = "rayon")]
+	pub fn add_gradients_from(&mut self, source: &BatchNorm) {
+		let pairs = [
+			(&mut self.scale_gradient, &source.scale_gradient),
+			(&mut self.shift_gradient, &source.shift_gradient),
+		];
+		for (gradients, source_gradients) in pairs {
+			for (gradient, source_gradient) in gradients.iter_mut().zip(source_gradients) {
+				*gradient += source_gradient;
+			}
+		}
+	}
+
+	/// Recreate the gradient buffers, which are skipped when saving
+	#[cfg(feature = "serde")]
+	pub fn restore_training_buffers(&mut self) {
+		self.scale_gradient = vec![0.0; self.scale.len()];
+		self.shift_gradient = vec![0.0; self.scale.len()];
+	}
+
+	/// A copy with the same parameters and running statistics but empty gradients and optimizer state
+	pub fn without_training_state(&self) -> BatchNorm {
+		BatchNorm {
+			scale: self.scale.clone(),
+			shift: self.shift.clone(),
+			running_mean: self.running_mean.clone(),
+			running_variance: self.running_variance.clone(),
+			scale_gradient: vec![0.0; self.scale.len()],
+			shift_gradient: vec![0.0; self.scale.len()],
+			optimizer_state: Vec::new(),
+			last_normalized: Vec::new(),
+			last_derivs: Vec::new(),
+		}
+	}
+
+	/// Forget everything the optimizer remembers (like momentum) so training starts fresh
+	pub fn reset_optimizer_state(&mut self) {
+		self.optimizer_state.clear();
+	}
+
+	/// Move the parameters and running statistics towards another batch normalization's: `self = tau * source + (1 - tau) * self`
+	pub fn soft_update_from(&mut self, source: &BatchNorm, tau: f64) {
+		let pairs = [
+			(&mut self.scale, &source.scale),
+			(&mut self.shift, &source.shift),
+			(&mut self.running_mean, &source.running_mean),
+			(&mut self.running_variance, &source.running_variance),
+		];
+		for (values, source_values) in pairs {
+			for (value, source_value) in values.iter_mut().zip(source_values) {
+				*value = tau * source_value + (1.0 - tau) * *value;
+			}
+		}
+	}
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize_batch() {
+		let mut batch_norm = BatchNorm::new(2);
+		let mut batch = vec![vec![1.0, 10.0], vec![3.0, 10.0], vec![5.0, 10.0]];
+		batch_norm.normalize_batch(&mut batch);
+
+		// The first neuron has a mean of 3 and a variance of 8/3. The second one is constant
+		let std = (8.0_f64 / 3.0 + EPSILON).sqrt();
+		for (sample, expected) in batch.iter().zip([-2.0 / std, 0.0, 2.0 / std]) {
+			assert!((sample[0] - expected).abs() < 1e-12);
+			assert_eq!(sample[1], 0.0);
+		}
+
+		// The running statistics moved 10% of the way to the batch's (with the unbiased variance)
+		let (multiplier, offset) = batch_norm.affine(0);
+		let running_variance: f64 = 0.9 + 0.1 * 4.0;
+		assert!((multiplier - 1.0 / (running_variance + EPSILON).sqrt()).abs() < 1e-12);
+		assert!((offset + 0.3 * multiplier).abs() < 1e-12);
+
+		let mut sample = [0.3, 1.0];
+		batch_norm.normalize(&mut sample);
+		assert!(sample[0].abs() < 1e-12);
+	}
+
+	#[test]
+	fn backpropagate() {
+		// The loss is a weighted sum of the outputs, so the derivative with respect to each output is its weight
+		let coefficients = [0.3, -1.2, 2.0];
+		let sample = [0.2, -1.0, 1.5];
+		let loss = |batch_norm: &BatchNorm, sample: &[f64]| {
+			let mut sample = sample.to_vec();
+			batch_norm.normalize(&mut sample);
+			sample.iter().zip(coefficients).map(|(output, coefficient)| output * coefficient).sum::<f64>()
+		};
+
+		let mut batch_norm = BatchNorm::new(3);
+		batch_norm.scale = vec![1.5, 0.5, -0.3];
+		batch_norm.shift = vec![0.1, -0.2, 0.0];
+		batch_norm.running_mean = vec![0.4, -0.1, 0.7];
+		batch_norm.running_variance = vec![2.0, 0.5, 1.2];
+
+		batch_norm.activate(&mut sample.clone());
+		let mut derivs = coefficients.to_vec();
+		batch_norm.calculate_derivs(&mut derivs);
+		batch_norm.update_gradients();
+
+		let epsilon = 1e-6;
+		for idx in 0..3 {
+			let mut up = sample;
+			up[idx] += epsilon;
+			let mut down = sample;
+			down[idx] -= epsilon;
+			assert!((derivs[idx] - (loss(&batch_norm, &up) - loss(&batch_norm, &down)) / (2.0 * epsilon)).abs() < 1e-6);
+
+			let mut up = batch_norm.clone();
+			up.scale[idx] += epsilon;
+			let mut down = batch_norm.clone();
+			down.scale[idx] -= epsilon;
+			assert!((batch_norm.scale_gradient[idx] - (loss(&up, &sample) - loss(&down, &sample)) / (2.0 * epsilon)).abs() < 1e-6);
+
+			let mut up = batch_norm.clone();
+			up.shift[idx] += epsilon;
+			let mut down = batch_norm.clone();
+			down.shift[idx] -= epsilon;
+			assert!((batch_norm.shift_gradient[idx] - (loss(&up, &sample) - loss(&down, &sample)) / (2.0 * epsilon)).abs() < 1e-6);
+		}
+	}
+
+	#[test]
+	fn backpropagate_batch() {
+		// The loss is a weighted sum of the outputs, so the derivative with respect to each output is its weight
+		let coefficients = [[0.3, -1.2], [2.0, 0.5], [-0.7, 0.1], [1.1, -0.4]];
+		let batch = vec![vec![0.2, -1.0], vec![1.5, 0.3], vec![-0.4, 2.2], vec![0.9, 0.8]];
+		let loss = |batch_norm: &BatchNorm, batch: &[Vec<f64>]| {
+			let mut batch = batch.to_vec();
+			batch_norm.clone().normalize_batch(&mut batch);
+			batch.iter().flatten().zip(coefficients.iter().flatten()).map(|(output, coefficient)| output * coefficient).sum::<f64>()
+		};
+
+		let mut batch_norm = BatchNorm::new(2);
+		batch_norm.scale = vec![1.5, 0.5];
+		batch_norm.shift = vec![0.1, -0.2];
+
+		let mut derivs: Vec<Vec<f64>> = coefficients.iter().map(|sample| sample.to_vec()).collect();
+		let statistics = batch_norm.clone().normalize_batch(&mut batch.clone());
+		batch_norm.backpropagate_batch(&mut derivs, &statistics);
+
+		let epsilon = 1e-6;
+		for sampleidx in 0..4 {
+			for neuronidx in 0..2 {
+				let mut up = batch.clone();
+				up[sampleidx][neuronidx] += epsilon;
+				let mut down = batch.clone();
+				down[sampleidx][neuronidx] -= epsilon;
+
+				let numerical = (loss(&batch_norm, &up) - loss(&batch_norm, &down)) / (2.0 * epsilon);
+				assert!((derivs[sampleidx][neuronidx] - numerical).abs() < 1e-6);
+			}
+		}
+
+		for neuronidx in 0..2 {
+			let mut up = batch_norm.clone();
+			up.scale[neuronidx] += epsilon;
+			let mut down = batch_norm.clone();
+			down.scale[neuronidx] -= epsilon;
+			assert!((batch_norm.scale_gradient[neuronidx] - (loss(&up, &batch) - loss(&down, &batch)) / (2.0 * epsilon)).abs() < 1e-6);
+
+			let mut up = batch_norm.clone();
+			up.shift[neuronidx] += epsilon;
+			let mut down = batch_norm.clone();
+			down.shift[neuronidx] -= epsilon;
+			assert!((batch_norm.shift_gradient[neuronidx] - (loss(&up, &batch) - loss(&down, &batch)) / (2.0 * epsilon)).abs() < 1e-6);
+		}
+	}
+}
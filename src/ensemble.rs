@@ -0,0 +1,142 @@
+use crate::network::{argmax, NeuralNetwork};
+
+/// Several networks with the same input and output sizes that make predictions together.
+///
+/// Networks trained with different seeds make different mistakes, so averaging their outputs is usually more accurate than any one of them
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+	networks: Vec<NeuralNetwork>,
+}
+
+impl Ensemble {
+	/// Create an ensemble from at least one network. Every network needs the same input size and output size
+	pub fn new(networks: Vec<NeuralNetwork>) -> crate::error::Result<Ensemble> {
+		let Some(first) = networks.first() else {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "networks".to_owned(),
+					reason: "at least one network is needed".to_owned()
+				}.into()
+			);
+		};
+
+		if networks.iter().any(|network| network.input_size() != first.input_size() || network.output_size() != first.output_size()) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "networks".to_owned(),
+					reason: "every network needs the same input size and output size".to_owned()
+				}.into()
+			);
+		}
+
+		Ok(Ensemble { networks })
+	}
+
+	/// The networks in the ensemble
+	pub fn networks(&self) -> &[NeuralNetwork] {
+		&self.networks
+	}
+
+	/// Run every network and average their outputs
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to every network
+	pub fn predict(&self, inputs: &[f64]) -> crate::error::Result<Vec<f64>> {
+		let mut outputs = self.networks[0].predict(inputs)?;
+		for network in &self.networks[1..] {
+			for (output, member_output) in outputs.iter_mut().zip(network.predict(inputs)?) {
+				*output += member_output;
+			}
+		}
+
+		outputs.iter_mut().for_each(|output| *output /= self.networks.len() as f64);
+		Ok(outputs)
+	}
+
+	/// The index of the largest averaged output (the predicted class). Ties resolve to the lowest index.
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to every network
+	pub fn predict_class(&self, inputs: &[f64]) -> crate::error::Result<usize> {
+		Ok(argmax(&self.predict(inputs)?))
+	}
+
+	/// The class predicted by the most networks. Ties resolve to the lowest index.
+	///
+	/// Unlike `predict_class`, one very confident network can't outweigh the others
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to every network
+	pub fn vote(&self, inputs: &[f64]) -> crate::error::Result<usize> {
+		let mut votes = Vec::new();
+		for network in &self.networks {
+			let outputs = network.predict(inputs)?;
+			votes.resize(outputs.len(), 0.0);
+			votes[argmax(&outputs)] += 1.0;
+		}
+
+		Ok(argmax(&votes))
+	}
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+	use super::*;
+	use crate::activation::Activation;
+	use crate::error::NeuralibError;
+
+	#[test]
+	fn predict() {
+		let networks: Vec<NeuralNetwork> = (0..2)
+			.map(|seed| NeuralNetwork::new_seeded(&[4, 3], 2, vec![Activation::ReLU, Activation::Softmax], seed).unwrap())
+			.collect();
+		let ensemble = Ensemble::new(networks.clone()).unwrap();
+		assert_eq!(ensemble.networks().len(), 2);
+
+		let inputs = [0.7, -1.3];
+		let first = networks[0].predict(&inputs).unwrap();
+		let second = networks[1].predict(&inputs).unwrap();
+		let outputs = ensemble.predict(&inputs).unwrap();
+		for ((output, a), b) in outputs.iter().zip(&first).zip(&second) {
+			assert!((output - (a + b) / 2.0).abs() < 1e-12);
+		}
+		assert_eq!(ensemble.predict_class(&inputs).unwrap(), argmax(&outputs));
+
+		assert!(ensemble.predict(&[1.0]).is_err());
+	}
+
+	#[test]
+	fn vote() {
+		// The outputs are just the biases, so each network always predicts the class with the largest bias
+		let network = |biases: Vec<f64>| {
+			let mut network = NeuralNetwork::new(&[3], 1, vec![Activation::Linear]).unwrap();
+			network.set_layer_weights(0, vec![vec![0.0]; 3], biases).unwrap();
+			network
+		};
+		let ensemble = Ensemble::new(vec![
+			network(vec![0.0, 1.0, 0.0]),
+			network(vec![0.0, 1.0, 0.0]),
+			network(vec![0.0, 0.0, 10.0]),
+		]).unwrap();
+
+		// The confident third network wins the average but loses the vote
+		assert_eq!(ensemble.predict_class(&[1.0]).unwrap(), 2);
+		assert_eq!(ensemble.vote(&[1.0]).unwrap(), 1);
+	}
+
+	#[test]
+	fn invalid() {
+		assert!(matches!(Ensemble::new(Vec::new()), Err(NeuralibError::InvalidArgument(_))));
+
+		let network = NeuralNetwork::new(&[3, 2], 4, vec![Activation::ReLU, Activation::Linear]).unwrap();
+		let other_input = NeuralNetwork::new(&[3, 2], 5, vec![Activation::ReLU, Activation::Linear]).unwrap();
+		let other_output = NeuralNetwork::new(&[3, 3], 4, vec![Activation::ReLU, Activation::Linear]).unwrap();
+		assert!(matches!(Ensemble::new(vec![network.clone(), other_input]), Err(NeuralibError::InvalidArgument(_))));
+		assert!(matches!(Ensemble::new(vec![network.clone(), other_output]), Err(NeuralibError::InvalidArgument(_))));
+
+		// Different hidden layers are fine
+		let other_hidden = NeuralNetwork::new(&[7, 2], 4, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		assert!(Ensemble::new(vec![network, other_hidden]).is_ok());
+	}
+}
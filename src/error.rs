@@ -1,7 +1,28 @@
 use std::error;
 use std::fmt;
 
-pub type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+pub type Result<T> = std::result::Result<T, NeuralibError>;
+
+/// Every error neuralib can return. Match on it to handle specific failures
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum NeuralibError {
+	InputSize(InputSizeError),
+	NoLayers(NoLayersError),
+	LayerActivationMismatch(LayerActivationMismatchError),
+	InvalidArgument(InvalidArgumentError),
+	UntrainableActivation(UntrainableActivationError),
+	ZeroSize(ZeroSizeError),
+	UnsupportedVersion(UnsupportedVersionError),
+	InvalidSave(InvalidSaveError),
+	Io(std::io::Error),
+	#[cfg(feature = "serde")]
+	Json(serde_json::Error),
+	#[cfg(feature = "csv")]
+	Csv(csv::Error),
+	#[cfg(feature = "idx")]
+	InvalidIdx(InvalidIdxError),
+}
 
 
 #[derive(Debug, Clone)]
@@ -14,6 +35,68 @@ pub struct InputSizeError {
 #[derive(Debug, Clone)]
 pub struct NoLayersError {}
 
+#[derive(Debug, Clone)]
+pub struct LayerActivationMismatchError {
+	pub num_layers: usize,
+	pub num_activations: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidArgumentError {
+	pub argument: String,
+	pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct UntrainableActivationError {
+	pub layer: usize,
+}
+
+/// A layer (or the input) was given a size of 0. `layer` is `None` for the input
+#[derive(Debug, Clone)]
+pub struct ZeroSizeError {
+	pub layer: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnsupportedVersionError {
+	pub found: u32,
+	pub supported: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidSaveError {
+	pub reason: String,
+}
+
+#[cfg(feature = "idx")]
+#[derive(Debug, Clone)]
+pub struct InvalidIdxError {
+	pub reason: String,
+}
+
+
+impl fmt::Display for NeuralibError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NeuralibError::InputSize(err) => err.fmt(f),
+            NeuralibError::NoLayers(err) => err.fmt(f),
+            NeuralibError::LayerActivationMismatch(err) => err.fmt(f),
+            NeuralibError::InvalidArgument(err) => err.fmt(f),
+            NeuralibError::UntrainableActivation(err) => err.fmt(f),
+            NeuralibError::ZeroSize(err) => err.fmt(f),
+            NeuralibError::UnsupportedVersion(err) => err.fmt(f),
+            NeuralibError::InvalidSave(err) => err.fmt(f),
+            NeuralibError::Io(err) => err.fmt(f),
+            #[cfg(feature = "serde")]
+            NeuralibError::Json(err) => err.fmt(f),
+            #[cfg(feature = "csv")]
+            NeuralibError::Csv(err) => err.fmt(f),
+            #[cfg(feature = "idx")]
+            NeuralibError::InvalidIdx(err) => err.fmt(f),
+        }
+    }
+}
 
 impl fmt::Display for InputSizeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -27,5 +110,156 @@ impl fmt::Display for NoLayersError {
     }
 }
 
+impl fmt::Display for LayerActivationMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Every layer needs an activation function. Got {} layer sizes, but {} activation functions.", self.num_layers, self.num_activations)
+    }
+}
+
+impl fmt::Display for InvalidArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid value for {}: {}.", self.argument, self.reason)
+    }
+}
+
+impl fmt::Display for UntrainableActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Layer {} uses an activation function with a derivative of 0 everywhere (like Step), so the network can't learn. Use StepSTE to train step activations.", self.layer)
+    }
+}
+
+impl fmt::Display for ZeroSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.layer {
+            Some(layer) => write!(f, "Layer {layer} has a size of 0. Every layer needs at least one neuron."),
+            None => write!(f, "The input size is 0. Networks need at least one input."),
+        }
+    }
+}
+
+impl fmt::Display for UnsupportedVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unsupported save format version {}. This version of neuralib supports up to version {}.", self.found, self.supported)
+    }
+}
+
+impl fmt::Display for InvalidSaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Not a valid neuralib save: {}.", self.reason)
+    }
+}
+
+#[cfg(feature = "idx")]
+impl fmt::Display for InvalidIdxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Not a valid IDX file: {}.", self.reason)
+    }
+}
+
+impl error::Error for NeuralibError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NeuralibError::InputSize(err) => Some(err),
+            NeuralibError::NoLayers(err) => Some(err),
+            NeuralibError::LayerActivationMismatch(err) => Some(err),
+            NeuralibError::InvalidArgument(err) => Some(err),
+            NeuralibError::UntrainableActivation(err) => Some(err),
+            NeuralibError::ZeroSize(err) => Some(err),
+            NeuralibError::UnsupportedVersion(err) => Some(err),
+            NeuralibError::InvalidSave(err) => Some(err),
+            NeuralibError::Io(err) => Some(err),
+            #[cfg(feature = "serde")]
+            NeuralibError::Json(err) => Some(err),
+            #[cfg(feature = "csv")]
+            NeuralibError::Csv(err) => Some(err),
+            #[cfg(feature = "idx")]
+            NeuralibError::InvalidIdx(err) => Some(err),
+        }
+    }
+}
+
 impl error::Error for InputSizeError {}
 impl error::Error for NoLayersError {}
+impl error::Error for LayerActivationMismatchError {}
+impl error::Error for InvalidArgumentError {}
+impl error::Error for UntrainableActivationError {}
+impl error::Error for ZeroSizeError {}
+impl error::Error for UnsupportedVersionError {}
+impl error::Error for InvalidSaveError {}
+#[cfg(feature = "idx")]
+impl error::Error for InvalidIdxError {}
+
+impl From<InputSizeError> for NeuralibError {
+    fn from(err: InputSizeError) -> Self {
+        NeuralibError::InputSize(err)
+    }
+}
+
+impl From<NoLayersError> for NeuralibError {
+    fn from(err: NoLayersError) -> Self {
+        NeuralibError::NoLayers(err)
+    }
+}
+
+impl From<LayerActivationMismatchError> for NeuralibError {
+    fn from(err: LayerActivationMismatchError) -> Self {
+        NeuralibError::LayerActivationMismatch(err)
+    }
+}
+
+impl From<InvalidArgumentError> for NeuralibError {
+    fn from(err: InvalidArgumentError) -> Self {
+        NeuralibError::InvalidArgument(err)
+    }
+}
+
+impl From<UntrainableActivationError> for NeuralibError {
+    fn from(err: UntrainableActivationError) -> Self {
+        NeuralibError::UntrainableActivation(err)
+    }
+}
+
+impl From<ZeroSizeError> for NeuralibError {
+    fn from(err: ZeroSizeError) -> Self {
+        NeuralibError::ZeroSize(err)
+    }
+}
+
+impl From<UnsupportedVersionError> for NeuralibError {
+    fn from(err: UnsupportedVersionError) -> Self {
+        NeuralibError::UnsupportedVersion(err)
+    }
+}
+
+impl From<InvalidSaveError> for NeuralibError {
+    fn from(err: InvalidSaveError) -> Self {
+        NeuralibError::InvalidSave(err)
+    }
+}
+
+impl From<std::io::Error> for NeuralibError {
+    fn from(err: std::io::Error) -> Self {
+        NeuralibError::Io(err)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for NeuralibError {
+    fn from(err: serde_json::Error) -> Self {
+        NeuralibError::Json(err)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl From<csv::Error> for NeuralibError {
+    fn from(err: csv::Error) -> Self {
+        NeuralibError::Csv(err)
+    }
+}
+
+#[cfg(feature = "idx")]
+impl From<InvalidIdxError> for NeuralibError {
+    fn from(err: InvalidIdxError) -> Self {
+        NeuralibError::InvalidIdx(err)
+    }
+}
@@ -0,0 +1,179 @@
+//! Weight initialization for neuralib
+//!
+//! This module provides the strategies a neural network can use to pick its starting weights.
+
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// How the weights (or biases) of new neurons are initialized
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Init {
+	/// He initialization: <https://arxiv.org/abs/1502.01852>
+	///
+	/// Weights are drawn from a normal distribution with a variance of `2 / fan_in`. This works well for ReLU-like activations.
+	/// `VarianceScaling` can do the same with the fan out or the average fan
+	#[default]
+	He,
+	/// The uniform version of He initialization, like PyTorch's `kaiming_uniform_` with its default arguments
+	///
+	/// Weights are drawn uniformly from `[-bound, bound)` with `bound = sqrt(6 / fan_in)`, which also gives a variance of `2 / fan_in`
+	HeUniform,
+	/// Xavier (Glorot) initialization: <https://proceedings.mlr.press/v9/glorot10a.html>
+	///
+	/// Weights are drawn from a normal distribution with a variance of `2 / (fan_in + fan_out)`. This works well for sigmoid and tanh
+	Xavier,
+	/// Weights are drawn uniformly from `[low, high)`
+	Uniform {
+		low: f64,
+		high: f64,
+	},
+	/// Variance scaling with an explicit fan: weights are drawn with a variance of `scale / fan`, where `mode` picks the fan.
+	///
+	/// The weights come from a normal distribution, or from a uniform distribution over `[-sqrt(3 * scale / fan), sqrt(3 * scale / fan))` if `uniform` is true.
+	/// `He` is `scale: 2.0, mode: FanMode::FanIn, uniform: false`, `HeUniform` is the same with `uniform: true`,
+	/// and `Xavier` is `scale: 1.0, mode: FanMode::FanAvg, uniform: false`. `scale` must be finite and positive
+	VarianceScaling {
+		scale: f64,
+		mode: FanMode,
+		uniform: bool,
+	},
+	/// Every weight is 0. The neurons in a layer will all learn the same thing, so this is mostly useful for tests and output layers
+	Zeros,
+	/// Every weight is `value`
+	Constant {
+		value: f64
+	},
+}
+
+/// Which fan divides the variance in `Init::VarianceScaling`.
+///
+/// Keeping the variance at `scale / fan_in` keeps the size of the outputs the same from layer to layer on the forward pass,
+/// while `scale / fan_out` does the same for the gradients on the backward pass. `FanAvg` is a compromise between the two,
+/// which helps when the layer sizes change a lot (like at a bottleneck or the output layer)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FanMode {
+	/// How many inputs each neuron has
+	#[default]
+	FanIn,
+	/// How many neurons are in the layer
+	FanOut,
+	/// The average of the two
+	FanAvg,
+}
+
+impl FanMode {
+	/// The fan for a neuron with `fan_in` inputs in a layer of `fan_out` neurons
+	pub fn fan(&self, fan_in: usize, fan_out: usize) -> f64 {
+		match self {
+			FanMode::FanIn => fan_in as f64,
+			FanMode::FanOut => fan_out as f64,
+			FanMode::FanAvg => (fan_in + fan_out) as f64 / 2.0,
+		}
+	}
+}
+
+impl Init {
+	/// Return an `InvalidArgumentError` for `argument` if the parameters can't be sampled from.
+	/// `VarianceScaling` needs a finite, positive `scale`
+	pub(crate) fn validate(&self, argument: &str) -> crate::error::Result<()> {
+		if let Init::VarianceScaling { scale, .. } = self && !(scale.is_finite() && *scale > 0.0) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: argument.to_owned(),
+					reason: format!("the variance scale must be finite and positive, not {scale}")
+				}.into()
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Draw the weights for one neuron
+	///
+	/// Arguments:
+	///
+	/// * `fan_in` - How many inputs the neuron has
+	/// * `fan_out` - How many neurons are in the neuron's layer
+	/// * `rng` - The random number generator to draw the weights with
+	pub(crate) fn weights(&self, fan_in: usize, fan_out: usize, rng: &mut impl Rng) -> Vec<f64> {
+		(0..fan_in).map(|_| self.sample(fan_in, fan_out, rng)).collect()
+	}
+
+	/// Draw a single value, like a bias
+	pub(crate) fn sample(&self, fan_in: usize, fan_out: usize, rng: &mut impl Rng) -> f64 {
+		match self {
+			Init::He => Init::variance_scaling(2.0, FanMode::FanIn, false, fan_in, fan_out, rng),
+			Init::HeUniform => Init::variance_scaling(2.0, FanMode::FanIn, true, fan_in, fan_out, rng),
+			Init::Xavier => Init::variance_scaling(1.0, FanMode::FanAvg, false, fan_in, fan_out, rng),
+			Init::VarianceScaling { scale, mode, uniform } => Init::variance_scaling(*scale, *mode, *uniform, fan_in, fan_out, rng),
+			Init::Uniform { low, high } => low + (high - low) * rng.random::<f64>(),
+			Init::Zeros => 0.0,
+			Init::Constant { value } => *value,
+		}
+	}
+
+	fn variance_scaling(scale: f64, mode: FanMode, uniform: bool, fan_in: usize, fan_out: usize, rng: &mut impl Rng) -> f64 {
+		let variance = scale / mode.fan(fan_in, fan_out);
+		if uniform {
+			// A uniform distribution over [-bound, bound) has a variance of bound^2 / 3
+			let bound = (3.0 * variance).sqrt();
+			rng.random_range(-bound..bound)
+		} else {
+			variance.sqrt() * rng.sample::<f64, _>(StandardNormal)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mean_and_variance(values: &[f64]) -> (f64, f64) {
+		let mean = values.iter().sum::<f64>() / values.len() as f64;
+		let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64;
+		(mean, variance)
+	}
+
+	#[test]
+	fn variance() {
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(1);
+
+		let (_, he) = mean_and_variance(&Init::He.weights(20000, 10, &mut rng));
+		assert!((he / (2.0 / 20000.0) - 1.0).abs() < 0.05);
+
+		let he_uniform = Init::HeUniform.weights(20000, 10, &mut rng);
+		let bound = (6.0 / 20000.0_f64).sqrt();
+		assert!(he_uniform.iter().all(|weight| (-bound..bound).contains(weight)));
+		// The weights use most of the range
+		assert!(he_uniform.iter().any(|weight| *weight > 0.99 * bound) && he_uniform.iter().any(|weight| *weight < -0.99 * bound));
+		let (_, he_uniform) = mean_and_variance(&he_uniform);
+		assert!((he_uniform / (2.0 / 20000.0) - 1.0).abs() < 0.05);
+
+		let (_, xavier) = mean_and_variance(&Init::Xavier.weights(20000, 10000, &mut rng));
+		assert!((xavier / (2.0 / 30000.0) - 1.0).abs() < 0.05);
+
+		let uniform = Init::Uniform { low: -1.0, high: 3.0 }.weights(20000, 10, &mut rng);
+		assert!(uniform.iter().all(|weight| (-1.0..3.0).contains(weight)));
+		let (mean, variance) = mean_and_variance(&uniform);
+		assert!((mean - 1.0).abs() < 0.05);
+		assert!((variance / (16.0 / 12.0) - 1.0).abs() < 0.05);
+
+		// The variance follows the chosen fan
+		for (mode, fan) in [(FanMode::FanIn, 20000.0), (FanMode::FanOut, 5000.0), (FanMode::FanAvg, 12500.0)] {
+			let (_, variance) = mean_and_variance(&Init::VarianceScaling { scale: 2.0, mode, uniform: false }.weights(20000, 5000, &mut rng));
+			assert!((variance / (2.0 / fan) - 1.0).abs() < 0.05);
+		}
+		let uniform = Init::VarianceScaling { scale: 1.0, mode: FanMode::FanOut, uniform: true }.weights(20000, 300, &mut rng);
+		let bound = (3.0 / 300.0_f64).sqrt();
+		assert!(uniform.iter().all(|weight| (-bound..bound).contains(weight)));
+		let (_, variance) = mean_and_variance(&uniform);
+		assert!((variance / (1.0 / 300.0) - 1.0).abs() < 0.05);
+
+		assert_eq!(Init::Zeros.weights(3, 1, &mut rng), vec![0.0; 3]);
+		assert_eq!(Init::Constant { value: 0.5 }.weights(3, 1, &mut rng), vec![0.5; 3]);
+	}
+}
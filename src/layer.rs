@@ -1,22 +1,81 @@
 use crate::neuron::Neuron;
 use crate::activation::Activation;
+use crate::batch_norm::BatchNorm;
+use crate::layer_norm::LayerNorm;
+
+#[cfg(feature = "training")]
+use {
+	crate::batch_norm::BatchStatistics,
+	crate::layer_norm::LayerStatistics,
+	crate::init::Init,
+	crate::loss::Loss,
+	crate::optimizer::{Optimizer, Regularization},
+	rand::Rng,
+};
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Layer {
 	neurons: Vec<Neuron>,
 	neuron_count: usize,
 	input_size: usize,
+	// Frozen layers don't accumulate or apply their gradients
+	#[cfg_attr(feature = "serde", serde(default))]
+	frozen: bool,
+	// The probability of dropping each output while training
+	#[cfg_attr(feature = "serde", serde(default))]
+	dropout: f64,
+	// Normalizes the weighted sums before they are activated
+	#[cfg_attr(feature = "serde", serde(default))]
+	batch_norm: Option<BatchNorm>,
+	// Normalizes the weighted sums of each sample across the layer (after batch normalization)
+	#[cfg_attr(feature = "serde", serde(default))]
+	layer_norm: Option<LayerNorm>,
+}
+
+/// Everything from running a batch through a layer with `activate_batch_training` that is needed to backpropagate it.
+/// Each Vec has one entry per sample
+#[cfg(feature = "training")]
+#[derive(Debug)]
+pub struct BatchCache {
+	inputs: Vec<Vec<f64>>,
+	// The values the activation function got (after batch normalization)
+	pre_activations: Vec<Vec<f64>>,
+	outputs: Vec<Vec<f64>>,
+	dropout_scales: Vec<Vec<f64>>,
+	statistics: Option<BatchStatistics>,
+	layer_statistics: Vec<LayerStatistics>,
+}
+
+#[cfg(feature = "training")]
+impl BatchCache {
+	/// The outputs of the layer (after dropout), which are the inputs of the next layer
+	pub fn outputs(&self) -> &[Vec<f64>] {
+		&self.outputs
+	}
 }
 
 impl Layer {
-	pub fn new(input_size: usize, layer_size: usize, activation: Activation) -> Layer {
+	#[cfg(feature = "training")]
+	pub fn new(input_size: usize, layer_size: usize, activation: Activation, weight_init: &Init, bias_init: &Init, rng: &mut impl Rng) -> Layer {
+		Layer::new_per_neuron(input_size, vec![activation; layer_size], weight_init, bias_init, rng)
+	}
+
+	/// Create a layer with one neuron for each activation function
+	#[cfg(feature = "training")]
+	pub fn new_per_neuron(input_size: usize, activations: Vec<Activation>, weight_init: &Init, bias_init: &Init, rng: &mut impl Rng) -> Layer {
+		let layer_size = activations.len();
 		Layer {
 			neuron_count: layer_size,
-			neurons: (0..layer_size).map(|_| Neuron::new(input_size, activation.clone())).collect(),
+			neurons: activations.into_iter().map(|activation| Neuron::new(input_size, activation, weight_init, bias_init, layer_size, rng)).collect(),
 			input_size,
+			frozen: false,
+			dropout: 0.0,
+			batch_norm: None,
+			layer_norm: None,
 		}
 	}
 
@@ -30,29 +89,620 @@ impl Layer {
             );
         }
 
+        if self.batch_norm.is_some() || self.layer_norm.is_some() {
+        	let mut weighted: Vec<f64> = self.neurons.iter_mut().map(|neuron| neuron.weigh(inputs)).collect();
+        	if let Some(batch_norm) = &mut self.batch_norm {
+        		batch_norm.activate(&mut weighted);
+        	}
+        	if let Some(layer_norm) = &mut self.layer_norm {
+        		layer_norm.activate(&mut weighted);
+        	}
+        	if !self.is_softmax() {
+        		return Ok(self.neurons.iter_mut().zip(weighted).map(|(neuron, value)| neuron.activate_weighed(value)).collect());
+        	}
+
+        	let outputs = Activation::softmax(&weighted);
+        	for (neuron, output) in self.neurons.iter_mut().zip(&outputs) {
+        		neuron.set_output(*output);
+        	}
+        	return Ok(outputs);
+        }
+
+        if self.is_softmax() {
+        	let weighted: Vec<f64> = self.neurons.iter_mut().map(|neuron| neuron.weigh(inputs)).collect();
+        	let outputs = Activation::softmax(&weighted);
+        	for (neuron, output) in self.neurons.iter_mut().zip(&outputs) {
+        		neuron.set_output(*output);
+        	}
+        	return Ok(outputs);
+        }
+
         Ok(self.neurons.iter_mut()
         	.map(|neuron| neuron.activate(inputs).expect("Length was already checked. This should not fail. (Layer)"))
         	.collect())
 	}
 
-	pub fn update_gradients_output(&mut self, expected_outputs: &[f64]) {
-		for (neuron, output) in self.neurons.iter_mut().zip(expected_outputs) {
-			neuron.calculate_deriv_output(output);
-			neuron.update_gradients();
+	/// Run a whole batch (one Vec of inputs per sample) for training. Batch normalization uses the batch's statistics and dropout is applied.
+	/// The input sizes have to be checked first
+	#[cfg(feature = "training")]
+	pub fn activate_batch_training(&mut self, inputs: Vec<Vec<f64>>, rng: &mut impl Rng) -> BatchCache {
+		let mut pre_activations: Vec<Vec<f64>> = inputs.iter().map(|input| self.weighted_sums(input)).collect();
+		// Frozen layers keep their running statistics and normalize with them, like outside of training
+		let statistics = match &mut self.batch_norm {
+			Some(batch_norm) if !self.frozen => Some(batch_norm.normalize_batch(&mut pre_activations)),
+			Some(batch_norm) => {
+				pre_activations.iter_mut().for_each(|pre_activation| batch_norm.normalize(pre_activation));
+				None
+			},
+			None => None,
+		};
+		let layer_statistics = match &self.layer_norm {
+			Some(layer_norm) => pre_activations.iter_mut().map(|pre_activation| layer_norm.normalize(pre_activation)).collect(),
+			None => Vec::new(),
+		};
+
+		let mut outputs = Vec::with_capacity(inputs.len());
+		let mut dropout_scales = Vec::with_capacity(inputs.len());
+		for pre_activation in &pre_activations {
+			let mut output = if self.is_softmax() {
+				Activation::softmax(pre_activation)
+			} else {
+				self.neurons.iter().zip(pre_activation).map(|(neuron, value)| neuron.activation_output(*value)).collect()
+			};
+
+			let scales: Vec<f64> = if self.dropout == 0.0 { vec![1.0; self.neuron_count] } else { (0..self.neuron_count).map(|_| self.dropout_scale(rng)).collect() };
+			output.iter_mut().zip(&scales).for_each(|(value, scale)| *value *= scale);
+
+			outputs.push(output);
+			dropout_scales.push(scales);
+		}
+
+		BatchCache { inputs, pre_activations, outputs, dropout_scales, statistics, layer_statistics }
+	}
+
+	/// Backpropagate a batch from the derivatives of the loss with respect to the layer's outputs, accumulating the gradients.
+	/// Returns the derivatives with respect to the inputs
+	#[cfg(feature = "training")]
+	pub fn backpropagate_batch(&mut self, cache: &BatchCache, output_derivs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+		let mut pre_activation_derivs = Vec::with_capacity(output_derivs.len());
+
+		for ((output_deriv, scales), pre_activation) in output_derivs.iter().zip(&cache.dropout_scales).zip(&cache.pre_activations) {
+			// Dropped outputs didn't affect the loss
+			let output_deriv: Vec<f64> = output_deriv.iter().zip(scales).map(|(deriv, scale)| deriv * scale).collect();
+
+			if self.is_softmax() {
+				let outputs = Activation::softmax(pre_activation);
+				let softmax_sum: f64 = outputs.iter().zip(&output_deriv).map(|(output, deriv)| output * deriv).sum();
+				pre_activation_derivs.push(outputs.iter().zip(&output_deriv).map(|(output, deriv)| output * (deriv - softmax_sum)).collect());
+				continue;
+			}
+
+			pre_activation_derivs.push(self.neurons.iter_mut().zip(&output_deriv).zip(pre_activation)
+				.map(|((neuron, deriv), value)| {
+					// The activation parameter only depends on this sample, so its gradient can be added now
+					let param_gradient = deriv * neuron.activation_param_deriv(*value);
+					neuron.add_gradients(&[], 0.0, param_gradient);
+					deriv * neuron.activation_deriv(*value)
+				})
+				.collect());
+		}
+
+		self.backpropagate_batch_pre_activation(cache, pre_activation_derivs)
+	}
+
+	/// Backpropagate a batch from the derivatives of the loss with respect to the values the activation function got, accumulating the gradients.
+	/// Returns the derivatives with respect to the inputs
+	#[cfg(feature = "training")]
+	pub fn backpropagate_batch_pre_activation(&mut self, cache: &BatchCache, mut derivs: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+		if let Some(layer_norm) = &mut self.layer_norm {
+			for (deriv, statistics) in derivs.iter_mut().zip(&cache.layer_statistics) {
+				layer_norm.add_gradients(deriv, statistics);
+				layer_norm.input_derivs(deriv, statistics);
+			}
+		}
+		match (&mut self.batch_norm, &cache.statistics) {
+			(Some(batch_norm), Some(statistics)) => batch_norm.backpropagate_batch(&mut derivs, statistics),
+			// Normalizing with the running statistics multiplies each weighted sum by a constant
+			(Some(batch_norm), None) => {
+				for deriv in &mut derivs {
+					deriv.iter_mut().enumerate().for_each(|(neuronidx, deriv)| *deriv *= batch_norm.affine(neuronidx).0);
+				}
+			},
+			(None, _) => {},
+		}
+
+		if !self.frozen {
+			for (input, deriv) in cache.inputs.iter().zip(&derivs) {
+				for (neuron, neuron_deriv) in self.neurons.iter_mut().zip(deriv) {
+					neuron.add_gradients(input, *neuron_deriv, 0.0);
+				}
+			}
+		}
+
+		derivs.iter()
+			.map(|deriv| (0..self.input_size)
+				.map(|inputidx| self.neurons.iter().zip(deriv)
+					.map(|(neuron, neuron_deriv)| neuron_deriv * neuron.get_weight(inputidx).expect("Length was already checked. This should not fail. (Layer)"))
+					.sum())
+				.collect())
+			.collect()
+	}
+
+	/// Randomly zero outputs from `activate` with the dropout probability and scale up the rest to keep the expected value the same (inverted dropout)
+	#[cfg(feature = "training")]
+	pub fn apply_dropout(&mut self, outputs: &mut [f64], rng: &mut impl Rng) {
+		if self.dropout == 0.0 {
+			return;
+		}
+
+		for (neuronidx, output) in outputs.iter_mut().enumerate() {
+			let scale = self.dropout_scale(rng);
+			self.neurons[neuronidx].set_dropout_scale(scale);
+			*output *= scale;
+		}
+	}
+
+	/// Draw what one output is multiplied by for dropout: 0 if it is dropped, and `1 / (1 - dropout)` otherwise
+	#[cfg(feature = "training")]
+	fn dropout_scale(&self, rng: &mut impl Rng) -> f64 {
+		if rng.random_bool(self.dropout) { 0.0 } else { 1.0 / (1.0 - self.dropout) }
+	}
+
+	pub fn set_dropout(&mut self, dropout: f64) {
+		self.dropout = dropout;
+	}
+
+	pub fn predict(&self, inputs: &[f64]) -> crate::error::Result<Vec<f64>> {
+		if inputs.len() != self.input_size {
+            return Err(crate::error::InputSizeError {
+                    inputted: inputs.len(),
+                    expected: self.input_size,
+                    chain_depth: "Layer".to_owned()
+                }.into()
+            );
+        }
+
+        if self.batch_norm.is_some() || self.layer_norm.is_some() {
+        	let mut weighted = self.weighted_sums(inputs);
+        	self.normalize(&mut weighted);
+        	if self.is_softmax() {
+        		return Ok(Activation::softmax(&weighted));
+        	}
+        	return Ok(self.neurons.iter().zip(weighted).map(|(neuron, value)| neuron.activation_output(value)).collect());
+        }
+
+        if self.is_softmax() {
+        	return Ok(Activation::softmax(&self.weighted_sums(inputs)));
+        }
+
+        Ok(self.neurons.iter()
+        	.map(|neuron| neuron.predict(inputs).expect("Length was already checked. This should not fail. (Layer)"))
+        	.collect())
+	}
+
+	/// Apply batch normalization (with the running statistics) and layer normalization to one sample's weighted sums
+	pub fn normalize(&self, weighted: &mut [f64]) {
+		if let Some(batch_norm) = &self.batch_norm {
+			batch_norm.normalize(weighted);
+		}
+		if let Some(layer_norm) = &self.layer_norm {
+			layer_norm.normalize(weighted);
 		}
 	}
 
+	/// Whether any neuron in this layer has an activation function that stops gradients
+	#[cfg(feature = "training")]
+	pub fn blocks_gradients(&self) -> bool {
+		self.neurons.iter().any(|neuron| neuron.get_activation().blocks_gradients())
+	}
+
+	/// The weighted sum of the inputs for every neuron, before the activation function. The input size has to be checked first
+	pub fn weighted_sums(&self, inputs: &[f64]) -> Vec<f64> {
+		self.neurons.iter().map(|neuron| neuron.weighted_sum(inputs)).collect()
+	}
+
+	/// Whether this layer has to be activated jointly with softmax
+	pub fn is_softmax(&self) -> bool {
+		self.neurons.first().is_some_and(|neuron| matches!(neuron.get_activation(), Activation::Softmax))
+	}
+
+	/// Calculate the derivatives of the output layer and accumulate its gradients. `class_weights` multiplies the loss of each output
+	#[cfg(feature = "training")]
+	pub fn update_gradients_output(&mut self, expected_outputs: &[f64], loss: &Loss, class_weights: Option<&[f64]>) {
+		let class_weight = |neuronidx: usize| class_weights.map_or(1.0, |weights| weights[neuronidx]);
+
+		if self.is_softmax() && *loss == Loss::CrossEntropy {
+			// The softmax Jacobian and the cross-entropy derivative cancel out into output * sum(expected) - expected
+			// (output - expected for one-hot outputs). This avoids dividing by tiny outputs
+			let weighted_expected: Vec<f64> = expected_outputs.iter().enumerate().map(|(neuronidx, expected)| expected * class_weight(neuronidx)).collect();
+			let expected_sum: f64 = weighted_expected.iter().sum();
+			for (neuron, expected) in self.neurons.iter_mut().zip(weighted_expected) {
+				neuron.set_deriv(neuron.get_last_output() * expected_sum - expected);
+			}
+			self.derivs_through_normalization();
+			self.update_gradients();
+			return;
+		}
+
+		let output_derivs: Vec<f64> = self.neurons.iter().zip(expected_outputs).enumerate()
+			.map(|(neuronidx, (neuron, output))| neuron.output_deriv_output(output, loss) * class_weight(neuronidx))
+			.collect();
+		self.calculate_derivs(&output_derivs);
+		self.update_gradients();
+	}
+
+	#[cfg(feature = "training")]
 	pub fn update_gradients_hidden(&mut self, next_layer: &Layer) {
-		for (neuronidx, neuron) in self.neurons.iter_mut().enumerate() {
-			neuron.calculate_deriv_hidden(next_layer, neuronidx);
+		self.calculate_derivs_hidden(next_layer);
+		self.update_gradients();
+	}
+
+	/// Calculate each neuron's derivative from the layer after this one without accumulating gradients
+	pub fn calculate_derivs_hidden(&mut self, next_layer: &Layer) {
+		let output_derivs: Vec<f64> = (0..self.neuron_count)
+			.map(|neuronidx| next_layer.input_deriv(neuronidx))
+			.collect();
+		self.calculate_derivs(&output_derivs);
+	}
+
+	/// Calculate each neuron's derivative from the derivative with respect to its output without accumulating gradients
+	pub fn calculate_derivs(&mut self, output_derivs: &[f64]) {
+		if self.is_softmax() {
+			let softmax_sum: f64 = self.neurons.iter().zip(output_derivs)
+				.map(|(neuron, deriv)| neuron.get_last_output() * deriv)
+				.sum();
+
+			for (neuron, deriv) in self.neurons.iter_mut().zip(output_derivs) {
+				neuron.calculate_deriv_softmax(*deriv, softmax_sum);
+			}
+		} else {
+			for (neuron, deriv) in self.neurons.iter_mut().zip(output_derivs) {
+				neuron.calculate_deriv(*deriv);
+			}
+		}
+
+		self.derivs_through_normalization();
+	}
+
+	/// Turn the neurons' derivatives with respect to the values they activated into derivatives with respect to their weighted sums.
+	/// Outside of batch training, batch normalization multiplies each weighted sum by a constant, so the derivatives are multiplied by it too.
+	/// The normalizations cache what `update_gradients` needs for their own gradients
+	fn derivs_through_normalization(&mut self) {
+		if let Some(layer_norm) = &mut self.layer_norm {
+			let mut derivs: Vec<f64> = self.neurons.iter().map(Neuron::get_last_deriv).collect();
+			layer_norm.calculate_derivs(&mut derivs);
+			for (neuron, deriv) in self.neurons.iter_mut().zip(derivs) {
+				neuron.set_weighted_deriv(deriv);
+			}
+		}
+
+		if let Some(batch_norm) = &mut self.batch_norm {
+			let mut derivs: Vec<f64> = self.neurons.iter().map(Neuron::get_last_deriv).collect();
+			batch_norm.calculate_derivs(&mut derivs);
+			for (neuron, deriv) in self.neurons.iter_mut().zip(derivs) {
+				neuron.set_weighted_deriv(deriv);
+			}
+		}
+	}
+
+	/// The derivative with respect to one of this layer's inputs. The derivatives have to be calculated first
+	pub fn input_deriv(&self, input_idx: usize) -> f64 {
+		self.neurons.iter()
+			.map(|neuron| neuron.get_last_deriv() * neuron.get_weight(input_idx).expect("Length was already checked. This should not fail. (Layer)"))
+			.sum()
+	}
+
+	#[cfg(feature = "training")]
+	fn update_gradients(&mut self) {
+		// The derivatives are still needed for the earlier layers, but frozen layers would throw their gradients away
+		if self.frozen {
+			return;
+		}
+
+		for neuron in &mut self.neurons {
 			neuron.update_gradients();
 		}
+		if let Some(batch_norm) = &mut self.batch_norm {
+			batch_norm.update_gradients();
+		}
+		if let Some(layer_norm) = &mut self.layer_norm {
+			layer_norm.update_gradients();
+		}
 	}
 
 	pub fn get_neuron_count(&self) -> usize {
 		self.neuron_count
 	}
 
+	pub fn get_input_size(&self) -> usize {
+		self.input_size
+	}
+
+	/// Replace every weight and bias. `weights` has one Vec per neuron with the weight for each input, and `biases` has one bias per neuron
+	pub fn set_weights(&mut self, weights: Vec<Vec<f64>>, biases: Vec<f64>) -> crate::error::Result<()> {
+		if weights.len() != self.neuron_count {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "weights".to_owned(),
+					reason: format!("it needs one row for each of the {} neurons, not {}", self.neuron_count, weights.len())
+				}.into()
+			);
+		}
+		if let Some(row) = weights.iter().find(|row| row.len() != self.input_size) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "weights".to_owned(),
+					reason: format!("each row needs one weight for each of the {} inputs, not {}", self.input_size, row.len())
+				}.into()
+			);
+		}
+		if biases.len() != self.neuron_count {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "biases".to_owned(),
+					reason: format!("it needs one bias for each of the {} neurons, not {}", self.neuron_count, biases.len())
+				}.into()
+			);
+		}
+
+		for ((neuron, row), bias) in self.neurons.iter_mut().zip(weights).zip(biases) {
+			for (weightidx, weight) in row.into_iter().enumerate() {
+				*neuron.get_weight_mut(weightidx).expect("Length was already checked. This should not fail. (Layer)") = weight;
+			}
+			neuron.set_bias(&bias);
+		}
+
+		Ok(())
+	}
+
+	/// The number of learnable parameters in this layer: the weights and biases, PReLU slopes, and batch and layer normalization's scales and shifts
+	pub fn parameter_count(&self) -> usize {
+		let normalization_count = [self.batch_norm.is_some(), self.layer_norm.is_some()].iter().filter(|enabled| **enabled).count();
+		self.weight_and_bias_count()
+			+ self.neurons.iter().filter(|neuron| neuron.get_activation().has_param()).count()
+			+ 2 * self.neuron_count * normalization_count
+	}
+
+	/// The number of weights and biases in this layer
+	pub fn weight_and_bias_count(&self) -> usize {
+		self.neurons.iter().map(|neuron| neuron.get_weight_count() + 1).sum()
+	}
+
+	/// Append every value the layer's outputs depend on to `parameters`: each neuron's weights, bias, and PReLU slope (only for PReLU neurons),
+	/// then the batch normalization parameters and running statistics, then the layer normalization parameters
+	pub fn export_parameters(&self, parameters: &mut Vec<f64>) {
+		for neuron in &self.neurons {
+			parameters.extend((0..neuron.get_weight_count()).filter_map(|weightidx| neuron.get_weight(weightidx)));
+			parameters.push(*neuron.get_bias());
+			if neuron.get_activation().has_param() {
+				parameters.push(neuron.get_activation_param());
+			}
+		}
+		if let Some(batch_norm) = &self.batch_norm {
+			batch_norm.export_parameters(parameters);
+		}
+		if let Some(layer_norm) = &self.layer_norm {
+			layer_norm.export_parameters(parameters);
+		}
+	}
+
+	/// Read the values in the order of `export_parameters`. There have to be enough
+	pub fn load_parameters<'a>(&mut self, parameters: &mut impl Iterator<Item = &'a f64>) {
+		let mut next = || *parameters.next().expect("Length was already checked. This should not fail. (Layer)");
+		for neuron in &mut self.neurons {
+			for weightidx in 0..neuron.get_weight_count() {
+				*neuron.get_weight_mut(weightidx).expect("Length was already checked. This should not fail. (Layer)") = next();
+			}
+			neuron.set_bias(&next());
+			if neuron.get_activation().has_param() {
+				neuron.set_activation_param(next());
+			}
+		}
+		if let Some(batch_norm) = &mut self.batch_norm {
+			batch_norm.load_parameters(parameters);
+		}
+		if let Some(layer_norm) = &mut self.layer_norm {
+			layer_norm.load_parameters(parameters);
+		}
+	}
+
+	/// Whether the neurons in this layer don't all use the same activation function
+	pub fn has_mixed_activations(&self) -> bool {
+		let mut activations = self.neurons.iter().map(Neuron::get_activation);
+		activations.next().is_some_and(|first| activations.any(|activation| activation != first))
+	}
+
+	/// The activation function of the layer's first neuron
+	pub fn get_activation(&self) -> Option<&Activation> {
+		self.neurons.first().map(Neuron::get_activation)
+	}
+
+	pub fn set_activation(&mut self, activation: Activation) {
+		for neuron in &mut self.neurons {
+			neuron.set_activation(activation.clone());
+		}
+	}
+
+	pub fn set_frozen(&mut self, frozen: bool) {
+		self.frozen = frozen;
+	}
+
+	pub fn is_frozen(&self) -> bool {
+		self.frozen
+	}
+
+	/// Turn batch normalization on (with fresh parameters and statistics) or off
+	pub fn set_batch_norm(&mut self, enabled: bool) {
+		self.batch_norm = enabled.then(|| BatchNorm::new(self.neuron_count));
+	}
+
+	pub fn get_batch_norm(&self) -> Option<&BatchNorm> {
+		self.batch_norm.as_ref()
+	}
+
+	/// Fold batch normalization with the running statistics into the weights and biases, then turn it off
+	pub fn fold_batch_norm(&mut self) {
+		let Some(batch_norm) = self.batch_norm.take() else {
+			return;
+		};
+
+		for (neuronidx, neuron) in self.neurons.iter_mut().enumerate() {
+			let (multiplier, offset) = batch_norm.affine(neuronidx);
+			for weightidx in 0..neuron.get_weight_count() {
+				*neuron.get_weight_mut(weightidx).expect("Length was already checked. This should not fail. (Layer)") *= multiplier;
+			}
+			neuron.set_bias(&(neuron.get_bias() * multiplier + offset));
+		}
+	}
+
+	/// Turn layer normalization on (with a gain of 1 and a bias of 0) or off
+	pub fn set_layer_norm(&mut self, enabled: bool) {
+		self.layer_norm = enabled.then(|| LayerNorm::new(self.neuron_count));
+	}
+
+	pub fn get_layer_norm(&self) -> Option<&LayerNorm> {
+		self.layer_norm.as_ref()
+	}
+
+	/// The sum of the squares of the accumulated gradients that will be applied (none if the layer is frozen)
+	#[cfg(feature = "training")]
+	pub fn gradient_norm_squared(&self) -> f64 {
+		if self.frozen {
+			return 0.0;
+		}
+
+		self.neurons.iter().filter(|neuron| !neuron.is_frozen()).map(Neuron::gradient_norm_squared).sum::<f64>()
+			+ self.batch_norm.as_ref().map_or(0.0, BatchNorm::gradient_norm_squared)
+			+ self.layer_norm.as_ref().map_or(0.0, LayerNorm::gradient_norm_squared)
+	}
+
+	#[cfg(feature = "training")]
+	pub fn scale_gradients(&mut self, scale: f64) {
+		for neuron in &mut self.neurons {
+			neuron.scale_gradients(scale);
+		}
+		if let Some(batch_norm) = &mut self.batch_norm {
+			batch_norm.scale_gradients(scale);
+		}
+		if let Some(layer_norm) = &mut self.layer_norm {
+			layer_norm.scale_gradients(scale);
+		}
+	}
+
+	/// Apply the accumulated gradients, or throw them away if the layer (or neuron) is frozen
+	#[cfg(feature = "training")]
+	pub fn apply_gradients(&mut self, learn_rate: f64, batch_size: usize, optimizer: &Optimizer, regularization: &Regularization) {
+		for neuron in &mut self.neurons {
+			if self.frozen || neuron.is_frozen() {
+				neuron.reset_gradients();
+			} else {
+				neuron.apply_gradients(learn_rate, batch_size, optimizer, regularization);
+			}
+		}
+
+		if let Some(batch_norm) = &mut self.batch_norm {
+			if self.frozen {
+				batch_norm.reset_gradients();
+			} else {
+				batch_norm.apply_gradients(learn_rate, batch_size, optimizer);
+			}
+		}
+
+		if let Some(layer_norm) = &mut self.layer_norm {
+			if self.frozen {
+				layer_norm.reset_gradients();
+			} else {
+				layer_norm.apply_gradients(learn_rate, batch_size, optimizer);
+			}
+		}
+	}
+
+	/// Scale down the weights of every neuron whose weights have a bigger L2 norm than `max_norm`. Frozen layers and neurons are left alone
+	#[cfg(feature = "training")]
+	pub fn constrain_weight_norms(&mut self, max_norm: f64) {
+		if self.frozen {
+			return;
+		}
+
+		for neuron in self.neurons.iter_mut().filter(|neuron| !neuron.is_frozen()) {
+			neuron.constrain_weight_norm(max_norm);
+		}
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn restore_training_buffers(&mut self) {
+		for neuron in &mut self.neurons {
+			neuron.restore_training_buffers();
+		}
+		if let Some(batch_norm) = &mut self.batch_norm {
+			batch_norm.restore_training_buffers();
+		}
+		if let Some(layer_norm) = &mut self.layer_norm {
+			layer_norm.restore_training_buffers();
+		}
+	}
+
+	pub fn reset_training_state(&mut self) {
+		for neuron in &mut self.neurons {
+			neuron.reset_training_state();
+		}
+		if let Some(batch_norm) = &mut self.batch_norm {
+			batch_norm.reset_gradients();
+			batch_norm.reset_optimizer_state();
+		}
+		if let Some(layer_norm) = &mut self.layer_norm {
+			layer_norm.reset_gradients();
+			layer_norm.reset_optimizer_state();
+		}
+	}
+
+	pub fn reset_optimizer_state(&mut self) {
+		for neuron in &mut self.neurons {
+			neuron.reset_optimizer_state();
+		}
+		if let Some(batch_norm) = &mut self.batch_norm {
+			batch_norm.reset_optimizer_state();
+		}
+		if let Some(layer_norm) = &mut self.layer_norm {
+			layer_norm.reset_optimizer_state();
+		}
+	}
+
+	#[cfg(feature = "rayon")]
+	pub fn worker_copy(&self) -> Layer {
+		Layer {
+			neurons: self.neurons.iter().map(Neuron::without_training_state).collect(),
+			neuron_count: self.neuron_count,
+			input_size: self.input_size,
+			frozen: self.frozen,
+			dropout: self.dropout,
+			batch_norm: self.batch_norm.as_ref().map(BatchNorm::without_training_state),
+			layer_norm: self.layer_norm.as_ref().map(LayerNorm::without_training_state),
+		}
+	}
+
+	#[cfg(feature = "rayon")]
+	pub fn add_gradients_from(&mut self, source: &Layer) {
+		for (neuron, source_neuron) in self.neurons.iter_mut().zip(&source.neurons) {
+			neuron.add_gradients_from(source_neuron);
+		}
+		if let (Some(batch_norm), Some(source_batch_norm)) = (&mut self.batch_norm, &source.batch_norm) {
+			batch_norm.add_gradients_from(source_batch_norm);
+		}
+		if let (Some(layer_norm), Some(source_layer_norm)) = (&mut self.layer_norm, &source.layer_norm) {
+			layer_norm.add_gradients_from(source_layer_norm);
+		}
+	}
+
+	pub fn soft_update_from(&mut self, source: &Layer, tau: f64) {
+		for (neuron, source_neuron) in self.neurons.iter_mut().zip(&source.neurons) {
+			neuron.soft_update_from(source_neuron, tau);
+		}
+		if let (Some(batch_norm), Some(source_batch_norm)) = (&mut self.batch_norm, &source.batch_norm) {
+			batch_norm.soft_update_from(source_batch_norm, tau);
+		}
+		if let (Some(layer_norm), Some(source_layer_norm)) = (&mut self.layer_norm, &source.layer_norm) {
+			layer_norm.soft_update_from(source_layer_norm, tau);
+		}
+	}
+
 	pub fn get_neuron(&self, idx: usize) -> Option<&Neuron> {
 		self.neurons.get(idx)
 	}
@@ -62,17 +712,26 @@ impl Layer {
 	}
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "training"))]
 mod tests {
 	use super::*;
 
 	#[test]
 	fn layer() {
-		let mut layer = Layer::new(1, 2, crate::activation::Activation::Linear);
+		let mut layer = Layer::new(1, 2, crate::activation::Activation::Linear, &Init::default(), &Init::Zeros, &mut rand::rng());
 
 		assert!(layer.activate(&[]).is_err());
 		layer.activate(&[1.0]).unwrap();
 
 		assert_eq!(layer.get_neuron_count(), 2);
 	}
+
+	#[test]
+	fn softmax_layer() {
+		let mut layer = Layer::new(3, 4, Activation::Softmax, &Init::default(), &Init::Zeros, &mut rand::rng());
+
+		let outputs = layer.activate(&[0.5, -2.0, 3.0]).unwrap();
+		assert!((outputs.iter().sum::<f64>() - 1.0).abs() < 1e-10);
+		assert_eq!(outputs, layer.predict(&[0.5, -2.0, 3.0]).unwrap());
+	}
 }
@@ -0,0 +1,279 @@
+use crate::optimizer::ParamState;
+
+#[cfg(feature = "training")]
+use crate::optimizer::Optimizer;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// Added to the variance so constant values don't divide by 0
+const EPSILON: f64 = 1e-5;
+
+/// Layer normalization of a layer's weighted sums: <https://arxiv.org/abs/1607.06450>
+///
+/// The weighted sums of each sample are normalized with their mean and variance across the layer's neurons,
+/// then scaled and shifted by learnable parameters. Every sample is normalized on its own, so nothing is kept between samples.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LayerNorm {
+	gain: Vec<f64>,
+	bias: Vec<f64>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	gain_gradient: Vec<f64>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	bias_gradient: Vec<f64>,
+	// The optimizer state for each gain, then each bias. Empty until the first update
+	#[cfg_attr(feature = "serde", serde(skip))]
+	optimizer_state: Vec<ParamState>,
+	// The statistics of the last sample from `activate` and the derivatives with respect to its outputs from `calculate_derivs`
+	#[cfg_attr(feature = "serde", serde(skip))]
+	last_statistics: LayerStatistics,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	last_derivs: Vec<f64>,
+}
+
+/// What the backward pass needs from normalizing one sample
+#[derive(Debug, Default, Clone)]
+pub struct LayerStatistics {
+	// The weighted sums after normalizing but before scaling and shifting
+	normalized: Vec<f64>,
+	// 1 / sqrt(variance + epsilon)
+	inverse_std: f64,
+}
+
+/// Clones copy the parameters and optimizer state. The accumulated gradients start empty
+impl Clone for LayerNorm {
+	fn clone(&self) -> LayerNorm {
+		LayerNorm {
+			optimizer_state: self.optimizer_state.clone(),
+			..self.without_training_state()
+		}
+	}
+}
+
+impl LayerNorm {
+	/// Layer normalization for `size` neurons with a gain of 1 and a bias of 0
+	pub fn new(size: usize) -> LayerNorm {
+		LayerNorm {
+			gain: vec![1.0; size],
+			bias: vec![0.0; size],
+			gain_gradient: vec![0.0; size],
+			bias_gradient: vec![0.0; size],
+			optimizer_state: Vec::new(),
+			last_statistics: LayerStatistics::default(),
+			last_derivs: Vec::new(),
+		}
+	}
+
+	/// Append the gains and biases to `parameters`
+	pub fn export_parameters(&self, parameters: &mut Vec<f64>) {
+		parameters.extend(&self.gain);
+		parameters.extend(&self.bias);
+	}
+
+	/// Read the values in the order of `export_parameters`. There have to be enough
+	pub fn load_parameters<'a>(&mut self, parameters: &mut impl Iterator<Item = &'a f64>) {
+		for values in [&mut self.gain, &mut self.bias] {
+			for value in values.iter_mut() {
+				*value = *parameters.next().expect("Length was already checked. This should not fail. (LayerNorm)");
+			}
+		}
+	}
+
+	/// Normalize one sample's weighted sums in place
+	pub fn normalize(&self, weighted: &mut [f64]) -> LayerStatistics {
+		let size = weighted.len() as f64;
+		let mean = weighted.iter().sum::<f64>() / size;
+		let variance = weighted.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / size;
+		let inverse_std = 1.0 / (variance + EPSILON).sqrt();
+
+		let mut normalized = Vec::with_capacity(weighted.len());
+		for ((value, gain), bias) in weighted.iter_mut().zip(&self.gain).zip(&self.bias) {
+			normalized.push((*value - mean) * inverse_std);
+			*value = gain * normalized[normalized.len() - 1] + bias;
+		}
+
+		LayerStatistics { normalized, inverse_std }
+	}
+
+	/// Normalize one sample's weighted sums in place and cache the statistics for `calculate_derivs`
+	pub fn activate(&mut self, weighted: &mut [f64]) {
+		self.last_statistics = self.normalize(weighted);
+	}
+
+	/// Turn the derivatives with respect to the normalized outputs of a sample into derivatives with respect to the weighted sums, in place
+	pub fn input_derivs(&self, derivs: &mut [f64], statistics: &LayerStatistics) {
+		let size = derivs.len() as f64;
+		let scaled: Vec<f64> = derivs.iter().zip(&self.gain).map(|(deriv, gain)| deriv * gain).collect();
+		let scaled_sum: f64 = scaled.iter().sum();
+		let scaled_normalized_sum: f64 = scaled.iter().zip(&statistics.normalized).map(|(scaled, normalized)| scaled * normalized).sum();
+
+		// Every weighted sum also moves the mean and variance, which is where the two sums come from
+		for ((deriv, scaled), normalized) in derivs.iter_mut().zip(scaled).zip(&statistics.normalized) {
+			*deriv = statistics.inverse_std * (scaled - scaled_sum / size - normalized * scaled_normalized_sum / size);
+		}
+	}
+
+	/// `input_derivs` for the last sample from `activate`. The derivatives with respect to the outputs are cached for `update_gradients`
+	pub fn calculate_derivs(&mut self, derivs: &mut [f64]) {
+		self.last_derivs = derivs.to_vec();
+		self.input_derivs(derivs, &self.last_statistics);
+	}
+
+	/// Accumulate the gradients of the gain and bias from the derivatives with respect to a sample's outputs
+	#[cfg(feature = "training")]
+	pub fn add_gradients(&mut self, derivs: &[f64], statistics: &LayerStatistics) {
+		LayerNorm::accumulate(&mut self.gain_gradient, &mut self.bias_gradient, derivs, &statistics.normalized);
+	}
+
+	/// Accumulate the gradients of the last sample from `calculate_derivs`
+	#[cfg(feature = "training")]
+	pub fn update_gradients(&mut self) {
+		LayerNorm::accumulate(&mut self.gain_gradient, &mut self.bias_gradient, &self.last_derivs, &self.last_statistics.normalized);
+	}
+
+	#[cfg(feature = "training")]
+	fn accumulate(gain_gradient: &mut [f64], bias_gradient: &mut [f64], derivs: &[f64], normalized: &[f64]) {
+		for (((deriv, normalized), gain_gradient), bias_gradient) in derivs.iter().zip(normalized).zip(gain_gradient).zip(bias_gradient) {
+			*gain_gradient += deriv * normalized;
+			*bias_gradient += deriv;
+		}
+	}
+
+	/// The sum of the squares of the accumulated gradients
+	#[cfg(feature = "training")]
+	pub fn gradient_norm_squared(&self) -> f64 {
+		self.gain_gradient.iter().chain(&self.bias_gradient).map(|gradient| gradient * gradient).sum()
+	}
+
+	/// Multiply the accumulated gradients by `scale`
+	#[cfg(feature = "training")]
+	pub fn scale_gradients(&mut self, scale: f64) {
+		self.gain_gradient.iter_mut().chain(&mut self.bias_gradient).for_each(|gradient| *gradient *= scale);
+	}
+
+	/// Zero the accumulated gradients without applying them
+	pub fn reset_gradients(&mut self) {
+		self.gain_gradient.iter_mut().chain(&mut self.bias_gradient).for_each(|gradient| *gradient = 0.0);
+	}
+
+	/// Apply the gradients accumulated over `batch_size` samples with an optimizer, then reset them
+	#[cfg(feature = "training")]
+	pub fn apply_gradients(&mut self, learn_rate: f64, batch_size: usize, optimizer: &Optimizer) {
+		let size = self.gain.len();
+		// The state is skipped when saving, so this also covers loaded networks
+		self.optimizer_state.resize(2 * size, ParamState::default());
+		let (gain_states, bias_states) = self.optimizer_state.split_at_mut(size);
+
+		for (neuronidx, (gain_state, bias_state)) in gain_states.iter_mut().zip(bias_states).enumerate() {
+			self.gain[neuronidx] += optimizer.step(gain_state, self.gain_gradient[neuronidx] / batch_size as f64, learn_rate);
+			self.bias[neuronidx] += optimizer.step(bias_state, self.bias_gradient[neuronidx] / batch_size as f64, learn_rate);
+		}
+
+		self.reset_gradients();
+	}
+
+	/// Add the gradients accumulated by another copy of this layer normalization
+	#[cfg(feature = "rayon")]
+	pub fn add_gradients_from(&mut self, source: &LayerNorm) {
+		let pairs = [
+			(&mut self.gain_gradient, &source.gain_gradient),
+			(&mut self.bias_gradient, &source.bias_gradient),
+		];
+		for (gradients, source_gradients) in pairs {
+			for (gradient, source_gradient) in gradients.iter_mut().zip(source_gradients) {
+				*gradient += source_gradient;
+			}
+		}
+	}
+
+	/// Recreate the gradient buffers, which are skipped when saving
+	#[cfg(feature = "serde")]
+	pub fn restore_training_buffers(&mut self) {
+		self.gain_gradient = vec![0.0; self.gain.len()];
+		self.bias_gradient = vec![0.0; self.gain.len()];
+	}
+
+	/// A copy with the same parameters but empty gradients and optimizer state
+	pub fn without_training_state(&self) -> LayerNorm {
+		LayerNorm {
+			gain: self.gain.clone(),
+			bias: self.bias.clone(),
+			..LayerNorm::new(self.gain.len())
+		}
+	}
+
+	/// Forget everything the optimizer remembers (like momentum) so training starts fresh
+	pub fn reset_optimizer_state(&mut self) {
+		self.optimizer_state.clear();
+	}
+
+	/// Move the parameters towards another layer normalization's: `self = tau * source + (1 - tau) * self`
+	pub fn soft_update_from(&mut self, source: &LayerNorm, tau: f64) {
+		for (values, source_values) in [(&mut self.gain, &source.gain), (&mut self.bias, &source.bias)] {
+			for (value, source_value) in values.iter_mut().zip(source_values) {
+				*value = tau * source_value + (1.0 - tau) * *value;
+			}
+		}
+	}
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize() {
+		let layer_norm = LayerNorm::new(3);
+		let mut sample = [1.0, 3.0, 5.0];
+		layer_norm.normalize(&mut sample);
+
+		// The mean is 3 and the variance is 8/3
+		let std = (8.0_f64 / 3.0 + EPSILON).sqrt();
+		for (value, expected) in sample.iter().zip([-2.0 / std, 0.0, 2.0 / std]) {
+			assert!((value - expected).abs() < 1e-12);
+		}
+	}
+
+	#[test]
+	fn backpropagate() {
+		// The loss is a weighted sum of the outputs, so the derivative with respect to each output is its weight
+		let coefficients = [0.3, -1.2, 2.0, 0.5];
+		let sample = [0.2, -1.0, 1.5, 0.3];
+		let loss = |layer_norm: &LayerNorm, sample: &[f64]| {
+			let mut sample = sample.to_vec();
+			layer_norm.normalize(&mut sample);
+			sample.iter().zip(coefficients).map(|(output, coefficient)| output * coefficient).sum::<f64>()
+		};
+
+		let mut layer_norm = LayerNorm::new(4);
+		layer_norm.gain = vec![1.5, 0.5, -0.3, 1.0];
+		layer_norm.bias = vec![0.1, -0.2, 0.0, 0.4];
+
+		layer_norm.activate(&mut sample.clone());
+		let mut derivs = coefficients.to_vec();
+		layer_norm.calculate_derivs(&mut derivs);
+		layer_norm.update_gradients();
+
+		let epsilon = 1e-6;
+		for idx in 0..4 {
+			let mut up = sample;
+			up[idx] += epsilon;
+			let mut down = sample;
+			down[idx] -= epsilon;
+			assert!((derivs[idx] - (loss(&layer_norm, &up) - loss(&layer_norm, &down)) / (2.0 * epsilon)).abs() < 1e-6);
+
+			let mut up = layer_norm.clone();
+			up.gain[idx] += epsilon;
+			let mut down = layer_norm.clone();
+			down.gain[idx] -= epsilon;
+			assert!((layer_norm.gain_gradient[idx] - (loss(&up, &sample) - loss(&down, &sample)) / (2.0 * epsilon)).abs() < 1e-6);
+
+			let mut up = layer_norm.clone();
+			up.bias[idx] += epsilon;
+			let mut down = layer_norm.clone();
+			down.bias[idx] -= epsilon;
+			assert!((layer_norm.bias_gradient[idx] - (loss(&up, &sample) - loss(&down, &sample)) / (2.0 * epsilon)).abs() < 1e-6);
+		}
+	}
+}
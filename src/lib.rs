@@ -1,12 +1,27 @@
 //! Easy neural network library
 
-mod error;
+/// Module containing the errors neuralib can return
+pub mod error;
 
 mod neuron;
 mod layer;
+mod batch_norm;
+mod layer_norm;
 /// Module for creating, training, and running a neural network
 pub mod network;
 /// Module containing activation functions for a neural network
 pub mod activation;
+/// Module containing loss functions for a neural network
+pub mod loss;
+/// Module containing optimizers for training a neural network
+pub mod optimizer;
+/// Module containing weight initialization strategies for a neural network
+#[cfg(feature = "training")]
+pub mod init;
 /// Module containing useful structs for training and training data
 pub mod training;
+/// Module containing ensembles of neural networks that predict together
+pub mod ensemble;
+/// Module containing a matrix-based version of a network for fast inference
+#[cfg(feature = "ndarray")]
+pub mod matrix;
@@ -0,0 +1,166 @@
+//! Loss functions for neuralib
+//!
+//! This module provides the loss functions a neural network can be trained with.
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// The loss functions this library supports
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Loss {
+	/// Mean squared error: `(output - expected)^2` for each output
+	#[default]
+	MSE,
+	/// Mean absolute error: `|output - expected|` for each output. It is less sensitive to outliers than MSE
+	MAE,
+	/// Huber loss: <https://en.wikipedia.org/wiki/Huber_loss>
+	///
+	/// Quadratic like MSE for errors up to `delta` and linear like MAE beyond that, so large errors from outliers don't dominate.
+	/// `delta` should be positive. `Loss::DEFAULT_HUBER_DELTA` (1.0) is a good default
+	Huber {
+		delta: f64
+	},
+	/// Categorical cross-entropy: `-expected * ln(output)` for each output.
+	///
+	/// The outputs should be probabilities, so this is best used with a Softmax output layer.
+	/// Together they have the simple gradient `output - expected`
+	CrossEntropy,
+}
+
+impl Loss {
+	/// The usual delta for `Loss::Huber`
+	pub const DEFAULT_HUBER_DELTA: f64 = 1.0;
+
+	/// Huber loss with the default delta
+	pub const fn huber_default() -> Loss {
+		Loss::Huber { delta: Loss::DEFAULT_HUBER_DELTA }
+	}
+
+	/// Call the selected loss function for a single output
+	pub fn call(&self, output: f64, expected: f64) -> f64 {
+		match self {
+			Loss::MSE          => Loss::mse(output, expected),
+			Loss::MAE          => Loss::mae(output, expected),
+			Loss::Huber{delta} => Loss::huber(output, expected, *delta),
+			Loss::CrossEntropy => Loss::cross_entropy(output, expected),
+		}
+	}
+
+	/// The (partial) derivative of the selected loss function with respect to a single output
+	pub fn derivative(&self, output: f64, expected: f64) -> f64 {
+		match self {
+			Loss::MSE          => Loss::deriv_mse(output, expected),
+			Loss::MAE          => Loss::deriv_mae(output, expected),
+			Loss::Huber{delta} => Loss::deriv_huber(output, expected, *delta),
+			Loss::CrossEntropy => Loss::deriv_cross_entropy(output, expected),
+		}
+	}
+
+	fn deriv_mse(output: f64, expected: f64) -> f64 {
+		2.0 * (output - expected)
+	}
+
+	fn mse(output: f64, expected: f64) -> f64 {
+		(output - expected).powi(2)
+	}
+
+	fn deriv_mae(output: f64, expected: f64) -> f64 {
+		// signum would give 1 for a perfect output
+		if output == expected {
+			0.0
+		} else {
+			(output - expected).signum()
+		}
+	}
+
+	fn mae(output: f64, expected: f64) -> f64 {
+		(output - expected).abs()
+	}
+
+	fn deriv_huber(output: f64, expected: f64, delta: f64) -> f64 {
+		(output - expected).clamp(-delta, delta)
+	}
+
+	fn huber(output: f64, expected: f64, delta: f64) -> f64 {
+		let error = (output - expected).abs();
+		if error <= delta {
+			0.5 * error * error
+		} else {
+			delta * (error - 0.5 * delta)
+		}
+	}
+
+	fn deriv_cross_entropy(output: f64, expected: f64) -> f64 {
+		-expected / output.max(Loss::MIN_PROBABILITY)
+	}
+
+	fn cross_entropy(output: f64, expected: f64) -> f64 {
+		// Outputs of 0 would give an infinite loss
+		-expected * output.max(Loss::MIN_PROBABILITY).ln()
+	}
+
+	const MIN_PROBABILITY: f64 = 1e-15;
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn floating_equal(a: f64, b: f64) -> bool {
+		let tolerance = 0.0001;
+		(a - b).abs() < tolerance
+	}
+
+	#[test]
+	fn mse() {
+		let loss = Loss::MSE;
+
+		assert_eq!(loss.call(3.0, 1.0), 4.0);
+		assert_eq!(loss.call(-1.0, 1.0), 4.0);
+		assert_eq!(loss.derivative(3.0, 1.0), 4.0);
+		assert_eq!(loss.derivative(-1.0, 1.0), -4.0);
+	}
+
+	#[test]
+	fn mae() {
+		let loss = Loss::MAE;
+
+		assert_eq!(loss.call(3.0, 1.0), 2.0);
+		assert_eq!(loss.call(-1.0, 1.0), 2.0);
+		assert_eq!(loss.derivative(3.0, 1.0), 1.0);
+		assert_eq!(loss.derivative(-1.0, 1.0), -1.0);
+		assert_eq!(loss.derivative(1.0, 1.0), 0.0);
+	}
+
+	#[test]
+	fn huber() {
+		let loss = Loss::Huber { delta: 2.0 };
+
+		// Quadratic inside delta
+		assert_eq!(loss.call(2.0, 1.0), 0.5);
+		assert_eq!(loss.derivative(2.0, 1.0), 1.0);
+		// Linear outside of it, and continuous at the boundary
+		assert_eq!(loss.call(3.0, 1.0), 2.0);
+		assert_eq!(loss.call(11.0, 1.0), 18.0);
+		assert_eq!(loss.derivative(11.0, 1.0), 2.0);
+		assert_eq!(loss.derivative(-9.0, 1.0), -2.0);
+
+		assert_eq!(Loss::huber_default(), Loss::Huber { delta: 1.0 });
+	}
+
+	#[test]
+	fn cross_entropy() {
+		let loss = Loss::CrossEntropy;
+
+		assert_eq!(loss.call(1.0, 1.0), 0.0);
+		assert_eq!(loss.call(0.3, 0.0), 0.0);
+		assert!(floating_equal(loss.call(0.5, 1.0), 2.0_f64.ln()));
+		assert_eq!(loss.derivative(0.5, 1.0), -2.0);
+
+		// An output of 0 shouldn't give an infinite loss
+		assert!(loss.call(0.0, 1.0).is_finite());
+		assert!(loss.derivative(0.0, 1.0).is_finite());
+	}
+}
@@ -0,0 +1,150 @@
+use crate::activation::Activation;
+use crate::layer::Layer;
+use crate::layer_norm::LayerNorm;
+use ndarray::{Array1, Array2, Axis};
+
+/// One layer of a `MatrixNetwork`. Row `i` of the weight matrix holds the weights of neuron `i`
+#[derive(Debug, Clone)]
+struct MatrixLayer {
+	weights: Array2<f64>,
+	biases: Array1<f64>,
+	activations: Vec<Activation>,
+	activation_params: Vec<f64>,
+	softmax: bool,
+	// Layer normalization depends on the whole sample, so it can't be folded in like batch normalization
+	layer_norm: Option<LayerNorm>,
+}
+
+impl MatrixLayer {
+	fn from_layer(layer: &Layer) -> MatrixLayer {
+		let neurons: Vec<_> = (0..layer.get_neuron_count())
+			.map(|neuronidx| layer.get_neuron(neuronidx).expect("Length was already checked. This should not fail. (MatrixLayer)"))
+			.collect();
+
+		// Batch normalization with the running statistics is an affine map, so it is folded into the weights and biases
+		let affine: Vec<(f64, f64)> = (0..neurons.len())
+			.map(|neuronidx| layer.get_batch_norm().map_or((1.0, 0.0), |batch_norm| batch_norm.affine(neuronidx)))
+			.collect();
+
+		let weights = Array2::from_shape_fn((neurons.len(), layer.get_input_size()), |(neuronidx, weightidx)| {
+			neurons[neuronidx].get_weight(weightidx).expect("Length was already checked. This should not fail. (MatrixLayer)") * affine[neuronidx].0
+		});
+
+		MatrixLayer {
+			weights,
+			biases: neurons.iter().zip(&affine).map(|(neuron, (multiplier, offset))| neuron.get_bias() * multiplier + offset).collect(),
+			activations: neurons.iter().map(|neuron| neuron.get_activation().clone()).collect(),
+			activation_params: neurons.iter().map(|neuron| neuron.get_activation_param()).collect(),
+			softmax: matches!(layer.get_activation(), Some(Activation::Softmax)),
+			layer_norm: layer.get_layer_norm().map(LayerNorm::without_training_state),
+		}
+	}
+
+	/// Activate every row of `weighted` (one row per sample) in place
+	fn activate(&self, weighted: &mut Array2<f64>) {
+		for mut row in weighted.rows_mut() {
+			if let Some(layer_norm) = &self.layer_norm {
+				layer_norm.normalize(row.as_slice_mut().expect("Rows of a standard layout array are contiguous. (MatrixLayer)"));
+			}
+
+			if self.softmax {
+				let outputs = Activation::softmax(row.as_slice().expect("Rows of a standard layout array are contiguous. (MatrixLayer)"));
+				row.assign(&Array1::from(outputs));
+				continue;
+			}
+
+			for ((value, activation), param) in row.iter_mut().zip(&self.activations).zip(&self.activation_params) {
+				*value = activation.call_with_param(*value, *param);
+			}
+		}
+	}
+}
+
+/// A read-only copy of a `NeuralNetwork` that stores each layer's weights as a matrix.
+///
+/// Each layer is computed as `W·x + b` with ndarray, which is much faster than the neuron-by-neuron path, especially for batches.
+/// Create one with `NeuralNetwork::to_matrix`. It doesn't change when the network it was made from is trained, so make a new one after training.
+#[derive(Debug, Clone)]
+pub struct MatrixNetwork {
+	layers: Vec<MatrixLayer>,
+	input_size: usize,
+}
+
+impl MatrixNetwork {
+	pub(crate) fn from_layers(layers: &[Layer], input_size: usize) -> MatrixNetwork {
+		MatrixNetwork {
+			layers: layers.iter().map(MatrixLayer::from_layer).collect(),
+			input_size,
+		}
+	}
+
+	/// Run the network on one input
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - The inputs to the network
+	pub fn predict(&self, inputs: &[f64]) -> crate::error::Result<Vec<f64>> {
+		Ok(self.predict_batch(std::slice::from_ref(&inputs.to_vec()))?.remove(0))
+	}
+
+	/// Run the network on many inputs at once. Each layer is a single matrix multiplication for the whole batch
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - The inputs to the network, one Vec per sample
+	pub fn predict_batch(&self, inputs: &[Vec<f64>]) -> crate::error::Result<Vec<Vec<f64>>> {
+		if let Some(input) = inputs.iter().find(|input| input.len() != self.input_size) {
+			return Err(crate::error::InputSizeError {
+			        inputted: input.len(),
+			        expected: self.input_size,
+			        chain_depth: "MatrixNetwork".to_owned()
+			    }.into()
+			);
+		}
+
+		let mut values = Array2::from_shape_fn((inputs.len(), self.input_size), |(sampleidx, inputidx)| inputs[sampleidx][inputidx]);
+
+		for layer in &self.layers {
+			let mut weighted = values.dot(&layer.weights.t());
+			weighted += &layer.biases.view().insert_axis(Axis(0));
+			layer.activate(&mut weighted);
+			values = weighted;
+		}
+
+		Ok(values.rows().into_iter().map(|row| row.to_vec()).collect())
+	}
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+	use crate::activation::Activation;
+	use crate::network::NeuralNetwork;
+	use crate::training::DataValue;
+
+	#[test]
+	fn matches_scalar_network() {
+		let mut network = NeuralNetwork::new(&[7, 5, 3], 4, vec![Activation::PReLU { initial_slope: 0.2 }, Activation::HyperTan, Activation::Softmax]).unwrap();
+		network.set_batch_norm(1, true).unwrap();
+		network.set_layer_norm(0, true).unwrap();
+		// Move the running statistics away from the identity
+		let data: Vec<DataValue> = (0..4).map(|i| DataValue { input: vec![i as f64, 1.0, -0.5, 0.2 * i as f64], expected_output: DataValue::one_hot(i % 3, 3).unwrap() }).collect();
+		network.learn(&data, 0.1).unwrap();
+		let matrix = network.to_matrix();
+
+		let inputs: Vec<Vec<f64>> = (0..10)
+			.map(|i| vec![i as f64 * 0.3, -1.0, (i % 4) as f64, 0.5 - i as f64])
+			.collect();
+
+		let batch = matrix.predict_batch(&inputs).unwrap();
+		for (input, output) in inputs.iter().zip(&batch) {
+			let expected = network.activate(input).unwrap();
+			for (a, b) in expected.iter().zip(output) {
+				assert!((a - b).abs() < 1e-12);
+			}
+			assert_eq!(matrix.predict(input).unwrap(), *output);
+		}
+
+		assert!(matrix.predict(&[1.0]).is_err());
+		assert!(matrix.predict_batch(&[]).unwrap().is_empty());
+	}
+}
@@ -1,7 +1,17 @@
 use crate::layer::Layer;
 use crate::neuron::Neuron;
 use crate::activation::Activation;
-use crate::training::DataValue;
+use crate::loss::Loss;
+use crate::optimizer::{Optimizer, Regularization};
+use crate::training::{DataValue, RegressionMetrics, TrainConfig};
+
+#[cfg(feature = "training")]
+use {
+	crate::init::Init,
+	crate::training::{EpochInfo, TrainHistory},
+	rand::Rng,
+	rand_distr::{Distribution, StandardNormal},
+};
 
 #[cfg(feature = "serde")]
 use {
@@ -9,13 +19,64 @@ use {
 	rmp_serde::{Deserializer, Serializer},
 };
 
+/// The version of the save format written by `NeuralNetwork::save`
+#[cfg(feature = "serde")]
+pub const FORMAT_VERSION: u32 = 16;
+
+/// The bytes every save written by `NeuralNetwork::save` starts with
+#[cfg(feature = "serde")]
+pub const SAVE_MAGIC: &[u8] = b"NEURALIB";
+
+/// Saves up to this version had no magic bytes before the version
+#[cfg(feature = "serde")]
+const LAST_HEADERLESS_VERSION: u32 = 8;
+
 /// A neural network
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Clones have the same architecture, parameters, optimizer state, and settings. Gradients and the training caches aren't copied
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NeuralNetwork {
 	layers: Vec<Layer>,
 	layer_count: usize,
 	input_size: usize,
 	output_size: usize,
+	#[cfg_attr(feature = "serde", serde(default))]
+	training_metadata: Option<TrainConfig>,
+	#[cfg_attr(feature = "serde", serde(default))]
+	loss: Loss,
+	#[cfg_attr(feature = "serde", serde(default))]
+	optimizer: Optimizer,
+	#[cfg_attr(feature = "serde", serde(default))]
+	regularization: Regularization,
+	// Gradients with a bigger global norm are scaled down to this
+	#[cfg_attr(feature = "serde", serde(default))]
+	max_gradient_norm: Option<f64>,
+	// The weights of each neuron are scaled down to this L2 norm after every update
+	#[cfg_attr(feature = "serde", serde(default))]
+	max_weight_norm: Option<f64>,
+	// Multiplies the loss of each output
+	#[cfg_attr(feature = "serde", serde(default))]
+	class_weights: Option<Vec<f64>>,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	gradient_norm_history: Vec<f64>,
+}
+
+/// The shape of a network: its input size and the activation function of every neuron in every layer.
+///
+/// A loaded network's architecture comes from the save itself, so its topology can be read without remembering the arguments it was created with
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Architecture {
+	pub input_size: usize,
+	pub layer_activations: Vec<Vec<Activation>>,
+}
+
+impl Architecture {
+	/// The number of neurons in each layer
+	pub fn layer_sizes(&self) -> Vec<usize> {
+		self.layer_activations.iter().map(Vec::len).collect()
+	}
 }
 
 impl NeuralNetwork {
@@ -26,26 +87,108 @@ impl NeuralNetwork {
 	/// * `layer_sizes` - A slice of usizes containing the size of each layer in the neural network
 	/// * `input_size`  - How many inputs the first layer should accept
 	/// * `activation_functions` - A Vec of which activation function should be in each layer 
+	#[cfg(feature = "training")]
 	pub fn new(layer_sizes: &[usize], input_size: usize, activation_functions: Vec<Activation>) -> crate::error::Result<NeuralNetwork> {
+		NeuralNetwork::new_with_rng(layer_sizes, input_size, activation_functions, &Init::default(), &Init::Zeros, &mut rand::rng())
+	}
+
+	/// Create a new neural network with a specific weight and bias initialization. `new` uses `Init::He` for the weights and `Init::Zeros` for the biases
+	///
+	/// Arguments:
+	///
+	/// * `layer_sizes` - A slice of usizes containing the size of each layer in the neural network
+	/// * `input_size`  - How many inputs the first layer should accept
+	/// * `activation_functions` - A Vec of which activation function should be in each layer 
+	/// * `weight_init` - How to initialize the weights of every layer
+	/// * `bias_init` - How to initialize the biases of every layer. A small constant (like 0.01) can help avoid dead ReLU neurons
+	#[cfg(feature = "training")]
+	pub fn new_with_init(layer_sizes: &[usize], input_size: usize, activation_functions: Vec<Activation>, weight_init: Init, bias_init: Init) -> crate::error::Result<NeuralNetwork> {
+		NeuralNetwork::new_with_rng(layer_sizes, input_size, activation_functions, &weight_init, &bias_init, &mut rand::rng())
+	}
+
+	/// Create a new neural network with weights initialized from a seeded random number generator.
+	/// Networks created with the same arguments and seed have the same weights
+	///
+	/// Arguments:
+	///
+	/// * `layer_sizes` - A slice of usizes containing the size of each layer in the neural network
+	/// * `input_size`  - How many inputs the first layer should accept
+	/// * `activation_functions` - A Vec of which activation function should be in each layer 
+	/// * `seed` - The seed for the weight initialization
+	#[cfg(feature = "training")]
+	pub fn new_seeded(layer_sizes: &[usize], input_size: usize, activation_functions: Vec<Activation>, seed: u64) -> crate::error::Result<NeuralNetwork> {
+		use rand::SeedableRng;
+		NeuralNetwork::new_with_rng(layer_sizes, input_size, activation_functions, &Init::default(), &Init::Zeros, &mut rand::rngs::StdRng::seed_from_u64(seed))
+	}
+
+	#[cfg(feature = "training")]
+	fn new_with_rng(layer_sizes: &[usize], input_size: usize, activation_functions: Vec<Activation>, weight_init: &Init, bias_init: &Init, rng: &mut impl Rng) -> crate::error::Result<NeuralNetwork> {
 		if layer_sizes.is_empty() {
 			return Err(crate::error::NoLayersError {}.into());
 		}
 
 		if layer_sizes.len() != activation_functions.len() {
-			// TODO: Make custom error for this
-			return Err(crate::error::NoLayersError {}.into())
+			return Err(crate::error::LayerActivationMismatchError {
+					num_layers: layer_sizes.len(),
+					num_activations: activation_functions.len(),
+				}.into()
+			);
+		}
+
+		let layer_activations = layer_sizes.iter().zip(activation_functions)
+			.map(|(layer_size, activator)| vec![activator; *layer_size])
+			.collect();
+
+		NeuralNetwork::from_layer_activations(input_size, layer_activations, weight_init, bias_init, rng)
+	}
+
+	/// Create a new, untrained network with the same architecture as another one (see `architecture`)
+	#[cfg(feature = "training")]
+	pub fn from_architecture(architecture: &Architecture) -> crate::error::Result<NeuralNetwork> {
+		NeuralNetwork::from_layer_activations(architecture.input_size, architecture.layer_activations.clone(), &Init::default(), &Init::Zeros, &mut rand::rng())
+	}
+
+	/// Create a network from the activation function of every neuron in every layer
+	#[cfg(feature = "training")]
+	fn from_layer_activations(input_size: usize, layer_activations: Vec<Vec<Activation>>, weight_init: &Init, bias_init: &Init, rng: &mut impl Rng) -> crate::error::Result<NeuralNetwork> {
+		if layer_activations.is_empty() {
+			return Err(crate::error::NoLayersError {}.into());
+		}
+
+		// A layer without neurons would give the next layer no inputs
+		if input_size == 0 {
+			return Err(crate::error::ZeroSizeError { layer: None }.into());
+		}
+		if let Some(layer) = layer_activations.iter().position(Vec::is_empty) {
+			return Err(crate::error::ZeroSizeError { layer: Some(layer) }.into());
+		}
+
+		weight_init.validate("weight_init")?;
+		bias_init.validate("bias_init")?;
+
+		// Softmax layers are activated jointly, so every neuron has to use it
+		if layer_activations.iter().any(|activations| {
+			let softmax_count = activations.iter().filter(|activation| matches!(activation, Activation::Softmax)).count();
+			softmax_count != 0 && softmax_count != activations.len()
+		}) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "activations".to_owned(),
+					reason: "softmax can't be mixed with other activation functions in a layer".to_owned()
+				}.into()
+			);
 		}
 	
 		// Allocate a vector for the layers
-		let mut layers: Vec<Layer> = Vec::with_capacity(layer_sizes.len());
+		let mut layers: Vec<Layer> = Vec::with_capacity(layer_activations.len());
 
 		let mut output_size = 0;
 		
-		let mut previous_size = &input_size;
-		for (layer_size, activator) in layer_sizes.iter().zip(activation_functions) {
-			layers.push(Layer::new(*previous_size, *layer_size, activator));
+		let mut previous_size = input_size;
+		for activations in layer_activations {
+			let layer_size = activations.len();
+			layers.push(Layer::new_per_neuron(previous_size, activations, weight_init, bias_init, rng));
 			previous_size = layer_size;
-			output_size = *layer_size;
+			output_size = layer_size;
 		}
 
 		Ok(NeuralNetwork {
@@ -53,6 +196,14 @@ impl NeuralNetwork {
 			layers,
 			input_size,
 			output_size,
+			training_metadata: None,
+			loss: Loss::default(),
+			optimizer: Optimizer::default(),
+			regularization: Regularization::default(),
+			max_gradient_norm: None,
+			max_weight_norm: None,
+			class_weights: None,
+			gradient_norm_history: Vec::new(),
 		})
 	}
 
@@ -83,166 +234,4001 @@ impl NeuralNetwork {
         Ok(next_in)
 	}
 
-	/// Get the number of layers in this neural network
-	pub fn get_layer_count(&self) -> usize {
-		self.layer_count
+	/// Run the network for training. This is the same as `activate`, except outputs of layers with dropout are randomly dropped
+	#[cfg(feature = "training")]
+	fn activate_training(&mut self, inputs: &[f64], rng: &mut impl Rng) -> crate::error::Result<Vec<f64>> {
+		if inputs.len() != self.input_size {
+            return Err(crate::error::InputSizeError {
+                    inputted: inputs.len(),
+                    expected: self.input_size,
+                    chain_depth: "NeuralNetwork".to_owned()
+                }.into()
+            );
+        }
+
+        let mut next_in = inputs.to_vec();
+
+        for layer in &mut self.layers {
+        	// All the sizes *should* be correct
+        	next_in = layer.activate(&next_in).expect("Length was already checked. This should not fail. (Network)");
+        	layer.apply_dropout(&mut next_in, rng);
+        }
+
+        Ok(next_in)
 	}
 
-	#[allow(dead_code)]
-	fn get_layer(&self, idx: usize) -> Option<&Layer> {
-		self.layers.get(idx)
+	/// Run the neural network and keep the output of every layer, for looking at what the hidden layers learned.
+	/// Returns one Vec per layer in order. The last one is the output of the network
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to the network
+	pub fn activate_with_intermediates(&mut self, inputs: &[f64]) -> crate::error::Result<Vec<Vec<f64>>> {
+		if inputs.len() != self.input_size {
+            return Err(crate::error::InputSizeError {
+                    inputted: inputs.len(),
+                    expected: self.input_size,
+                    chain_depth: "NeuralNetwork".to_owned()
+                }.into()
+            );
+        }
+
+        let mut outputs: Vec<Vec<f64>> = Vec::with_capacity(self.layer_count);
+
+        for layer in &mut self.layers {
+        	let next_in = outputs.last().map_or(inputs, |output| output.as_slice());
+        	// All the sizes *should* be correct
+        	let output = layer.activate(next_in).expect("Length was already checked. This should not fail. (Network)");
+        	outputs.push(output);
+        }
+
+        Ok(outputs)
 	}
 
-	fn get_layer_mut(&mut self, idx: usize) -> Option<&mut Layer> {
-		self.layers.get_mut(idx)
+	/// Run the neural network on many inputs and collect the outputs in the same order
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of inputs to the network. Every input is validated like in `activate`
+	pub fn activate_batch(&mut self, inputs: &[Vec<f64>]) -> crate::error::Result<Vec<Vec<f64>>> {
+		inputs.iter().map(|input| self.activate(input)).collect()
 	}
 
-	/// Calculate the loss of the network with a DataValue
+	/// Run the neural network and get the index of the largest output (the predicted class). Ties resolve to the lowest index.
 	///
 	/// Arguments:
 	///
-	/// * `value` - A reference to a DataValue
-	pub fn loss_with_value(&mut self, value: &DataValue) -> crate::error::Result<f64> {
-		if value.expected_output.len() != self.output_size {
-			return Err(crate::error::InputSizeError {
-			        inputted: value.expected_output.len(),
-			        expected: self.output_size,
-			        chain_depth: "NeuralNetwork".to_owned()
-			    }.into()
+	/// * `inputs` - A slice of f64s to be used as input to the network
+	pub fn predict_class(&mut self, inputs: &[f64]) -> crate::error::Result<usize> {
+		Ok(argmax(&self.activate(inputs)?))
+	}
+
+	/// Run the neural network with specific inputs without touching the training caches.
+	///
+	/// This gives the same outputs as `activate`, but only needs `&self`, so one network can be shared between threads for inference.
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to the network
+	pub fn predict(&self, inputs: &[f64]) -> crate::error::Result<Vec<f64>> {
+		if inputs.len() != self.input_size {
+            return Err(crate::error::InputSizeError {
+                    inputted: inputs.len(),
+                    expected: self.input_size,
+                    chain_depth: "NeuralNetwork".to_owned()
+                }.into()
+            );
+        }
+
+        let mut next_in = inputs.to_vec();
+
+        for layer in &self.layers {
+        	next_in = layer.predict(&next_in).expect("Length was already checked. This should not fail. (Network)")
+        }
+
+        Ok(next_in)
+	}
+
+	/// Copy the network into a `MatrixNetwork`, which runs each layer as one matrix multiplication.
+	///
+	/// The copy gives the same outputs as `predict` (within float tolerance) but is faster. It isn't updated when this network is trained.
+	#[cfg(feature = "ndarray")]
+	pub fn to_matrix(&self) -> crate::matrix::MatrixNetwork {
+		crate::matrix::MatrixNetwork::from_layers(&self.layers, self.input_size)
+	}
+
+	/// Measure how stable the network's prediction is when random inputs are zeroed.
+	/// Returns the fraction of masked runs whose argmax matches the argmax of the unmasked run.
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to the network
+	/// * `drop_prob` - The probability of each input being zeroed in a masked run
+	/// * `samples` - How many masked runs to do
+	/// * `rng` - The random number generator used to pick which inputs to zero
+	#[cfg(feature = "training")]
+	pub fn input_dropout_consistency(&self, inputs: &[f64], drop_prob: f64, samples: usize, rng: &mut impl Rng) -> crate::error::Result<f64> {
+		if !(0.0..=1.0).contains(&drop_prob) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "drop_prob".to_owned(),
+					reason: format!("{drop_prob} is not a probability between 0 and 1")
+				}.into()
 			);
 		}
-		
-		let output = self.activate(&value.input)?;
 
-		let mut loss = 0.0;
+		if samples == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "samples".to_owned(),
+					reason: "at least one sample is needed".to_owned()
+				}.into()
+			);
+		}
+
+		let expected_class = argmax(&self.predict(inputs)?);
 
-		for (actual, expected) in output.iter().zip(value.expected_output.iter()) {
-			loss += Neuron::loss(actual, expected);
+		let mut agreeing = 0;
+
+		for _ in 0..samples {
+			let masked: Vec<f64> = inputs.iter()
+				.map(|input| if rng.random_bool(drop_prob) {0.0} else {*input})
+				.collect();
+
+			if argmax(&self.predict(&masked)?) == expected_class {
+				agreeing += 1;
+			}
 		}
 
-		Ok(loss)
+		Ok(agreeing as f64 / samples as f64)
 	}
 
-	/// Calculate the average loss for a slice of DataValues.
-	/// This method should be preferred over `loss_with_value`
+	/// Calculate the average activation of a layer for each class in a dataset.
+	/// The class of a DataValue is the index of the largest value in its expected output.
+	///
+	/// Returns one Vec per class (in class order). Classes with no samples get a Vec of zeros.
 	///
 	/// Arguments:
 	///
-	/// * `values` - A slice of DataValues to test
-	pub fn loss(&mut self, values: &[DataValue]) -> crate::error::Result<f64> {
-		let mut total_loss = 0.0;
+	/// * `data` - A slice of DataValues to average over
+	/// * `layer` - The index of the layer to average. Defaults to the output layer
+	pub fn class_prototypes(&mut self, data: &[DataValue], layer: Option<usize>) -> crate::error::Result<Vec<Vec<f64>>> {
+		let layer = layer.unwrap_or(self.layer_count - 1);
 
-		let value_length = values.len();
+		let Some(layer_size) = self.get_layer(layer).map(|layer| layer.get_neuron_count()) else {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer".to_owned(),
+					reason: format!("the network only has {} layers", self.layer_count)
+				}.into()
+			);
+		};
 
-		for value in values {
-			total_loss += self.loss_with_value(value)?;
+		let mut sums = vec![vec![0.0; layer_size]; self.output_size];
+		let mut counts = vec![0usize; self.output_size];
+
+		for value in data {
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+
+			let class = argmax(&value.expected_output);
+			let activations = self.activate_with_intermediates(&value.input)?;
+
+			for (sum, activation) in sums[class].iter_mut().zip(&activations[layer]) {
+				*sum += activation;
+			}
+			counts[class] += 1;
+		}
+
+		for (sum, count) in sums.iter_mut().zip(counts) {
+			if count > 0 {
+				sum.iter_mut().for_each(|value| *value /= count as f64);
+			}
 		}
 
-		Ok(total_loss / (value_length as f64))
+		Ok(sums)
 	}
 
-	fn apply_gradients(&mut self, learn_rate: f64) {
-		for layeridx in 0..self.get_layer_count() {
-			let layer = self.get_layer_mut(layeridx).unwrap();
-			for neuronidx in 0..layer.get_neuron_count() {
-				let neuron = layer.get_neuron_mut(neuronidx).unwrap();
-				neuron.apply_gradients(learn_rate);
-			}
+	/// Calculate the effective rank of a layer's activations over some data: <https://infoscience.epfl.ch/record/110188>
+	///
+	/// This is the exponential of the entropy of the normalized singular values of the activation matrix (one row per sample).
+	/// It is between 1 and the layer size, and a low value means the representation has collapsed onto a few directions.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to collect activations for. Only the inputs are used
+	/// * `layer` - The index of the layer to measure
+	pub fn effective_rank(&mut self, data: &[DataValue], layer: usize) -> crate::error::Result<f64> {
+		if layer >= self.layer_count {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer".to_owned(),
+					reason: format!("the network only has {} layers", self.layer_count)
+				}.into()
+			);
+		}
+
+		if data.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "data".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+
+		let activations = data.iter()
+			.map(|value| self.activate_with_intermediates(&value.input).map(|mut outputs| outputs.swap_remove(layer)))
+			.collect::<crate::error::Result<Vec<_>>>()?;
+
+		// Without centering, the eigenvalues are the squared singular values divided by the number of samples
+		let (eigenvalues, _) = crate::training::principal_components(&activations);
+		let singular_values: Vec<f64> = eigenvalues.iter().map(|value| value.max(0.0).sqrt()).collect();
+
+		let total: f64 = singular_values.iter().sum();
+		if total == 0.0 {
+			return Ok(0.0);
 		}
+
+		let entropy: f64 = singular_values.iter()
+			.map(|value| value / total)
+			.filter(|p| *p > 0.0)
+			.map(|p| -p * p.ln())
+			.sum();
+
+		Ok(entropy.exp())
 	}
 
-	/// Train the network on some data
+	/// Run the network on inputs with missing values by averaging over guesses for them.
+	/// Each missing value is sampled from a standard normal distribution, so this works best with standardized inputs.
+	///
+	/// See `predict_with_missing_from` for the assumptions this makes.
 	///
 	/// Arguments:
 	///
-	/// * `training_data` - The data to train the network on in a slice of DataValues
-	/// * `learn_rate` - How fast the network should try to learn
-	pub fn learn(&mut self, training_data: &[DataValue], learn_rate: f64) -> crate::error::Result<()> {
-		for value in training_data {
-			self.update_all_gradients(value)?;
+	/// * `inputs` - A slice of inputs to the network. Missing inputs are `None`
+	/// * `samples` - How many runs to average over
+	/// * `rng` - The random number generator used to sample the missing inputs
+	#[cfg(feature = "training")]
+	pub fn predict_with_missing(&self, inputs: &[Option<f64>], samples: usize, rng: &mut impl Rng) -> crate::error::Result<Vec<f64>> {
+		self.predict_with_missing_from(inputs, samples, &StandardNormal, rng)
+	}
+
+	/// Run the network on inputs with missing values by averaging over guesses for them.
+	///
+	/// This approximately marginalizes over the missing inputs. It assumes every missing input follows `distribution`
+	/// and is independent of every other input (including the known ones).
+	/// If no inputs are missing, the network is only run once.
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of inputs to the network. Missing inputs are `None`
+	/// * `samples` - How many runs to average over
+	/// * `distribution` - The distribution to sample missing inputs from
+	/// * `rng` - The random number generator used to sample the missing inputs
+	#[cfg(feature = "training")]
+	pub fn predict_with_missing_from(&self, inputs: &[Option<f64>], samples: usize, distribution: &impl Distribution<f64>, rng: &mut impl Rng) -> crate::error::Result<Vec<f64>> {
+		if inputs.iter().all(Option::is_some) {
+			return self.predict(&inputs.iter().flatten().copied().collect::<Vec<_>>());
 		}
 
-		self.apply_gradients(learn_rate / (training_data.len() as f64));
+		if samples == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "samples".to_owned(),
+					reason: "at least one sample is needed".to_owned()
+				}.into()
+			);
+		}
 
-		Ok(())
+		let mut total = vec![0.0; self.output_size];
+
+		for _ in 0..samples {
+			let filled: Vec<f64> = inputs.iter()
+				.map(|input| input.unwrap_or_else(|| distribution.sample(rng)))
+				.collect();
+
+			for (sum, output) in total.iter_mut().zip(self.predict(&filled)?) {
+				*sum += output;
+			}
+		}
+
+		Ok(total.iter().map(|sum| sum / samples as f64).collect())
 	}
 
+	/// Calculate the accuracy-vs-coverage tradeoff for a classifier that abstains on low-confidence predictions.
+	/// The confidence of a prediction is its largest output.
+	///
+	/// Returns a `(threshold, coverage, accuracy)` tuple for each threshold, where coverage is the fraction of samples with a confidence above the threshold
+	/// and accuracy is the fraction of those samples that were classified correctly. If no samples are covered, the accuracy is NaN.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to test
+	/// * `thresholds` - The confidence thresholds to test
+	pub fn coverage_curve(&mut self, data: &[DataValue], thresholds: &[f64]) -> crate::error::Result<Vec<(f64, f64, f64)>> {
+		let predictions = self.confidence_predictions(data)?;
 
-	pub fn learn_randomly(&mut self, training_data: &[DataValue], learn_rate: f64, amount: usize) -> crate::error::Result<()> {
-		use rand::seq::SliceRandom;
-		let mut rand_split = training_data.to_vec();
+		Ok(thresholds.iter().map(|threshold| {
+			let covered: Vec<bool> = predictions.iter()
+				.filter(|(confidence, _)| confidence > threshold)
+				.map(|(_, correct)| *correct)
+				.collect();
 
-		// Shuffle the data
-		rand_split.shuffle(&mut rand::rng());
-		// Get the split
-		self.learn(&rand_split[..amount], learn_rate)
+			let coverage = covered.len() as f64 / data.len() as f64;
+			let accuracy = covered.iter().filter(|correct| **correct).count() as f64 / covered.len() as f64;
+
+			(*threshold, coverage, accuracy)
+		}).collect())
 	}
 
-	fn update_all_gradients(&mut self, value: &DataValue) -> crate::error::Result<()> {
-		if value.expected_output.len() != self.output_size {
-			return Err(crate::error::InputSizeError {
-			        inputted: value.expected_output.len(),
-			        expected: self.output_size,
-			        chain_depth: "NeuralNetwork".to_owned()
-			    }.into()
+	/// Calculate the expected calibration error (ECE): how far the network's confidence is from its actual accuracy.
+	/// The confidence of a prediction is its largest output.
+	///
+	/// Predictions are grouped into `bins` equal-width confidence bins. The ECE is the average gap between the
+	/// confidence and the accuracy of each bin, weighted by how many predictions are in it. 0 is perfectly calibrated.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to test
+	/// * `bins` - How many confidence bins to use
+	pub fn expected_calibration_error(&mut self, data: &[DataValue], bins: usize) -> crate::error::Result<f64> {
+		if bins == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "bins".to_owned(),
+					reason: "at least one bin is needed".to_owned()
+				}.into()
 			);
 		}
 
-		// Prep the network
-		self.activate(&value.input)?;
+		let predictions = self.confidence_predictions(data)?;
 
-		let output_layer = self.get_layer_mut(self.get_layer_count() - 1).expect("Length was already checked. This should not fail. (Network)");
-		output_layer.update_gradients_output(&value.expected_output);
-		
-		for layeridx in (0..self.get_layer_count()).rev().skip(1) {
-			// Fun borrow checker shenanigans
-			let (up_to_current, past_current) = self.layers.split_at_mut_checked(layeridx+1).expect("Length was already checked. This should not fail. (Network)");
-			let current_layer = up_to_current.get_mut(layeridx).expect("Length was already checked. This should not fail. (Network)");
-			let next_layer = past_current.first().expect("Length was already checked. This should not fail. (Network)");
-			current_layer.update_gradients_hidden(next_layer);
+		// (total confidence, correct predictions, predictions) for every bin
+		let mut totals = vec![(0.0, 0usize, 0usize); bins];
+		for (confidence, correct) in &predictions {
+			// A confidence of exactly 1 goes in the last bin
+			let bin = ((confidence * bins as f64) as usize).min(bins - 1);
+			totals[bin].0 += confidence;
+			totals[bin].1 += usize::from(*correct);
+			totals[bin].2 += 1;
 		}
 
-		Ok(())
+		Ok(totals.iter()
+			.filter(|(_, _, count)| *count > 0)
+			// |accuracy - average confidence| * count / total simplifies to this
+			.map(|(confidence, correct, _)| (*correct as f64 - confidence).abs() / predictions.len() as f64)
+			.sum())
 	}
 
-	#[cfg(feature = "serde")]
-	pub fn save(&self, file: &mut impl std::io::Write) -> std::io::Result<()> {
-		let mut buf = Vec::new();
-		self.serialize(&mut Serializer::new(&mut buf)).unwrap();
+	/// Run the network on every DataValue and get `(confidence, correct)` for each prediction.
+	/// The confidence is the largest output, and it is correct if it's the largest expected output.
+	fn confidence_predictions(&mut self, data: &[DataValue]) -> crate::error::Result<Vec<(f64, bool)>> {
+		if data.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "data".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
 
-		file.write_all(&buf)
-	}
+		let mut predictions = Vec::with_capacity(data.len());
+		for value in data {
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
 
-	#[cfg(feature = "serde")]
-	pub fn from_saved(file: impl std::io::Read) -> Result<Self, rmp_serde::decode::Error> {
-		//let mut buf = Vec::new();
-	
-		//file.read_to_end(&mut buf).unwrap();
+			let output = self.activate(&value.input)?;
+			let predicted = argmax(&output);
+			predictions.push((output[predicted], predicted == argmax(&value.expected_output)));
+		}
 
-		//Self::deserialize(&mut Deserializer::new(&buf[..]))
-		Self::deserialize(&mut Deserializer::new(file))
+		Ok(predictions)
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use super::*;
-	
-	#[test]
-	fn methods() {
-		let mut network = NeuralNetwork::new(&[2, 2], 2, vec![Activation::Sigmoid, Activation::Step]).unwrap();
+	/// Move this network's parameters towards another network's (Polyak averaging): `self = tau * source + (1 - tau) * self`.
+	/// This is how target networks slowly track the main network in reinforcement learning (DDPG, SAC, ...).
+	///
+	/// Arguments:
+	///
+	/// * `source` - The network to move towards. It must have the same layer sizes as this one
+	/// * `tau` - How far to move, between 0 (not at all) and 1 (copy the source)
+	pub fn soft_update_from(&mut self, source: &NeuralNetwork, tau: f64) -> crate::error::Result<()> {
+		if !(0.0..=1.0).contains(&tau) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "tau".to_owned(),
+					reason: format!("{tau} is not between 0 and 1")
+				}.into()
+			);
+		}
 
-		network.activate(&[0.0, 0.0]).unwrap();
+		if !self.same_topology(source) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "source".to_owned(),
+					reason: "the networks have different layer sizes".to_owned()
+				}.into()
+			);
+		}
 
-		assert_eq!(network.get_layer_count(), 2);
+		for (layer, source_layer) in self.layers.iter_mut().zip(&source.layers) {
+			layer.soft_update_from(source_layer, tau);
+		}
+
+		Ok(())
 	}
 
-	#[test]
-	fn errors() {
-		assert!(NeuralNetwork::new(&[], 0, vec![]).is_err());
-		assert!(NeuralNetwork::new(&[1], 0, vec![]).is_err());
+	/// Whether both networks have the same input size and layer sizes
+	fn same_topology(&self, other: &NeuralNetwork) -> bool {
+		self.input_size == other.input_size
+			&& self.layer_count == other.layer_count
+			&& self.layers.iter().zip(&other.layers).all(|(a, b)| a.get_neuron_count() == b.get_neuron_count())
+	}
 
-		let mut network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
-		assert!(network.activate(&[]).is_err());
+	/// Get the number of layers in this neural network
+	pub fn get_layer_count(&self) -> usize {
+		self.layer_count
+	}
+
+	/// Get how many inputs the network accepts
+	pub fn input_size(&self) -> usize {
+		self.input_size
+	}
+
+	/// Get how many outputs the network has (the number of neurons in the last layer)
+	pub fn output_size(&self) -> usize {
+		self.output_size
+	}
+
+	/// Get the loss function this network is trained with
+	pub fn get_loss(&self) -> &Loss {
+		&self.loss
+	}
+
+	/// Set the loss function this network is trained with. The default is `Loss::MSE`
+	pub fn set_loss(&mut self, loss: Loss) {
+		self.loss = loss;
+	}
+
+	/// Get the optimizer this network is trained with
+	pub fn get_optimizer(&self) -> &Optimizer {
+		&self.optimizer
+	}
+
+	/// Set the optimizer this network is trained with. The default is `Optimizer::SGD`
+	///
+	/// This also resets the optimizer state (like momentum). The state is kept between calls to `learn`.
+	/// Returns an `InvalidArgumentError` if AdaGrad's epsilon isn't positive
+	pub fn set_optimizer(&mut self, optimizer: Optimizer) -> crate::error::Result<()> {
+		optimizer.validate()?;
+
+		self.optimizer = optimizer;
+		self.reset_optimizer_state();
+		Ok(())
+	}
+
+	/// Get the weight penalties this network is trained with
+	pub fn get_regularization(&self) -> &Regularization {
+		&self.regularization
+	}
+
+	/// Set the weight penalties this network is trained with. There are none by default
+	pub fn set_regularization(&mut self, regularization: Regularization) {
+		self.regularization = regularization;
+	}
+
+	/// Get the global gradient norm that gradients are clipped to, if any
+	pub fn get_max_gradient_norm(&self) -> Option<f64> {
+		self.max_gradient_norm
+	}
+
+	/// Clip gradients by their global norm. When the L2 norm of the average gradient (all weights and biases together)
+	/// is bigger than `max_norm`, every gradient is scaled down so the norm is exactly `max_norm`. `None` turns clipping off (the default)
+	///
+	/// Arguments:
+	///
+	/// * `max_norm` - The biggest norm allowed. Must be positive
+	pub fn set_max_gradient_norm(&mut self, max_norm: Option<f64>) -> crate::error::Result<()> {
+		if max_norm.is_some_and(|max_norm| max_norm <= 0.0 || max_norm.is_nan()) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "max_norm".to_owned(),
+					reason: "it must be positive".to_owned()
+				}.into()
+			);
+		}
+
+		self.max_gradient_norm = max_norm;
+		Ok(())
+	}
+
+	/// Get the weight of each output's loss, if any
+	pub fn get_class_weights(&self) -> Option<&[f64]> {
+		self.class_weights.as_deref()
+	}
+
+	/// Weight the loss of each output, for imbalanced data. The loss (and so the gradient) of each output is multiplied by its weight,
+	/// so rare classes can be weighted higher to make the network pay attention to them. `None` weights every output by 1 (the default).
+	/// `replace_output_layer` resets them to `None`
+	///
+	/// Arguments:
+	///
+	/// * `class_weights` - One weight for each output. They can't be negative
+	pub fn set_class_weights(&mut self, class_weights: Option<Vec<f64>>) -> crate::error::Result<()> {
+		if let Some(weights) = &class_weights {
+			if weights.len() != self.output_size {
+				return Err(crate::error::InvalidArgumentError {
+						argument: "class_weights".to_owned(),
+						reason: format!("it needs one weight for each of the {} outputs, not {}", self.output_size, weights.len())
+					}.into()
+				);
+			}
+			if weights.iter().any(|weight| !weight.is_finite() || *weight < 0.0) {
+				return Err(crate::error::InvalidArgumentError {
+						argument: "class_weights".to_owned(),
+						reason: "every weight must be finite and not negative".to_owned()
+					}.into()
+				);
+			}
+		}
+
+		self.class_weights = class_weights;
+		Ok(())
+	}
+
+	/// The weight of an output's loss (1 without class weights)
+	fn class_weight(&self, outputidx: usize) -> f64 {
+		self.class_weights.as_ref().map_or(1.0, |weights| weights[outputidx])
+	}
+
+	/// Get the L2 norm that each neuron's weights are constrained to, if any
+	pub fn get_max_weight_norm(&self) -> Option<f64> {
+		self.max_weight_norm
+	}
+
+	/// Constrain the weights with a max-norm constraint. After every update, each neuron whose weights have an L2 norm bigger than
+	/// `max_norm` has its weights scaled down so the norm is exactly `max_norm`. Biases aren't constrained.
+	/// This is often used together with dropout. `None` turns the constraint off (the default)
+	///
+	/// Arguments:
+	///
+	/// * `max_norm` - The biggest norm allowed. Must be positive
+	pub fn set_max_weight_norm(&mut self, max_norm: Option<f64>) -> crate::error::Result<()> {
+		if max_norm.is_some_and(|max_norm| max_norm <= 0.0 || max_norm.is_nan()) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "max_norm".to_owned(),
+					reason: "it must be positive".to_owned()
+				}.into()
+			);
+		}
+
+		self.max_weight_norm = max_norm;
+		Ok(())
+	}
+
+	/// Reset everything left over from training so a new training run starts fresh.
+	/// This zeros the accumulated gradients, clears the cached values from the last run, forgets the optimizer state, and clears the gradient norm history.
+	/// The weights, biases, and settings are kept
+	pub fn reset_training_state(&mut self) {
+		for layer in &mut self.layers {
+			layer.reset_training_state();
+		}
+		self.gradient_norm_history.clear();
+	}
+
+	/// Forget the optimizer state (like momentum) so a new training run starts fresh
+	pub fn reset_optimizer_state(&mut self) {
+		for layer in &mut self.layers {
+			layer.reset_optimizer_state();
+		}
+	}
+
+	/// Get the global norm of the (averaged) gradient for every training step so far, in order.
+	/// Each call to `learn` is one step. The history isn't saved with the network
+	pub fn gradient_norm_history(&self) -> &[f64] {
+		&self.gradient_norm_history
+	}
+
+	/// Get the record of how this network was trained, if one was set
+	pub fn training_metadata(&self) -> Option<&TrainConfig> {
+		self.training_metadata.as_ref()
+	}
+
+	/// Set the record of how this network was trained. It is saved along with the network
+	pub fn set_training_metadata(&mut self, config: Option<TrainConfig>) {
+		self.training_metadata = config;
+	}
+
+	fn get_layer(&self, idx: usize) -> Option<&Layer> {
+		self.layers.get(idx)
+	}
+
+	fn get_layer_mut(&mut self, idx: usize) -> Option<&mut Layer> {
+		self.layers.get_mut(idx)
+	}
+
+	/// Calculate the loss of the network with a DataValue. The loss of each output is multiplied by its class weight if there are any.
+	/// With a softmax output layer and cross-entropy loss, the loss is computed from the log probabilities (log-sum-exp) so it stays exact for confident wrong outputs
+	///
+	/// Arguments:
+	///
+	/// * `value` - A reference to a DataValue
+	pub fn loss_with_value(&mut self, value: &DataValue) -> crate::error::Result<f64> {
+		if value.expected_output.len() != self.output_size {
+			return Err(crate::error::InputSizeError {
+			        inputted: value.expected_output.len(),
+			        expected: self.output_size,
+			        chain_depth: "NeuralNetwork".to_owned()
+			    }.into()
+			);
+		}
+		
+		let outputs = self.activate_with_intermediates(&value.input)?;
+
+		let output_layer = self.layers.last().expect("Length was already checked. This should not fail. (Network)");
+		if output_layer.is_softmax() && self.loss == Loss::CrossEntropy {
+			// Fused softmax and cross-entropy: use the log probabilities directly instead of taking the log of outputs that may have rounded to 0
+			let layer_inputs = outputs.len().checked_sub(2).map_or(value.input.as_slice(), |idx| outputs[idx].as_slice());
+			let mut weighted = output_layer.weighted_sums(layer_inputs);
+			output_layer.normalize(&mut weighted);
+			let log_outputs = Activation::log_softmax(&weighted);
+			return Ok(-log_outputs.iter().zip(&value.expected_output).enumerate().map(|(outputidx, (log_output, expected))| self.class_weight(outputidx) * expected * log_output).sum::<f64>());
+		}
+
+		let output = outputs.last().expect("Length was already checked. This should not fail. (Network)");
+		let mut loss = 0.0;
+
+		for (outputidx, (actual, expected)) in output.iter().zip(value.expected_output.iter()).enumerate() {
+			loss += self.class_weight(outputidx) * self.loss.call(*actual, *expected);
+		}
+
+		Ok(loss)
+	}
+
+	/// Calculate the average loss for a slice of DataValues.
+	/// This method should be preferred over `loss_with_value`
+	///
+	/// Training averages the gradients over each batch the same way, so this is the loss the gradients are the derivatives of
+	///
+	/// Arguments:
+	///
+	/// * `values` - A slice of DataValues to test
+	pub fn loss(&mut self, values: &[DataValue]) -> crate::error::Result<f64> {
+		Ok(self.loss_sum(values)? / (values.len() as f64))
+	}
+
+	/// Calculate the total (not averaged) loss for a slice of DataValues. This is `loss` times the number of values
+	///
+	/// Arguments:
+	///
+	/// * `values` - A slice of DataValues to test
+	pub fn loss_sum(&mut self, values: &[DataValue]) -> crate::error::Result<f64> {
+		let mut total_loss = 0.0;
+
+		for value in values {
+			total_loss += self.loss_with_value(value)?;
+		}
+
+		Ok(total_loss)
+	}
+
+	/// Calculate the fraction of DataValues that are classified correctly.
+	/// A DataValue is classified correctly if the largest output is at the same index as the largest value in its expected output.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to test
+	pub fn accuracy(&mut self, data: &[DataValue]) -> crate::error::Result<f64> {
+		if data.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "data".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+
+		let mut correct = 0;
+
+		for value in data {
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+
+			if self.predict_class(&value.input)? == argmax(&value.expected_output) {
+				correct += 1;
+			}
+		}
+
+		Ok(correct as f64 / data.len() as f64)
+	}
+
+	/// Calculate the fraction of DataValues whose class is among the `k` largest outputs.
+	/// The class of a DataValue is the index of the largest value in its expected output. Outputs that tie with the class's output don't push it out of the top `k`.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to test
+	/// * `k` - How many of the largest outputs to check. Must be between 1 and the number of outputs
+	pub fn top_k_accuracy(&mut self, data: &[DataValue], k: usize) -> crate::error::Result<f64> {
+		if data.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "data".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+		if k == 0 || k > self.output_size {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "k".to_owned(),
+					reason: format!("it must be between 1 and the number of outputs ({})", self.output_size)
+				}.into()
+			);
+		}
+
+		let mut correct = 0;
+
+		for value in data {
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+
+			let outputs = self.activate(&value.input)?;
+			let class_output = outputs[argmax(&value.expected_output)];
+			// The class is in the top k if fewer than k outputs beat it
+			if outputs.iter().filter(|output| **output > class_output).count() < k {
+				correct += 1;
+			}
+		}
+
+		Ok(correct as f64 / data.len() as f64)
+	}
+
+	/// Count how often each class is predicted as each other class. Entry `[actual][predicted]` is the number of DataValues
+	/// whose largest expected output is at `actual` and whose largest output is at `predicted`.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to test
+	/// * `num_classes` - The number of classes. This must be the same as the number of outputs
+	pub fn confusion_matrix(&mut self, data: &[DataValue], num_classes: usize) -> crate::error::Result<Vec<Vec<usize>>> {
+		if num_classes != self.output_size {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "num_classes".to_owned(),
+					reason: format!("the network has {} outputs", self.output_size)
+				}.into()
+			);
+		}
+
+		let mut matrix = vec![vec![0; num_classes]; num_classes];
+
+		for value in data {
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+
+			let predicted = self.predict_class(&value.input)?;
+			matrix[argmax(&value.expected_output)][predicted] += 1;
+		}
+
+		Ok(matrix)
+	}
+
+	/// Calculate the mean absolute error, root mean squared error, and R² of the network's outputs.
+	/// R² compares the squared errors to the squared distances of the expected outputs from each output's mean, summed over all outputs
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to test
+	pub fn regression_metrics(&mut self, data: &[DataValue]) -> crate::error::Result<RegressionMetrics> {
+		if data.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "data".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+
+		let mut absolute_error = 0.0;
+		let mut squared_error = 0.0;
+		let mut output_sums = vec![0.0; self.output_size];
+
+		for value in data {
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+
+			let output = self.activate(&value.input)?;
+			for ((actual, expected), sum) in output.iter().zip(&value.expected_output).zip(&mut output_sums) {
+				absolute_error += (actual - expected).abs();
+				squared_error += (actual - expected).powi(2);
+				*sum += expected;
+			}
+		}
+
+		let means: Vec<f64> = output_sums.iter().map(|sum| sum / data.len() as f64).collect();
+		let total_variation: f64 = data.iter()
+			.flat_map(|value| value.expected_output.iter().zip(&means).map(|(expected, mean)| (expected - mean).powi(2)))
+			.sum();
+
+		let count = (data.len() * self.output_size) as f64;
+		let r_squared = if total_variation == 0.0 {
+			// Constant expected outputs can only be fit perfectly or not at all
+			if squared_error == 0.0 { 1.0 } else { 0.0 }
+		} else {
+			1.0 - squared_error / total_variation
+		};
+
+		Ok(RegressionMetrics {
+			mae: absolute_error / count,
+			rmse: (squared_error / count).sqrt(),
+			r_squared,
+		})
+	}
+
+	/// Scale the accumulated gradients down if their norm (`gradient_norm`) is bigger than the maximum
+	#[cfg(feature = "training")]
+	fn clip_gradients(&mut self, gradient_norm: f64) {
+		if let Some(max_norm) = self.max_gradient_norm && gradient_norm > max_norm {
+			for layer in &mut self.layers {
+				layer.scale_gradients(max_norm / gradient_norm);
+			}
+		}
+	}
+
+	/// Apply the gradients accumulated over `batch_size` samples, then the max-norm constraint
+	#[cfg(feature = "training")]
+	fn apply_gradients(&mut self, learn_rate: f64, batch_size: usize) {
+		for layer in &mut self.layers {
+			layer.apply_gradients(learn_rate, batch_size, &self.optimizer, &self.regularization);
+			if let Some(max_norm) = self.max_weight_norm {
+				layer.constrain_weight_norms(max_norm);
+			}
+		}
+	}
+
+	/// Replace the output layer with a freshly initialized one and freeze every other layer.
+	/// Training after this only trains the new output layer (linear probing).
+	/// The class weights are reset to `None`, since they were for the old outputs.
+	///
+	/// Arguments:
+	///
+	/// * `new_size` - How many neurons the new output layer has
+	/// * `activation` - The activation function of the new output layer
+	#[cfg(feature = "training")]
+	pub fn replace_output_layer(&mut self, new_size: usize, activation: Activation) -> crate::error::Result<()> {
+		if new_size == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "new_size".to_owned(),
+					reason: "the output layer needs at least one neuron".to_owned()
+				}.into()
+			);
+		}
+
+		self.layers.pop();
+		for layer in &mut self.layers {
+			layer.set_frozen(true);
+		}
+
+		let input_size = self.layers.last().map_or(self.input_size, Layer::get_neuron_count);
+		self.layers.push(Layer::new(input_size, new_size, activation, &Init::default(), &Init::Zeros, &mut rand::rng()));
+		self.output_size = new_size;
+		self.class_weights = None;
+
+		Ok(())
+	}
+
+	/// Change the activation function of one layer. The weights and biases are kept.
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer to change
+	/// * `activation` - The new activation function
+	pub fn set_layer_activation(&mut self, layer: usize, activation: Activation) -> crate::error::Result<()> {
+		let layer_count = self.layer_count;
+		let Some(layer) = self.get_layer_mut(layer) else {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer".to_owned(),
+					reason: format!("the network only has {layer_count} layers")
+				}.into()
+			);
+		};
+
+		layer.set_activation(activation);
+		Ok(())
+	}
+
+	/// Change the activation function of every layer except the output layer. The weights and biases are kept.
+	pub fn set_hidden_activation(&mut self, activation: Activation) {
+		let hidden = self.layers.len() - 1;
+		for layer in &mut self.layers[..hidden] {
+			layer.set_activation(activation.clone());
+		}
+	}
+
+	/// Change the activation function of the output layer. The weights and biases are kept.
+	pub fn set_output_activation(&mut self, activation: Activation) {
+		self.layers.last_mut()
+			.expect("Networks always have at least one layer. This should not fail. (Network)")
+			.set_activation(activation);
+	}
+
+	/// Turn batch normalization on or off for a layer. Each weighted sum is normalized to a mean of 0 and a variance of 1, then scaled and shifted by learnable parameters before it is activated.
+	///
+	/// While training on batches of more than one DataValue, the statistics of the batch are used and running averages of them are kept.
+	/// Everywhere else (running the network, the loss, and training on single DataValues), the running averages are used.
+	/// Turning it on starts from fresh parameters and statistics.
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer
+	/// * `enabled` - Whether the layer should use batch normalization
+	pub fn set_batch_norm(&mut self, layer: usize, enabled: bool) -> crate::error::Result<()> {
+		let layer_count = self.layer_count;
+		let Some(layer) = self.get_layer_mut(layer) else {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer".to_owned(),
+					reason: format!("the network only has {layer_count} layers")
+				}.into()
+			);
+		};
+
+		layer.set_batch_norm(enabled);
+		Ok(())
+	}
+
+	/// Fold the batch normalization of every layer into the layer's weights and biases, for faster inference.
+	///
+	/// Outside of batch training, batch normalization uses its running statistics, which makes it an affine map of each weighted sum.
+	/// That map is absorbed into the weights and biases and batch normalization is turned off, so the network gives the same outputs with less work.
+	/// Training after folding trains the network without batch normalization
+	pub fn fold_batchnorm(&mut self) -> crate::error::Result<()> {
+		for layer in &mut self.layers {
+			layer.fold_batch_norm();
+		}
+
+		Ok(())
+	}
+
+	/// Turn layer normalization on or off for a layer. The weighted sums of each DataValue are normalized across the layer to a mean of 0 and a variance of 1,
+	/// then scaled and shifted by learnable parameters before they are activated.
+	///
+	/// Unlike batch normalization, every DataValue is normalized on its own, so training and running the network behave the same.
+	/// If the layer also uses batch normalization, layer normalization is applied after it. Turning it on starts from fresh parameters.
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer
+	/// * `enabled` - Whether the layer should use layer normalization
+	pub fn set_layer_norm(&mut self, layer: usize, enabled: bool) -> crate::error::Result<()> {
+		let layer_count = self.layer_count;
+		let Some(layer) = self.get_layer_mut(layer) else {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer".to_owned(),
+					reason: format!("the network only has {layer_count} layers")
+				}.into()
+			);
+		};
+
+		layer.set_layer_norm(enabled);
+		Ok(())
+	}
+
+	/// Set the dropout probability of a hidden layer. While training with `learn`, each output of the layer is zeroed with this probability
+	/// and the rest are scaled by `1 / (1 - dropout)`. Running the network normally is not affected.
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer. It can't be the output layer
+	/// * `dropout` - The probability of dropping each output, in [0, 1). 0 turns dropout off
+	pub fn set_dropout(&mut self, layer: usize, dropout: f64) -> crate::error::Result<()> {
+		if !(0.0..1.0).contains(&dropout) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "dropout".to_owned(),
+					reason: "it must be at least 0 and less than 1".to_owned()
+				}.into()
+			);
+		}
+
+		if layer + 1 >= self.layer_count {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer".to_owned(),
+					reason: format!("the network has {} hidden layers, and the output layer can't use dropout", self.layer_count - 1)
+				}.into()
+			);
+		}
+
+		self.layers[layer].set_dropout(dropout);
+		Ok(())
+	}
+
+	/// Stop training a single neuron. Gradients still flow through it to the earlier layers,
+	/// but its own weights, bias, and activation parameter stay the same
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer the neuron is in
+	/// * `neuron` - The index of the neuron in its layer
+	pub fn freeze_neuron(&mut self, layer: usize, neuron: usize) -> crate::error::Result<()> {
+		self.checked_neuron_mut(layer, neuron)?.set_frozen(true);
+		Ok(())
+	}
+
+	/// Resume training a neuron frozen with `freeze_neuron`
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer the neuron is in
+	/// * `neuron` - The index of the neuron in its layer
+	pub fn unfreeze_neuron(&mut self, layer: usize, neuron: usize) -> crate::error::Result<()> {
+		self.checked_neuron_mut(layer, neuron)?.set_frozen(false);
+		Ok(())
+	}
+
+	/// Stop training a whole layer, for example to keep a pretrained feature extractor fixed while training the layers after it.
+	/// Gradients still flow through it to the earlier layers, but it doesn't accumulate its own and its parameters stay the same.
+	/// Batch normalization in a frozen layer uses its running statistics during training too, and doesn't update them
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer
+	pub fn freeze_layer(&mut self, layer: usize) -> crate::error::Result<()> {
+		self.checked_layer_mut(layer)?.set_frozen(true);
+		Ok(())
+	}
+
+	/// Resume training a layer frozen with `freeze_layer` (or by `replace_output_layer`)
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer
+	pub fn unfreeze_layer(&mut self, layer: usize) -> crate::error::Result<()> {
+		self.checked_layer_mut(layer)?.set_frozen(false);
+		Ok(())
+	}
+
+	/// Whether a layer is frozen. Returns `None` if the layer doesn't exist
+	///
+	/// Arguments:
+	///
+	/// * `layer` - The index of the layer
+	pub fn is_layer_frozen(&self, layer: usize) -> Option<bool> {
+		self.get_layer(layer).map(Layer::is_frozen)
+	}
+
+	fn checked_layer_mut(&mut self, layer: usize) -> crate::error::Result<&mut Layer> {
+		let layer_count = self.layer_count;
+		self.get_layer_mut(layer).ok_or_else(|| crate::error::InvalidArgumentError {
+				argument: "layer".to_owned(),
+				reason: format!("the network only has {layer_count} layers")
+			}.into()
+		)
+	}
+
+	fn checked_neuron_mut(&mut self, layer: usize, neuron: usize) -> crate::error::Result<&mut Neuron> {
+		let layer = self.checked_layer_mut(layer)?;
+
+		let neuron_count = layer.get_neuron_count();
+		layer.get_neuron_mut(neuron).ok_or_else(|| crate::error::InvalidArgumentError {
+				argument: "neuron".to_owned(),
+				reason: format!("the layer only has {neuron_count} neurons")
+			}.into()
+		)
+	}
+
+	/// Calculate the average gradient over some data as one flat Vec.
+	/// The gradients are ordered by layer, then by neuron, with each neuron's weights followed by its bias.
+	///
+	/// The accumulated gradients are reset afterwards so this doesn't affect training.
+	#[cfg(feature = "training")]
+	fn flat_gradient(&mut self, data: &[DataValue]) -> crate::error::Result<Vec<f64>> {
+		if data.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "data".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+
+		let result = data.iter().try_for_each(|value| self.update_all_gradients(value));
+
+		let mut gradient = Vec::new();
+		for layer in &mut self.layers {
+			for neuronidx in 0..layer.get_neuron_count() {
+				let neuron = layer.get_neuron_mut(neuronidx).expect("Length was already checked. This should not fail. (Network)");
+				let loss_gradient = neuron.get_loss_gradient();
+				gradient.extend(loss_gradient.loss_gradient_weight.iter().map(|weight| weight / data.len() as f64));
+				gradient.push(loss_gradient.loss_gradient_bias / data.len() as f64);
+				neuron.reset_gradients();
+			}
+		}
+
+		result.map(|_| gradient)
+	}
+
+	/// Calculate the cosine similarity between the average gradients of two datasets.
+	/// Negative values mean training on one dataset works against the other.
+	///
+	/// If either gradient is zero, the similarity is 0.
+	///
+	/// Arguments:
+	///
+	/// * `data_a` - The first dataset
+	/// * `data_b` - The second dataset
+	#[cfg(feature = "training")]
+	pub fn gradient_cosine(&mut self, data_a: &[DataValue], data_b: &[DataValue]) -> crate::error::Result<f64> {
+		let gradient_a = self.flat_gradient(data_a)?;
+		let gradient_b = self.flat_gradient(data_b)?;
+
+		let dot: f64 = gradient_a.iter().zip(&gradient_b).map(|(a, b)| a * b).sum();
+		let norm_a = gradient_a.iter().map(|a| a * a).sum::<f64>().sqrt();
+		let norm_b = gradient_b.iter().map(|b| b * b).sum::<f64>().sqrt();
+
+		if norm_a == 0.0 || norm_b == 0.0 {
+			return Ok(0.0);
+		}
+
+		Ok(dot / (norm_a * norm_b))
+	}
+
+	/// Get a copy of a layer's weights, with one Vec per neuron holding the weight for each of its inputs.
+	/// Returns `None` if the layer doesn't exist
+	///
+	/// Arguments:
+	///
+	/// * `layer_idx` - The index of the layer
+	pub fn layer_weights(&self, layer_idx: usize) -> Option<Vec<Vec<f64>>> {
+		let layer = self.get_layer(layer_idx)?;
+		Some((0..layer.get_neuron_count())
+			.filter_map(|neuronidx| layer.get_neuron(neuronidx))
+			.map(|neuron| (0..neuron.get_weight_count()).filter_map(|weightidx| neuron.get_weight(weightidx)).copied().collect())
+			.collect())
+	}
+
+	/// Get a copy of a layer's biases, with one per neuron. Returns `None` if the layer doesn't exist
+	///
+	/// Arguments:
+	///
+	/// * `layer_idx` - The index of the layer
+	pub fn layer_biases(&self, layer_idx: usize) -> Option<Vec<f64>> {
+		let layer = self.get_layer(layer_idx)?;
+		Some((0..layer.get_neuron_count())
+			.filter_map(|neuronidx| layer.get_neuron(neuronidx))
+			.map(|neuron| *neuron.get_bias())
+			.collect())
+	}
+
+	/// Replace a layer's weights and biases, for example to start from a known solution.
+	/// The shapes are the same as in `layer_weights` and `layer_biases`
+	///
+	/// Arguments:
+	///
+	/// * `layer_idx` - The index of the layer
+	/// * `weights` - One Vec per neuron holding the weight for each of its inputs
+	/// * `biases` - One bias per neuron
+	pub fn set_layer_weights(&mut self, layer_idx: usize, weights: Vec<Vec<f64>>, biases: Vec<f64>) -> crate::error::Result<()> {
+		let layer_count = self.layer_count;
+		let Some(layer) = self.get_layer_mut(layer_idx) else {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "layer_idx".to_owned(),
+					reason: format!("the network only has {layer_count} layers")
+				}.into()
+			);
+		};
+
+		layer.set_weights(weights, biases)
+	}
+
+	/// The input size and the activation function of every neuron, which is enough to recreate the network with `from_architecture`
+	pub fn architecture(&self) -> Architecture {
+		Architecture {
+			input_size: self.input_size,
+			layer_activations: self.layers.iter()
+				.map(|layer| (0..layer.get_neuron_count()).filter_map(|neuronidx| layer.get_neuron(neuronidx)).map(|neuron| neuron.get_activation().clone()).collect())
+				.collect(),
+		}
+	}
+
+	/// The total number of learnable parameters in the network: the weights and biases, PReLU slopes, and batch and layer normalization's scales and shifts
+	pub fn num_parameters(&self) -> usize {
+		self.layers.iter().map(Layer::parameter_count).sum()
+	}
+
+	/// The number of weights and biases, which is the length of `flat_parameters`
+	fn num_weights_and_biases(&self) -> usize {
+		self.layers.iter().map(Layer::weight_and_bias_count).sum()
+	}
+
+	/// A table of the network's layers with each layer's input size, neuron count, activation, and parameter count
+	pub fn summary(&self) -> String {
+		let mut summary = format!("{:<8}{:<8}{:<10}{:<24}{}\n", "Layer", "Inputs", "Neurons", "Activation", "Parameters");
+		for (layeridx, layer) in self.layers.iter().enumerate() {
+			let mut activation = if layer.has_mixed_activations() { "Mixed".to_owned() } else { layer.get_activation().map(|activation| format!("{activation:?}")).unwrap_or_default() };
+			if layer.get_batch_norm().is_some() {
+				activation += " + BatchNorm";
+			}
+			if layer.get_layer_norm().is_some() {
+				activation += " + LayerNorm";
+			}
+			summary += &format!("{:<8}{:<8}{:<10}{:<24}{}\n", layeridx, layer.get_input_size(), layer.get_neuron_count(), activation, layer.parameter_count());
+		}
+		summary += &format!("Total parameters: {}", self.num_parameters());
+		summary
+	}
+
+	/// Get all of the weights and biases as one flat Vec, in the same order as `flat_gradient`
+	fn flat_parameters(&self) -> Vec<f64> {
+		let mut parameters = Vec::with_capacity(self.num_weights_and_biases());
+		for layer in &self.layers {
+			for neuron in (0..layer.get_neuron_count()).filter_map(|neuronidx| layer.get_neuron(neuronidx)) {
+				parameters.extend((0..neuron.get_weight_count()).filter_map(|weightidx| neuron.get_weight(weightidx)));
+				parameters.push(*neuron.get_bias());
+			}
+		}
+		parameters
+	}
+
+	/// Set all of the weights and biases from a flat slice in the order of `flat_parameters`. The length must already be checked
+	fn set_flat_parameters(&mut self, parameters: &[f64]) {
+		let mut parameters = parameters.iter();
+		for layer in &mut self.layers {
+			for neuronidx in 0..layer.get_neuron_count() {
+				let neuron = layer.get_neuron_mut(neuronidx).expect("Length was already checked. This should not fail. (Network)");
+				for weightidx in 0..neuron.get_weight_count() {
+					*neuron.get_weight_mut(weightidx).expect("Length was already checked. This should not fail. (Network)") = *parameters.next().expect("Length was already checked. This should not fail. (Network)");
+				}
+				neuron.set_bias(parameters.next().expect("Length was already checked. This should not fail. (Network)"));
+			}
+		}
+	}
+
+	/// Get every value the network's outputs depend on as one flat Vec, for moving the parameters to another library.
+	///
+	/// The order is by layer. Each layer has each neuron's weights, bias, and PReLU slope (only for PReLU neurons),
+	/// then the scales, shifts, running means, and running variances of batch normalization, then the gains and biases of layer normalization.
+	/// Because of the running statistics, this is longer than `num_parameters` when batch normalization is used. `load_parameters` reads the same order
+	pub fn export_parameters(&self) -> Vec<f64> {
+		let mut parameters = Vec::with_capacity(self.num_parameters());
+		for layer in &self.layers {
+			layer.export_parameters(&mut parameters);
+		}
+		parameters
+	}
+
+	/// Replace every value the network's outputs depend on with values from a flat slice, for using parameters that were trained elsewhere.
+	/// The order is the same as in `export_parameters`
+	///
+	/// Arguments:
+	///
+	/// * `parameters` - One value for each value in `export_parameters`
+	pub fn load_parameters(&mut self, parameters: &[f64]) -> crate::error::Result<()> {
+		let parameter_count = self.export_parameters().len();
+		if parameters.len() != parameter_count {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "parameters".to_owned(),
+					reason: format!("it needs one value for each of the {parameter_count} parameters, not {}", parameters.len())
+				}.into()
+			);
+		}
+
+		let mut parameters = parameters.iter();
+		for layer in &mut self.layers {
+			layer.load_parameters(&mut parameters);
+		}
+		Ok(())
+	}
+
+	/// Calculate the loss along a line through weight space, for visualizing the loss landscape.
+	/// For each alpha, the parameters are temporarily set to `parameters + alpha * direction` and the average loss is calculated.
+	/// The parameters are restored afterwards.
+	///
+	/// The direction has one value per weight and bias: by layer, then by neuron, with each neuron's weights followed by its bias.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to calculate the loss on
+	/// * `direction` - The direction to move in
+	/// * `alphas` - How far to move along the direction for each sample
+	pub fn loss_along_direction(&mut self, data: &[DataValue], direction: &[f64], alphas: &[f64]) -> crate::error::Result<Vec<f64>> {
+		let parameter_count = self.num_weights_and_biases();
+		if direction.len() != parameter_count {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "direction".to_owned(),
+					reason: format!("it needs one value for each of the {parameter_count} parameters, not {}", direction.len())
+				}.into()
+			);
+		}
+
+		let original = self.flat_parameters();
+
+		let losses = alphas.iter()
+			.map(|alpha| {
+				let moved: Vec<f64> = original.iter().zip(direction).map(|(parameter, step)| parameter + alpha * step).collect();
+				self.set_flat_parameters(&moved);
+				self.loss(data)
+			})
+			.collect();
+
+		self.set_flat_parameters(&original);
+
+		losses
+	}
+
+	/// Measure how sharp the loss minimum is: the average increase in loss when the weights and biases are moved
+	/// in a random direction by a distance of `epsilon`. Flat minima (low sharpness) tend to generalize better.
+	///
+	/// The parameters are restored afterwards.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to calculate the loss on
+	/// * `epsilon` - How far to move the parameters
+	/// * `samples` - How many random directions to average over
+	/// * `rng` - The random number generator used for the directions
+	#[cfg(feature = "training")]
+	pub fn sharpness(&mut self, data: &[DataValue], epsilon: f64, samples: usize, rng: &mut impl Rng) -> crate::error::Result<f64> {
+		if samples == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "samples".to_owned(),
+					reason: "at least one sample is needed".to_owned()
+				}.into()
+			);
+		}
+
+		let original = self.flat_parameters();
+		let original_loss = self.loss(data)?;
+
+		let mut total_increase = 0.0;
+		for _ in 0..samples {
+			let direction: Vec<f64> = (0..original.len()).map(|_| rng.sample(StandardNormal)).collect();
+			let norm = direction.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+			let moved: Vec<f64> = original.iter().zip(&direction).map(|(parameter, step)| parameter + epsilon * step / norm).collect();
+			self.set_flat_parameters(&moved);
+			let loss = self.loss(data);
+			self.set_flat_parameters(&original);
+
+			total_increase += loss? - original_loss;
+		}
+
+		Ok(total_increase / samples as f64)
+	}
+
+	/// Check backpropagation against finite differences, for testing new activation or loss functions.
+	/// Each weight and bias is moved by `epsilon` in both directions and the central difference of the average loss
+	/// is compared to the gradient from backpropagation.
+	///
+	/// Returns the largest relative error `|analytic - numerical| / max(|analytic| + |numerical|, 1e-8)`.
+	/// Values around 1e-7 or less mean the gradients agree. The parameters are restored afterwards.
+	///
+	/// Arguments:
+	///
+	/// * `data` - A slice of DataValues to calculate the loss on
+	/// * `epsilon` - How far to move each parameter. Around 1e-5 works well
+	#[cfg(feature = "training")]
+	pub fn gradient_check(&mut self, data: &[DataValue], epsilon: f64) -> crate::error::Result<f64> {
+		if epsilon <= 0.0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "epsilon".to_owned(),
+					reason: "it must be positive".to_owned()
+				}.into()
+			);
+		}
+
+		let analytic = self.flat_gradient(data)?;
+		let original = self.flat_parameters();
+		let mut moved = original.clone();
+
+		let mut max_error: f64 = 0.0;
+		for (idx, analytic) in analytic.iter().enumerate() {
+			moved[idx] = original[idx] + epsilon;
+			self.set_flat_parameters(&moved);
+			let loss_plus = self.loss(data);
+
+			moved[idx] = original[idx] - epsilon;
+			self.set_flat_parameters(&moved);
+			let loss_minus = self.loss(data);
+
+			moved[idx] = original[idx];
+			self.set_flat_parameters(&original);
+
+			let numerical = (loss_plus? - loss_minus?) / (2.0 * epsilon);
+			let error = (analytic - numerical).abs() / (analytic.abs() + numerical.abs()).max(1e-8);
+			max_error = max_error.max(error);
+		}
+
+		Ok(max_error)
+	}
+
+	/// Train the network on some data
+	///
+	/// Arguments:
+	///
+	/// * `training_data` - The data to train the network on in a slice of DataValues
+	/// * `learn_rate` - How fast the network should try to learn
+	///
+	/// Returns an `UntrainableActivationError` if a layer uses an activation function that stops gradients (like Step)
+	#[cfg(feature = "training")]
+	pub fn learn(&mut self, training_data: &[DataValue], learn_rate: f64) -> crate::error::Result<()> {
+		self.learn_with_rng(&training_data.iter().collect::<Vec<_>>(), learn_rate, &mut rand::rng())
+	}
+
+	/// `learn` on references to the DataValues (so subsets don't have to be copied), with the random number generator used for dropout
+	#[cfg(feature = "training")]
+	fn learn_with_rng(&mut self, training_data: &[&DataValue], learn_rate: f64, rng: &mut impl Rng) -> crate::error::Result<()> {
+		if let Some(layer) = self.layers.iter().position(Layer::blocks_gradients) {
+			return Err(crate::error::UntrainableActivationError { layer }.into());
+		}
+
+		if training_data.len() > 1 && self.layers.iter().any(|layer| layer.get_batch_norm().is_some()) {
+			self.accumulate_gradients_batch(training_data, rng)?;
+		} else {
+			#[cfg(feature = "rayon")]
+			self.accumulate_gradients_parallel(training_data, rng)?;
+			#[cfg(not(feature = "rayon"))]
+			for value in training_data {
+				self.accumulate_gradients(value, true, rng)?;
+			}
+		}
+
+		let gradient_norm = self.layers.iter().map(Layer::gradient_norm_squared).sum::<f64>().sqrt() / training_data.len() as f64;
+		self.gradient_norm_history.push(gradient_norm);
+		self.clip_gradients(gradient_norm);
+
+		self.apply_gradients(learn_rate, training_data.len());
+
+		Ok(())
+	}
+
+
+	#[cfg(feature = "training")]
+	pub fn learn_randomly(&mut self, training_data: &[DataValue], learn_rate: f64, amount: usize) -> crate::error::Result<()> {
+		self.learn_randomly_with_rng(training_data, learn_rate, amount, &mut rand::rng())
+	}
+
+	/// Train the network on a random subset of the data, like `learn_randomly`, but with a given random number generator.
+	/// The same seeded generator and data always pick the same subset (and drop the same outputs with dropout)
+	///
+	/// Arguments:
+	///
+	/// * `training_data` - The data to pick the subset from
+	/// * `learn_rate` - How fast the network should try to learn
+	/// * `amount` - How many DataValues to train on
+	/// * `rng` - The random number generator used to pick the subset
+	#[cfg(feature = "training")]
+	pub fn learn_randomly_with_rng(&mut self, training_data: &[DataValue], learn_rate: f64, amount: usize, rng: &mut impl Rng) -> crate::error::Result<()> {
+		use rand::seq::SliceRandom;
+		// Shuffle references instead of copying the data. This picks the same subset as shuffling the data itself
+		let mut rand_split: Vec<&DataValue> = training_data.iter().collect();
+
+		// Shuffle the data
+		rand_split.shuffle(rng);
+		// Get the split
+		self.learn_with_rng(&rand_split[..amount], learn_rate, rng)
+	}
+
+	/// Train the network on every DataValue once (one epoch) in shuffled mini-batches.
+	/// The data is split into `ceil(data.len() / batch_size)` disjoint batches, and the last one is smaller if the data doesn't divide evenly.
+	///
+	/// Arguments:
+	///
+	/// * `data` - The data to train the network on in a slice of DataValues
+	/// * `learn_rate` - How fast the network should try to learn
+	/// * `batch_size` - How many DataValues are in each batch. Must be at least 1
+	#[cfg(feature = "training")]
+	pub fn learn_minibatches(&mut self, data: &[DataValue], learn_rate: f64, batch_size: usize) -> crate::error::Result<()> {
+		if batch_size == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "batch_size".to_owned(),
+					reason: "it must be at least 1".to_owned()
+				}.into()
+			);
+		}
+
+		self.learn_batches(data, learn_rate, batch_size, &mut rand::rng())
+	}
+
+	/// Learn from each batch given by `minibatches`
+	#[cfg(feature = "training")]
+	fn learn_batches(&mut self, data: &[DataValue], learn_rate: f64, batch_size: usize, rng: &mut impl Rng) -> crate::error::Result<()> {
+		for batch in minibatches(data.len(), batch_size, rng) {
+			let batch: Vec<&DataValue> = batch.into_iter().map(|idx| &data[idx]).collect();
+			self.learn_with_rng(&batch, learn_rate, rng)?;
+		}
+
+		Ok(())
+	}
+
+	/// Train the network for a number of epochs. Every epoch, the training data is shuffled and split into batches, and the network learns from each batch with `learn`.
+	/// The config is saved as the network's training metadata afterwards.
+	///
+	/// Returns the average training loss (and validation loss if there is validation data) after each epoch.
+	///
+	/// Arguments:
+	///
+	/// * `train` - The data to train the network on
+	/// * `config` - The learn rate, number of epochs, batch size, and the seed used for shuffling and dropout
+	/// * `validation` - Data to measure the loss on after each epoch without training on it
+	#[cfg(feature = "training")]
+	pub fn fit(&mut self, train: &[DataValue], config: TrainConfig, validation: Option<&[DataValue]>) -> crate::error::Result<TrainHistory> {
+		self.fit_with_callback(train, config, validation, |_| {})
+	}
+
+	/// `fit`, but `callback` is called after every epoch with the epoch and its losses, for things like logging or progress bars
+	///
+	/// Arguments:
+	///
+	/// * `train` - The data to train the network on
+	/// * `config` - The learn rate, number of epochs, batch size, and the seed used for shuffling and dropout
+	/// * `validation` - Data to measure the loss on after each epoch without training on it
+	/// * `callback` - Called after every epoch
+	#[cfg(feature = "training")]
+	pub fn fit_with_callback(&mut self, train: &[DataValue], config: TrainConfig, validation: Option<&[DataValue]>, mut callback: impl FnMut(EpochInfo)) -> crate::error::Result<TrainHistory> {
+		use rand::SeedableRng;
+
+		if train.is_empty() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "train".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+		if validation.is_some_and(<[DataValue]>::is_empty) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "validation".to_owned(),
+					reason: "at least one DataValue is needed".to_owned()
+				}.into()
+			);
+		}
+		if config.batch_size == Some(0) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "batch_size".to_owned(),
+					reason: "it must be at least 1".to_owned()
+				}.into()
+			);
+		}
+
+		let mut rng = match config.seed {
+			Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+			None => rand::rngs::StdRng::from_rng(&mut rand::rng()),
+		};
+		let batch_size = config.batch_size.unwrap_or(train.len());
+
+		let mut history = TrainHistory::default();
+
+		for epoch in 0..config.epochs {
+			self.learn_batches(train, config.lr_schedule.learn_rate(config.learn_rate, epoch), batch_size, &mut rng)?;
+
+			let train_loss = self.loss(train)?;
+			let validation_loss = validation.map(|validation| self.loss(validation)).transpose()?;
+
+			history.train_loss.push(train_loss);
+			history.validation_loss.extend(validation_loss);
+			callback(EpochInfo { epoch, epochs: config.epochs, train_loss, validation_loss });
+		}
+
+		self.training_metadata = Some(config);
+
+		Ok(history)
+	}
+
+	/// Train the network one sample at a time, starting with the easiest samples (curriculum learning).
+	///
+	/// The data is sorted by `difficulty` (ascending) and the gradients are applied after every sample so the order matters.
+	/// A good difficulty is the per-sample loss from a previous pass (e.g. from `loss_with_value`), computed before calling this.
+	///
+	/// Arguments:
+	///
+	/// * `data` - The data to train the network on in a slice of DataValues
+	/// * `learn_rate` - How fast the network should try to learn
+	/// * `difficulty` - A function returning how hard a DataValue is. Lower values are trained on first
+	#[cfg(feature = "training")]
+	pub fn learn_curriculum(&mut self, data: &[DataValue], learn_rate: f64, difficulty: impl Fn(&DataValue) -> f64) -> crate::error::Result<()> {
+		for idx in curriculum_order(data, difficulty) {
+			self.learn(std::slice::from_ref(&data[idx]), learn_rate)?;
+		}
+
+		Ok(())
+	}
+
+	#[cfg(feature = "training")]
+	fn update_all_gradients(&mut self, value: &DataValue) -> crate::error::Result<()> {
+		self.accumulate_gradients(value, false, &mut rand::rng())
+	}
+
+	/// Accumulate the gradients of all the DataValues, splitting them between rayon's threads.
+	/// Each thread backpropagates its share on its own copy of the network and the copies' gradients are summed afterwards
+	#[cfg(feature = "rayon")]
+	fn accumulate_gradients_parallel(&mut self, training_data: &[&DataValue], rng: &mut impl Rng) -> crate::error::Result<()> {
+		use rand::SeedableRng;
+		use rayon::prelude::*;
+
+		let chunk_size = training_data.len().div_ceil(rayon::current_num_threads()).max(1);
+		if chunk_size >= training_data.len() {
+			// Copying the network isn't worth it for a single chunk
+			for value in training_data {
+				self.accumulate_gradients(value, true, rng)?;
+			}
+			return Ok(());
+		}
+
+		// Check the sizes up front so the threads don't have to send errors back
+		self.check_sizes(training_data)?;
+
+		// Each thread gets its own generator so the dropout masks only depend on `rng`
+		let seeds: Vec<u64> = (0..training_data.len().div_ceil(chunk_size)).map(|_| rng.random()).collect();
+
+		let workers: Vec<Vec<Layer>> = training_data.par_chunks(chunk_size)
+			.zip(seeds)
+			.map(|(chunk, seed)| {
+				let mut worker = self.worker_copy();
+				let mut worker_rng = rand::rngs::StdRng::seed_from_u64(seed);
+				for value in chunk {
+					worker.accumulate_gradients(value, true, &mut worker_rng).expect("Sizes were already checked. This should not fail. (Network)");
+				}
+				worker.layers
+			})
+			.collect();
+
+		for worker_layers in &workers {
+			for (layer, worker_layer) in self.layers.iter_mut().zip(worker_layers) {
+				layer.add_gradients_from(worker_layer);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Check that every DataValue has the right input and expected output sizes
+	#[cfg(feature = "training")]
+	fn check_sizes(&self, data: &[&DataValue]) -> crate::error::Result<()> {
+		for value in data {
+			if value.input.len() != self.input_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.input.len(),
+				        expected: self.input_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+			if value.expected_output.len() != self.output_size {
+				return Err(crate::error::InputSizeError {
+				        inputted: value.expected_output.len(),
+				        expected: self.output_size,
+				        chain_depth: "NeuralNetwork".to_owned()
+				    }.into()
+				);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Accumulate the gradients of all the DataValues by running them through each layer together.
+	/// Batch normalization needs this because every output depends on the statistics of the whole batch
+	#[cfg(feature = "training")]
+	fn accumulate_gradients_batch(&mut self, training_data: &[&DataValue], rng: &mut impl Rng) -> crate::error::Result<()> {
+		self.check_sizes(training_data)?;
+
+		let mut caches: Vec<crate::layer::BatchCache> = Vec::with_capacity(self.layer_count);
+		for layer in &mut self.layers {
+			let inputs = caches.last().map_or_else(|| training_data.iter().map(|value| value.input.clone()).collect(), |cache| cache.outputs().to_vec());
+			caches.push(layer.activate_batch_training(inputs, rng));
+		}
+
+		let class_weights = self.class_weights.as_deref();
+		let class_weight = |outputidx: usize| class_weights.map_or(1.0, |weights| weights[outputidx]);
+		let output_layer = self.layers.last_mut().expect("Length was already checked. This should not fail. (Network)");
+		let output_cache = caches.last().expect("Length was already checked. This should not fail. (Network)");
+		let mut derivs = if output_layer.is_softmax() && self.loss == Loss::CrossEntropy {
+			// The same simplification as `update_gradients_output`
+			let derivs = output_cache.outputs().iter().zip(training_data)
+				.map(|(outputs, value)| {
+					let weighted_expected: Vec<f64> = value.expected_output.iter().enumerate().map(|(outputidx, expected)| expected * class_weight(outputidx)).collect();
+					let expected_sum: f64 = weighted_expected.iter().sum();
+					outputs.iter().zip(weighted_expected).map(|(output, expected)| output * expected_sum - expected).collect()
+				})
+				.collect();
+			output_layer.backpropagate_batch_pre_activation(output_cache, derivs)
+		} else {
+			let derivs: Vec<Vec<f64>> = output_cache.outputs().iter().zip(training_data)
+				.map(|(outputs, value)| outputs.iter().zip(&value.expected_output).enumerate()
+					.map(|(outputidx, (output, expected))| self.loss.derivative(*output, *expected) * class_weight(outputidx))
+					.collect())
+				.collect();
+			output_layer.backpropagate_batch(output_cache, &derivs)
+		};
+
+		for (layer, cache) in self.layers.iter_mut().zip(&caches).rev().skip(1) {
+			derivs = layer.backpropagate_batch(cache, &derivs);
+		}
+
+		Ok(())
+	}
+
+	/// A copy of the network with the same parameters and empty training state
+	#[cfg(feature = "rayon")]
+	fn worker_copy(&self) -> NeuralNetwork {
+		NeuralNetwork {
+			layers: self.layers.iter().map(Layer::worker_copy).collect(),
+			layer_count: self.layer_count,
+			input_size: self.input_size,
+			output_size: self.output_size,
+			training_metadata: None,
+			loss: self.loss.clone(),
+			optimizer: self.optimizer.clone(),
+			regularization: self.regularization.clone(),
+			max_gradient_norm: self.max_gradient_norm,
+			max_weight_norm: self.max_weight_norm,
+			class_weights: self.class_weights.clone(),
+			gradient_norm_history: Vec::new(),
+		}
+	}
+
+	/// Backpropagate one DataValue and add its gradients to the accumulated ones. Dropout is only used when `dropout` is true, with `rng` picking the dropped outputs
+	#[cfg(feature = "training")]
+	fn accumulate_gradients(&mut self, value: &DataValue, dropout: bool, rng: &mut impl Rng) -> crate::error::Result<()> {
+		if value.expected_output.len() != self.output_size {
+			return Err(crate::error::InputSizeError {
+			        inputted: value.expected_output.len(),
+			        expected: self.output_size,
+			        chain_depth: "NeuralNetwork".to_owned()
+			    }.into()
+			);
+		}
+
+		// Prep the network
+		if dropout {
+			self.activate_training(&value.input, rng)?;
+		} else {
+			self.activate(&value.input)?;
+		}
+
+		let output_layer = self.layers.last_mut().expect("Length was already checked. This should not fail. (Network)");
+		output_layer.update_gradients_output(&value.expected_output, &self.loss, self.class_weights.as_deref());
+		
+		for layeridx in (0..self.get_layer_count()).rev().skip(1) {
+			// Fun borrow checker shenanigans
+			let (up_to_current, past_current) = self.layers.split_at_mut_checked(layeridx+1).expect("Length was already checked. This should not fail. (Network)");
+			let current_layer = up_to_current.get_mut(layeridx).expect("Length was already checked. This should not fail. (Network)");
+			let next_layer = past_current.first().expect("Length was already checked. This should not fail. (Network)");
+			current_layer.update_gradients_hidden(next_layer);
+		}
+
+		Ok(())
+	}
+
+	/// Calculate the derivative of some function of the outputs with respect to each input.
+	/// `output_derivs` is the derivative of that function with respect to each output. No gradients are accumulated.
+	fn input_gradient(&mut self, inputs: &[f64], output_derivs: &[f64]) -> crate::error::Result<Vec<f64>> {
+		self.activate(inputs)?;
+
+		let output_layer = self.get_layer_mut(self.layer_count - 1).expect("Length was already checked. This should not fail. (Network)");
+		output_layer.calculate_derivs(output_derivs);
+
+		for layeridx in (0..self.layer_count).rev().skip(1) {
+			// Fun borrow checker shenanigans
+			let (up_to_current, past_current) = self.layers.split_at_mut(layeridx+1);
+			up_to_current[layeridx].calculate_derivs_hidden(&past_current[0]);
+		}
+
+		let first_layer = self.get_layer(0).expect("Length was already checked. This should not fail. (Network)");
+		Ok((0..self.input_size).map(|inputidx| first_layer.input_deriv(inputidx)).collect())
+	}
+
+	/// Find a small change to the inputs that changes the predicted class (DeepFool: <https://arxiv.org/abs/1511.04599>).
+	///
+	/// Each step linearizes the network around the current inputs and moves to the nearest linearized decision boundary.
+	/// The total step is overshot slightly so it crosses the boundary. If the class hasn't changed after `max_iter` steps,
+	/// the perturbation so far is returned.
+	///
+	/// Arguments:
+	///
+	/// * `inputs` - A slice of f64s to be used as input to the network
+	/// * `max_iter` - The maximum number of steps to take
+	pub fn minimal_perturbation(&mut self, inputs: &[f64], max_iter: usize) -> crate::error::Result<Vec<f64>> {
+		let overshoot = 0.02;
+
+		if self.output_size < 2 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "self".to_owned(),
+					reason: "the network needs at least 2 outputs to change class".to_owned()
+				}.into()
+			);
+		}
+
+		let original_class = argmax(&self.activate(inputs)?);
+		let mut perturbation = vec![0.0; inputs.len()];
+		let mut perturbed = inputs.to_vec();
+
+		for _ in 0..max_iter {
+			let outputs = self.activate(&perturbed)?;
+			if argmax(&outputs) != original_class {
+				break;
+			}
+
+			let jacobian = (0..self.output_size)
+				.map(|output_idx| {
+					let mut output_derivs = vec![0.0; self.output_size];
+					output_derivs[output_idx] = 1.0;
+					self.input_gradient(&perturbed, &output_derivs)
+				})
+				.collect::<crate::error::Result<Vec<_>>>()?;
+
+			// Find the closest boundary: (distance, output gap, gradient gap)
+			let mut closest: Option<(f64, f64, Vec<f64>)> = None;
+			for class in (0..self.output_size).filter(|class| *class != original_class) {
+				let gradient_gap: Vec<f64> = jacobian[class].iter().zip(&jacobian[original_class]).map(|(a, b)| a - b).collect();
+				let norm = gradient_gap.iter().map(|x| x * x).sum::<f64>().sqrt();
+				if norm == 0.0 {
+					continue;
+				}
+
+				let output_gap = outputs[class] - outputs[original_class];
+				let distance = output_gap.abs() / norm;
+				if closest.as_ref().is_none_or(|(closest_distance, _, _)| distance < *closest_distance) {
+					closest = Some((distance, output_gap, gradient_gap));
+				}
+			}
+
+			// No output can change, so there is nowhere to go
+			let Some((_, output_gap, gradient_gap)) = closest else {
+				break;
+			};
+
+			let norm_squared: f64 = gradient_gap.iter().map(|x| x * x).sum();
+			// The small constant makes sure each step actually reaches the boundary
+			let scale = (output_gap.abs() + 1e-4) / norm_squared;
+			for ((total, step), (perturbed, input)) in perturbation.iter_mut().zip(&gradient_gap).zip(perturbed.iter_mut().zip(inputs)) {
+				*total += scale * step;
+				*perturbed = input + (1.0 + overshoot) * *total;
+			}
+		}
+
+		Ok(perturbation.iter().map(|x| (1.0 + overshoot) * x).collect())
+	}
+
+	/// Find an input that maximizes one output (activation maximization), for visualizing what the network has learned.
+	///
+	/// Starts from a random input (standard normal) and takes `steps` steps of gradient ascent on the target output.
+	/// An L2 penalty pulls the input toward zero, which keeps it from growing without bound on unbounded activations.
+	///
+	/// Arguments:
+	///
+	/// * `class` - The index of the output to maximize
+	/// * `steps` - How many gradient ascent steps to take
+	/// * `lr` - The size of each step
+	/// * `l2` - How strongly to penalize large inputs. 0.0 disables the penalty
+	/// * `rng` - The random number generator used for the starting input
+	#[cfg(feature = "training")]
+	pub fn maximize_output(&mut self, class: usize, steps: usize, lr: f64, l2: f64, rng: &mut impl Rng) -> crate::error::Result<Vec<f64>> {
+		if class >= self.output_size {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "class".to_owned(),
+					reason: format!("the network only has {} outputs", self.output_size)
+				}.into()
+			);
+		}
+
+		let mut inputs: Vec<f64> = (0..self.input_size).map(|_| rng.sample(StandardNormal)).collect();
+
+		let mut output_derivs = vec![0.0; self.output_size];
+		output_derivs[class] = 1.0;
+
+		for _ in 0..steps {
+			let gradient = self.input_gradient(&inputs, &output_derivs)?;
+			for (input, deriv) in inputs.iter_mut().zip(gradient) {
+				*input += lr * (deriv - 2.0 * l2 * *input);
+			}
+		}
+
+		Ok(inputs)
+	}
+
+	/// Save the network to a writer in messagepack format.
+	///
+	/// A header with `SAVE_MAGIC` and the save format version is written before the network so that `load` can reject files it doesn't understand.
+	///
+	/// Networks with custom activation functions can't be saved and return an error.
+	#[cfg(feature = "serde")]
+	pub fn save(&self, file: &mut impl std::io::Write) -> std::io::Result<()> {
+		let mut buf = Vec::new();
+		buf.extend_from_slice(SAVE_MAGIC);
+		let mut serializer = Serializer::new(&mut buf);
+		FORMAT_VERSION.serialize(&mut serializer).map_err(std::io::Error::other)?;
+		self.serialize(&mut serializer).map_err(std::io::Error::other)?;
+
+		file.write_all(&buf)
+	}
+
+	/// Load a network that was saved with `save`. The loaded network can be trained further.
+	///
+	/// Files saved with older versions (including before the format had a header or was versioned) are still loaded.
+	/// Files with a version newer than `FORMAT_VERSION` return an `UnsupportedVersionError`,
+	/// and files that aren't neuralib saves return an `InvalidSaveError`.
+	#[cfg(feature = "serde")]
+	pub fn load(mut reader: impl std::io::Read) -> crate::error::Result<Self> {
+		let mut buf = Vec::new();
+		reader.read_to_end(&mut buf)?;
+
+		let invalid = |reason: String| crate::error::InvalidSaveError { reason };
+
+		let mut network: Self = if let Some(rest) = buf.strip_prefix(SAVE_MAGIC) {
+			let mut deserializer = Deserializer::new(rest);
+
+			let version = u32::deserialize(&mut deserializer)
+				.map_err(|err| invalid(format!("the format version couldn't be read ({err})")))?;
+			if version > FORMAT_VERSION {
+				return Err(crate::error::UnsupportedVersionError {
+						found: version,
+						supported: FORMAT_VERSION,
+					}.into()
+				);
+			}
+
+			// Fields added since version 1 are filled in with their defaults:
+			// training metadata (2), the loss function (3), the optimizer (4), frozen neurons (5), regularization (6), L1 regularization (7), dropout (8), gradient clipping (10), the training batch size (11), the learn rate schedule (12), batch normalization (13), layer normalization (14), the max-norm weight constraint (15), and class weights (16)
+			Self::deserialize(&mut deserializer)
+				.map_err(|err| invalid(format!("the network couldn't be read ({err})")))?
+		} else {
+			let mut deserializer = Deserializer::new(&buf[..]);
+
+			match u32::deserialize(&mut deserializer) {
+				Ok(1..=LAST_HEADERLESS_VERSION) => Self::deserialize(&mut deserializer),
+				Ok(found) => return Err(invalid(format!("there is no header, and {found} isn't a version from before headers were added")).into()),
+				// Unversioned saves (version 0) start with the network itself
+				Err(_) => Self::deserialize(&mut Deserializer::new(&buf[..])),
+			}.map_err(|err| invalid(format!("there is no header, and it couldn't be read as an older save ({err})")))?
+		};
+
+		network.restore_training_buffers();
+
+		Ok(network)
+	}
+
+	/// Save the network to a writer as (pretty printed) JSON, so it can be read and edited by hand.
+	///
+	/// The JSON is an object with the save format `version` and the `network`, which lists its layers and each layer's neurons
+	/// with their weights, bias, and activation function.
+	///
+	/// Networks with custom activation functions can't be saved and return an error.
+	#[cfg(feature = "serde")]
+	pub fn save_json(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+		let saved = SavedNetwork { version: FORMAT_VERSION, network: self };
+		serde_json::to_writer_pretty(writer, &saved).map_err(std::io::Error::other)
+	}
+
+	/// Load a network that was saved with `save_json`. The loaded network can be trained further.
+	///
+	/// Fields that are missing (like ones added in newer versions) are filled in with their defaults.
+	/// Files with a version newer than `FORMAT_VERSION` return an `UnsupportedVersionError`.
+	#[cfg(feature = "serde")]
+	pub fn load_json(reader: impl std::io::Read) -> crate::error::Result<Self> {
+		let saved: SavedNetwork<Self> = serde_json::from_reader(reader)?;
+
+		if saved.version > FORMAT_VERSION {
+			return Err(crate::error::UnsupportedVersionError {
+					found: saved.version,
+					supported: FORMAT_VERSION,
+				}.into()
+			);
+		}
+
+		let mut network = saved.network;
+		network.restore_training_buffers();
+
+		Ok(network)
+	}
+
+	/// Recreate the buffers that are skipped when saving
+	#[cfg(feature = "serde")]
+	fn restore_training_buffers(&mut self) {
+		for layer in &mut self.layers {
+			layer.restore_training_buffers();
+		}
+	}
+
+	/// Load a network that was saved with `save`.
+	#[cfg(feature = "serde")]
+	#[deprecated(since="0.0.2", note="Please use load instead")]
+	pub fn from_saved(file: impl std::io::Read) -> crate::error::Result<Self> {
+		Self::load(file)
+	}
+}
+
+/// The layout of JSON saves
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SavedNetwork<N> {
+	version: u32,
+	network: N,
+}
+
+/// Get the indices of the data sorted by difficulty (ascending). Samples with the same difficulty keep their order.
+#[cfg(feature = "training")]
+fn curriculum_order(data: &[DataValue], difficulty: impl Fn(&DataValue) -> f64) -> Vec<usize> {
+	let difficulties: Vec<f64> = data.iter().map(difficulty).collect();
+
+	let mut order: Vec<usize> = (0..data.len()).collect();
+	order.sort_by(|a, b| difficulties[*a].total_cmp(&difficulties[*b]));
+
+	order
+}
+
+/// Builds a `NeuralNetwork` one layer at a time, so the layer sizes and activation functions can't get out of line
+///
+/// ```
+/// use neuralib::{network::NetworkBuilder, activation::Activation};
+///
+/// let network = NetworkBuilder::new(784)
+///     .layer(100, Activation::Sigmoid)
+///     .layer(10, Activation::Softmax)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg(feature = "training")]
+pub struct NetworkBuilder {
+	input_size: usize,
+	// The activation function of every neuron in every layer
+	layer_activations: Vec<Vec<Activation>>,
+	weight_init: Init,
+	bias_init: Init,
+	seed: Option<u64>,
+	loss: Loss,
+	optimizer: Optimizer,
+}
+
+#[cfg(feature = "training")]
+impl NetworkBuilder {
+	/// Start building a network that takes `input_size` inputs
+	pub fn new(input_size: usize) -> NetworkBuilder {
+		NetworkBuilder {
+			input_size,
+			bias_init: Init::Zeros,
+			..Default::default()
+		}
+	}
+
+	/// Add a layer after the previous one (or the inputs)
+	///
+	/// Arguments:
+	///
+	/// * `size` - How many neurons the layer has
+	/// * `activation` - The activation function of the layer
+	pub fn layer(mut self, size: usize, activation: Activation) -> NetworkBuilder {
+		self.layer_activations.push(vec![activation; size]);
+		self
+	}
+
+	/// Add a layer where every neuron has its own activation function, with one neuron per activation function.
+	/// Softmax can't be mixed with other activation functions
+	///
+	/// Arguments:
+	///
+	/// * `activations` - The activation function of each neuron in the layer
+	pub fn layer_per_neuron(mut self, activations: Vec<Activation>) -> NetworkBuilder {
+		self.layer_activations.push(activations);
+		self
+	}
+
+	/// How to initialize the weights. The default is `Init::He`
+	pub fn weight_init(mut self, init: Init) -> NetworkBuilder {
+		self.weight_init = init;
+		self
+	}
+
+	/// How to initialize the biases. The default is `Init::Zeros`
+	pub fn bias_init(mut self, init: Init) -> NetworkBuilder {
+		self.bias_init = init;
+		self
+	}
+
+	/// Initialize the network from a seeded random number generator, like `NeuralNetwork::new_seeded`
+	pub fn seed(mut self, seed: u64) -> NetworkBuilder {
+		self.seed = Some(seed);
+		self
+	}
+
+	/// The loss function the network is trained with. The default is `Loss::MSE`
+	pub fn loss(mut self, loss: Loss) -> NetworkBuilder {
+		self.loss = loss;
+		self
+	}
+
+	/// The optimizer the network is trained with. The default is `Optimizer::SGD`
+	pub fn optimizer(mut self, optimizer: Optimizer) -> NetworkBuilder {
+		self.optimizer = optimizer;
+		self
+	}
+
+	/// Create the network. Returns a `NoLayersError` if no layers were added, or an `InvalidArgumentError` if a layer mixes softmax with other activation functions or an initialization or the optimizer is invalid
+	pub fn build(self) -> crate::error::Result<NeuralNetwork> {
+		use rand::SeedableRng;
+
+		self.optimizer.validate()?;
+
+		let mut network = match self.seed {
+			Some(seed) => NeuralNetwork::from_layer_activations(self.input_size, self.layer_activations, &self.weight_init, &self.bias_init, &mut rand::rngs::StdRng::seed_from_u64(seed)),
+			None => NeuralNetwork::from_layer_activations(self.input_size, self.layer_activations, &self.weight_init, &self.bias_init, &mut rand::rng()),
+		}?;
+		network.loss = self.loss;
+		network.optimizer = self.optimizer;
+
+		Ok(network)
+	}
+}
+
+/// Shuffle the indices of the data and split them into batches of `batch_size` (the last batch gets what is left over)
+#[cfg(feature = "training")]
+fn minibatches(len: usize, batch_size: usize, rng: &mut impl Rng) -> Vec<Vec<usize>> {
+	use rand::seq::SliceRandom;
+
+	let mut order: Vec<usize> = (0..len).collect();
+	order.shuffle(rng);
+
+	order.chunks(batch_size).map(<[usize]>::to_vec).collect()
+}
+
+/// Get the index of the largest value. Ties resolve to the lowest index.
+pub(crate) fn argmax(values: &[f64]) -> usize {
+	let mut max_idx = 0;
+
+	for (idx, value) in values.iter().enumerate() {
+		if *value > values[max_idx] {
+			max_idx = idx;
+		}
+	}
+
+	max_idx
+}
+
+#[cfg(all(test, feature = "training"))]
+mod tests {
+	use super::*;
+	use crate::error::NeuralibError;
+	use crate::optimizer::LrSchedule;
+
+	/// A single linear layer that outputs its inputs
+	fn identity_network(size: usize) -> NeuralNetwork {
+		let mut network = NeuralNetwork::new(&[size], size, vec![Activation::Linear]).unwrap();
+
+		let layer = network.get_layer_mut(0).unwrap();
+		for neuronidx in 0..size {
+			let neuron = layer.get_neuron_mut(neuronidx).unwrap();
+			for weightidx in 0..size {
+				neuron.set_weight(weightidx, &if weightidx == neuronidx {1.0} else {0.0}).unwrap();
+			}
+		}
+
+		network
+	}
+	
+	#[test]
+	fn methods() {
+		let mut network = NeuralNetwork::new(&[2, 2], 2, vec![Activation::Sigmoid, Activation::Step]).unwrap();
+
+		network.activate(&[0.0, 0.0]).unwrap();
+
+		assert_eq!(network.get_layer_count(), 2);
+	}
+
+	#[test]
+	fn errors() {
+		assert!(matches!(NeuralNetwork::new(&[], 0, vec![]), Err(NeuralibError::NoLayers(_))));
+
+		let Err(NeuralibError::LayerActivationMismatch(err)) = NeuralNetwork::new(&[1, 2], 0, vec![Activation::Linear]) else {
+			panic!("Expected a LayerActivationMismatchError");
+		};
+		assert_eq!(err.num_layers, 2);
+		assert_eq!(err.num_activations, 1);
+
+		let mut network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
+		assert!(network.activate(&[]).is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn save_and_load() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 0.0] }];
+		network.learn(&data, 0.1).unwrap();
+
+		let mut buf = Vec::new();
+		network.save(&mut buf).unwrap();
+		let mut loaded = NeuralNetwork::load(&buf[..]).unwrap();
+
+		assert_eq!(loaded.get_layer_count(), 2);
+		assert_eq!(network.activate(&[0.5, -1.0]).unwrap(), loaded.activate(&[0.5, -1.0]).unwrap());
+
+		// The loaded network can keep training
+		network.learn(&data, 0.1).unwrap();
+		loaded.learn(&data, 0.1).unwrap();
+		assert_eq!(network.activate(&[0.5, -1.0]).unwrap(), loaded.activate(&[0.5, -1.0]).unwrap());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn save_and_load_json() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::leaky_re_lu_default(), Activation::Softmax]).unwrap();
+		network.learn(&[DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 0.0] }], 0.1).unwrap();
+
+		let mut buf = Vec::new();
+		network.save_json(&mut buf).unwrap();
+
+		let json = String::from_utf8(buf.clone()).unwrap();
+		assert!(json.contains("\"weights\""));
+		assert!(json.contains("\"LeakyReLU\""));
+
+		let mut loaded = NeuralNetwork::load_json(&buf[..]).unwrap();
+		assert_eq!(network.activate(&[0.5, -1.0]).unwrap(), loaded.activate(&[0.5, -1.0]).unwrap());
+
+		let newer = json.replacen(&format!("\"version\": {FORMAT_VERSION}"), &format!("\"version\": {}", FORMAT_VERSION + 1), 1);
+		assert!(NeuralNetwork::load_json(newer.as_bytes()).is_err());
+		assert!(NeuralNetwork::load_json(&b"not json"[..]).is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn load_unversioned() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+
+		// Saves from before the format was versioned only contain the network
+		let mut buf = Vec::new();
+		network.serialize(&mut Serializer::new(&mut buf)).unwrap();
+		let mut loaded = NeuralNetwork::load(&buf[..]).unwrap();
+
+		assert_eq!(network.activate(&[0.5, -1.0]).unwrap(), loaded.activate(&[0.5, -1.0]).unwrap());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn load_unsupported_version() {
+		let network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
+
+		let mut buf = SAVE_MAGIC.to_vec();
+		let mut serializer = Serializer::new(&mut buf);
+		(FORMAT_VERSION + 1).serialize(&mut serializer).unwrap();
+		network.serialize(&mut serializer).unwrap();
+
+		let Err(NeuralibError::UnsupportedVersion(err)) = NeuralNetwork::load(&buf[..]) else {
+			panic!("Expected an UnsupportedVersionError");
+		};
+		assert_eq!(err.found, FORMAT_VERSION + 1);
+		assert_eq!(err.supported, FORMAT_VERSION);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn load_headerless() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+
+		// Saves from before the header only have the version
+		let mut buf = Vec::new();
+		let mut serializer = Serializer::new(&mut buf);
+		LAST_HEADERLESS_VERSION.serialize(&mut serializer).unwrap();
+		network.serialize(&mut serializer).unwrap();
+		let mut loaded = NeuralNetwork::load(&buf[..]).unwrap();
+
+		assert_eq!(network.activate(&[0.5, -1.0]).unwrap(), loaded.activate(&[0.5, -1.0]).unwrap());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn load_invalid() {
+		let network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let mut buf = Vec::new();
+		network.save(&mut buf).unwrap();
+		assert!(buf.starts_with(SAVE_MAGIC));
+
+		let is_invalid_save = |buf: &[u8]| matches!(NeuralNetwork::load(buf), Err(NeuralibError::InvalidSave(_)));
+
+		assert!(is_invalid_save(b"definitely not a network"));
+		assert!(is_invalid_save(&[]));
+		// A truncated save
+		assert!(is_invalid_save(&buf[..buf.len() / 2]));
+		// A headerless save can't have a newer version
+		let mut buf = Vec::new();
+		let mut serializer = Serializer::new(&mut buf);
+		FORMAT_VERSION.serialize(&mut serializer).unwrap();
+		network.serialize(&mut serializer).unwrap();
+		assert!(is_invalid_save(&buf));
+	}
+
+	#[test]
+	fn input_dropout_consistency() {
+		let network = NeuralNetwork::new(&[4, 3], 5, vec![Activation::ReLU, Activation::Sigmoid]).unwrap();
+		let inputs = [0.3, -1.2, 0.8, 2.0, -0.5];
+
+		assert_eq!(network.input_dropout_consistency(&inputs, 0.0, 20, &mut rand::rng()).unwrap(), 1.0);
+
+		let consistency = network.input_dropout_consistency(&inputs, 0.5, 20, &mut rand::rng()).unwrap();
+		assert!((0.0..=1.0).contains(&consistency));
+
+		assert!(network.input_dropout_consistency(&inputs, 1.5, 20, &mut rand::rng()).is_err());
+		assert!(network.input_dropout_consistency(&inputs, 0.5, 0, &mut rand::rng()).is_err());
+	}
+
+	#[test]
+	fn class_prototypes() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Linear, Activation::Sigmoid]).unwrap();
+
+		let data = vec![
+			DataValue { input: vec![1.0, 2.0], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![-1.0, 0.5], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![0.0, -3.0], expected_output: vec![0.0, 1.0] },
+		];
+
+		let mut expected_hidden = vec![vec![0.0; 3]; 2];
+		let mut expected_output = vec![vec![0.0; 2]; 2];
+		for (value, (class, count)) in data.iter().zip([(0, 2.0), (0, 2.0), (1, 1.0)]) {
+			let activations = network.activate_with_intermediates(&value.input).unwrap();
+			for (sum, activation) in expected_hidden[class].iter_mut().zip(&activations[0]) {
+				*sum += activation / count;
+			}
+			for (sum, activation) in expected_output[class].iter_mut().zip(&activations[1]) {
+				*sum += activation / count;
+			}
+		}
+
+		let hidden = network.class_prototypes(&data, Some(0)).unwrap();
+		let output = network.class_prototypes(&data, None).unwrap();
+
+		for (actual, expected) in hidden.iter().flatten().zip(expected_hidden.iter().flatten()) {
+			assert!((actual - expected).abs() < 1e-10);
+		}
+		for (actual, expected) in output.iter().flatten().zip(expected_output.iter().flatten()) {
+			assert!((actual - expected).abs() < 1e-10);
+		}
+
+		assert!(network.class_prototypes(&data, Some(2)).is_err());
+	}
+
+	#[test]
+	fn gradient_cosine() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Sigmoid]).unwrap();
+
+		let data = vec![
+			DataValue { input: vec![1.0, 2.0], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![0.0, -3.0], expected_output: vec![0.0, 1.0] },
+		];
+		let flipped = vec![
+			DataValue { input: vec![1.0, 2.0], expected_output: vec![0.0, 1.0] },
+		];
+
+		assert!((network.gradient_cosine(&data, &data).unwrap() - 1.0).abs() < 1e-10);
+
+		let cosine = network.gradient_cosine(&data, &flipped).unwrap();
+		assert!((-1.0..=1.0).contains(&cosine));
+
+		assert!(network.gradient_cosine(&data, &[]).is_err());
+	}
+
+	#[test]
+	fn softmax() {
+		let mut network = NeuralNetwork::new(&[100, 10], 784, vec![Activation::Sigmoid, Activation::Softmax]).unwrap();
+
+		let outputs = network.activate(&[0.5; 784]).unwrap();
+		assert!((outputs.iter().sum::<f64>() - 1.0).abs() < 1e-10);
+	}
+
+	#[test]
+	fn softmax_gradients() {
+		let mut network = NeuralNetwork::new(&[3, 3], 2, vec![Activation::Softmax, Activation::Softmax]).unwrap();
+		let value = DataValue { input: vec![0.4, -0.7], expected_output: vec![0.0, 1.0, 0.0] };
+
+		network.update_all_gradients(&value).unwrap();
+
+		// Compare every bias gradient with a numerical estimate
+		let epsilon = 1e-6;
+		for layeridx in 0..2 {
+			for neuronidx in 0..3 {
+				let analytic = network.get_layer(layeridx).unwrap().get_neuron(neuronidx).unwrap().get_loss_gradient().loss_gradient_bias;
+
+				*network.get_layer_mut(layeridx).unwrap().get_neuron_mut(neuronidx).unwrap().get_bias_mut() += epsilon;
+				let loss_up = network.loss_with_value(&value).unwrap();
+				*network.get_layer_mut(layeridx).unwrap().get_neuron_mut(neuronidx).unwrap().get_bias_mut() -= 2.0 * epsilon;
+				let loss_down = network.loss_with_value(&value).unwrap();
+				*network.get_layer_mut(layeridx).unwrap().get_neuron_mut(neuronidx).unwrap().get_bias_mut() += epsilon;
+
+				let numerical = (loss_up - loss_down) / (2.0 * epsilon);
+				assert!((analytic - numerical).abs() < 1e-6);
+			}
+		}
+	}
+
+	#[test]
+	fn learn_curriculum() {
+		let data: Vec<DataValue> = [3.0, -1.0, 2.0, 0.5, -1.0]
+			.iter()
+			.map(|x| DataValue { input: vec![*x], expected_output: vec![x * 2.0] })
+			.collect();
+
+		let difficulty = |value: &DataValue| value.input[0];
+		assert_eq!(curriculum_order(&data, difficulty), vec![1, 4, 3, 2, 0]);
+
+		let mut network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
+		let before = network.loss(&data).unwrap();
+		network.learn_curriculum(&data, 0.01, difficulty).unwrap();
+		assert!(network.loss(&data).unwrap() < before);
+	}
+
+	#[test]
+	fn predict_with_missing() {
+		let network = NeuralNetwork::new(&[4, 2], 3, vec![Activation::ReLU, Activation::Sigmoid]).unwrap();
+
+		let complete = [Some(0.5), Some(-1.0), Some(2.0)];
+		assert_eq!(network.predict_with_missing(&complete, 10, &mut rand::rng()).unwrap(), network.predict(&[0.5, -1.0, 2.0]).unwrap());
+
+		let missing = [Some(0.5), None, Some(2.0)];
+		let outputs = network.predict_with_missing(&missing, 10, &mut rand::rng()).unwrap();
+		assert_eq!(outputs.len(), 2);
+		assert!(outputs.iter().all(|output| (0.0..=1.0).contains(output)));
+
+		// A distribution that always gives the same value is the same as filling it in
+		let constant = rand_distr::Uniform::new_inclusive(1.5, 1.5).unwrap();
+		let outputs = network.predict_with_missing_from(&missing, 5, &constant, &mut rand::rng()).unwrap();
+		for (actual, expected) in outputs.iter().zip(network.predict(&[0.5, 1.5, 2.0]).unwrap()) {
+			assert!((actual - expected).abs() < 1e-10);
+		}
+
+		assert!(network.predict_with_missing(&missing, 0, &mut rand::rng()).is_err());
+		assert!(network.predict_with_missing(&[None], 10, &mut rand::rng()).is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn save_training_metadata() {
+		let mut network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
+		assert_eq!(network.training_metadata(), None);
+
+		let config = TrainConfig { learn_rate: 0.5, epochs: 650, seed: Some(42), batch_size: Some(6000), lr_schedule: LrSchedule::Exponential { gamma: 0.99 } };
+		network.set_training_metadata(Some(config.clone()));
+
+		let mut buf = Vec::new();
+		network.save(&mut buf).unwrap();
+		let loaded = NeuralNetwork::load(&buf[..]).unwrap();
+
+		assert_eq!(loaded.training_metadata(), Some(&config));
+	}
+
+	#[test]
+	fn coverage_curve() {
+		let mut network = identity_network(2);
+
+		let data = vec![
+			DataValue { input: vec![0.9, 0.1], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![0.6, 0.4], expected_output: vec![0.0, 1.0] },
+			DataValue { input: vec![0.3, 0.7], expected_output: vec![0.0, 1.0] },
+			DataValue { input: vec![0.55, 0.45], expected_output: vec![1.0, 0.0] },
+		];
+
+		let curve = network.coverage_curve(&data, &[0.5, 0.65, 0.8, 0.95]).unwrap();
+
+		assert_eq!(curve[0], (0.5, 1.0, 0.75));
+		assert_eq!(curve[1], (0.65, 0.5, 1.0));
+		assert_eq!(curve[2], (0.8, 0.25, 1.0));
+		assert_eq!(curve[3].1, 0.0);
+		assert!(curve[3].2.is_nan());
+
+		assert!(network.coverage_curve(&[], &[0.5]).is_err());
+	}
+
+	#[test]
+	fn soft_update_from() {
+		let source = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let mut target = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let inputs = [0.5, -1.0];
+		let original = target.predict(&inputs).unwrap();
+
+		target.soft_update_from(&source, 0.0).unwrap();
+		assert_eq!(target.predict(&inputs).unwrap(), original);
+
+		target.soft_update_from(&source, 1.0).unwrap();
+		assert_eq!(target.predict(&inputs).unwrap(), source.predict(&inputs).unwrap());
+
+		// The learned PReLU slopes are blended too
+		let activations = vec![Activation::PReLU { initial_slope: 0.25 }, Activation::Linear];
+		let mut source = NeuralNetwork::new_seeded(&[4, 1], 2, activations.clone(), 1).unwrap();
+		let mut target = NeuralNetwork::new_seeded(&[4, 1], 2, activations, 2).unwrap();
+		let data = vec![DataValue { input: vec![-1.0, -2.0], expected_output: vec![3.0] }, DataValue { input: vec![1.5, -0.5], expected_output: vec![-1.0] }];
+		for _ in 0..20 {
+			source.learn(&data, 0.05).unwrap();
+		}
+		let inputs = [-1.0, -2.0];
+		let original = target.predict(&inputs).unwrap();
+
+		target.soft_update_from(&source, 0.0).unwrap();
+		assert_eq!(target.predict(&inputs).unwrap(), original);
+
+		target.soft_update_from(&source, 1.0).unwrap();
+		assert_eq!(target.predict(&inputs).unwrap(), source.predict(&inputs).unwrap());
+
+		let other = NeuralNetwork::new(&[4, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		assert!(target.soft_update_from(&other, 0.5).is_err());
+		assert!(target.soft_update_from(&source, 1.5).is_err());
+	}
+
+	#[test]
+	fn minimal_perturbation() {
+		let mut network = identity_network(2);
+		let inputs = [0.51, 0.49];
+
+		let perturbation = network.minimal_perturbation(&inputs, 10).unwrap();
+		let perturbed: Vec<f64> = inputs.iter().zip(&perturbation).map(|(x, r)| x + r).collect();
+
+		assert_eq!(argmax(&network.predict(&inputs).unwrap()), 0);
+		assert_eq!(argmax(&network.predict(&perturbed).unwrap()), 1);
+		assert!(perturbation.iter().map(|x| x * x).sum::<f64>().sqrt() < 0.05);
+
+		// It should work through a hidden layer and softmax too
+		let mut network = NeuralNetwork::new(&[5, 3], 3, vec![Activation::Linear, Activation::Softmax]).unwrap();
+		let inputs = [0.2, -0.4, 0.9];
+		let perturbation = network.minimal_perturbation(&inputs, 50).unwrap();
+		let perturbed: Vec<f64> = inputs.iter().zip(&perturbation).map(|(x, r)| x + r).collect();
+		assert_ne!(argmax(&network.predict(&inputs).unwrap()), argmax(&network.predict(&perturbed).unwrap()));
+
+		let mut network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
+		assert!(network.minimal_perturbation(&[1.0], 10).is_err());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn p_re_lu_saved() {
+		// Seeded so that some neurons get negative inputs, otherwise the slopes have no gradient
+		let mut network = NeuralNetwork::new_seeded(&[4, 1], 2, vec![Activation::PReLU { initial_slope: 0.25 }, Activation::Linear], 1).unwrap();
+		let data = vec![
+			DataValue { input: vec![-1.0, -2.0], expected_output: vec![3.0] },
+			DataValue { input: vec![1.5, -0.5], expected_output: vec![-1.0] },
+		];
+
+		for _ in 0..10 {
+			network.learn(&data, 0.01).unwrap();
+		}
+
+		let slopes: Vec<f64> = (0..4).map(|idx| network.get_layer(0).unwrap().get_neuron(idx).unwrap().get_activation_param()).collect();
+		assert!(slopes.iter().any(|slope| *slope != 0.25));
+
+		let mut buf = Vec::new();
+		network.save(&mut buf).unwrap();
+		let loaded = NeuralNetwork::load(&buf[..]).unwrap();
+
+		for (idx, slope) in slopes.iter().enumerate() {
+			assert_eq!(loaded.get_layer(0).unwrap().get_neuron(idx).unwrap().get_activation_param(), *slope);
+		}
+	}
+
+	#[test]
+	fn replace_output_layer() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Sigmoid]).unwrap();
+		network.replace_output_layer(4, Activation::Linear).unwrap();
+
+		assert_eq!(network.get_layer_count(), 2);
+		assert_eq!(network.predict(&[0.5, -1.0]).unwrap().len(), 4);
+
+		let weights = |network: &NeuralNetwork, layeridx: usize| -> Vec<f64> {
+			let layer = network.get_layer(layeridx).unwrap();
+			(0..layer.get_neuron_count())
+				.flat_map(|neuronidx| {
+					let neuron = layer.get_neuron(neuronidx).unwrap();
+					(0..neuron.get_weight_count()).map(|weightidx| *neuron.get_weight(weightidx).unwrap()).chain([*neuron.get_bias()])
+				})
+				.collect()
+		};
+		let hidden = weights(&network, 0);
+		let output = weights(&network, 1);
+
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 0.0, -1.0, 2.0] }];
+		network.learn(&data, 0.1).unwrap();
+
+		assert_eq!(weights(&network, 0), hidden);
+		assert_ne!(weights(&network, 1), output);
+
+		assert!(network.replace_output_layer(0, Activation::Linear).is_err());
+	}
+
+	struct Cube;
+
+	impl crate::activation::ActivationFn for Cube {
+		fn call(&self, x: f64) -> f64 {
+			x * x * x
+		}
+
+		fn derivative(&self, x: f64) -> f64 {
+			3.0 * x * x
+		}
+	}
+
+	#[test]
+	fn custom_activation() {
+		let mut network = NeuralNetwork::new_seeded(&[3, 1], 2, vec![Activation::custom(Cube), Activation::Linear], 1).unwrap();
+		let data = vec![
+			DataValue { input: vec![0.5, -0.2], expected_output: vec![0.3] },
+			DataValue { input: vec![-0.4, 0.1], expected_output: vec![-0.2] },
+		];
+
+		assert_eq!(network.activate(&[0.5, -0.2]).unwrap(), network.predict(&[0.5, -0.2]).unwrap());
+
+		let before = network.loss(&data).unwrap();
+		network.learn(&data, 0.01).unwrap();
+		assert!(network.loss(&data).unwrap() < before);
+
+		#[cfg(feature = "serde")]
+		assert!(network.save(&mut Vec::new()).is_err());
+	}
+
+	#[test]
+	fn step_training() {
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 0.0] }];
+
+		let mut network = NeuralNetwork::new(&[2, 2], 2, vec![Activation::Step, Activation::Sigmoid]).unwrap();
+		let Err(NeuralibError::UntrainableActivation(err)) = network.learn(&data, 0.1) else {
+			panic!("Expected an UntrainableActivationError");
+		};
+		assert_eq!(err.layer, 0);
+
+		let mut network = NeuralNetwork::new(&[2, 2], 2, vec![Activation::StepSTE, Activation::Sigmoid]).unwrap();
+		network.learn(&data, 0.1).unwrap();
+	}
+
+	#[test]
+	fn expected_calibration_error() {
+		let mut network = identity_network(2);
+
+		let sample = |confidence: f64, correct: bool| DataValue {
+			input: vec![confidence, 1.0 - confidence],
+			expected_output: if correct {vec![1.0, 0.0]} else {vec![0.0, 1.0]},
+		};
+
+		// 80% confident and right 80% of the time, 60% confident and right 60% of the time
+		let mut calibrated: Vec<DataValue> = (0..10).map(|idx| sample(0.8, idx < 8)).collect();
+		calibrated.extend((0..5).map(|idx| sample(0.6, idx < 3)));
+		assert!(network.expected_calibration_error(&calibrated, 10).unwrap().abs() < 1e-10);
+
+		// 90% confident but only right half the time
+		let overconfident: Vec<DataValue> = (0..10).map(|idx| sample(0.9, idx < 5)).collect();
+		assert!((network.expected_calibration_error(&overconfident, 10).unwrap() - 0.4).abs() < 1e-10);
+
+		assert!(network.expected_calibration_error(&calibrated, 0).is_err());
+		assert!(network.expected_calibration_error(&[], 10).is_err());
+	}
+
+	#[test]
+	fn cross_entropy() {
+		let mut network = NeuralNetwork::new(&[4, 3], 2, vec![Activation::Sigmoid, Activation::Softmax]).unwrap();
+		network.set_loss(Loss::CrossEntropy);
+		assert_eq!(network.get_loss(), &Loss::CrossEntropy);
+
+		let value = DataValue { input: vec![0.4, -0.7], expected_output: vec![0.0, 1.0, 0.0] };
+
+		let output = network.activate(&value.input).unwrap();
+		assert!((network.loss_with_value(&value).unwrap() + output[1].ln()).abs() < 1e-10);
+
+		// The simplified softmax gradient should match a numerical estimate
+		network.update_all_gradients(&value).unwrap();
+		let epsilon = 1e-6;
+		for layeridx in 0..2 {
+			let neurons = network.get_layer(layeridx).unwrap().get_neuron_count();
+			for neuronidx in 0..neurons {
+				let analytic = network.get_layer(layeridx).unwrap().get_neuron(neuronidx).unwrap().get_loss_gradient().loss_gradient_bias;
+
+				*network.get_layer_mut(layeridx).unwrap().get_neuron_mut(neuronidx).unwrap().get_bias_mut() += epsilon;
+				let loss_up = network.loss_with_value(&value).unwrap();
+				*network.get_layer_mut(layeridx).unwrap().get_neuron_mut(neuronidx).unwrap().get_bias_mut() -= 2.0 * epsilon;
+				let loss_down = network.loss_with_value(&value).unwrap();
+				*network.get_layer_mut(layeridx).unwrap().get_neuron_mut(neuronidx).unwrap().get_bias_mut() += epsilon;
+
+				let numerical = (loss_up - loss_down) / (2.0 * epsilon);
+				assert!((analytic - numerical).abs() < 1e-6);
+			}
+		}
+	}
+
+	#[test]
+	fn maximize_output() {
+		let mut network = NeuralNetwork::new(&[6, 3], 4, vec![Activation::HyperTan, Activation::Softmax]).unwrap();
+
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(7);
+		let start = network.maximize_output(2, 0, 0.1, 0.01, &mut rng).unwrap();
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(7);
+		let maximized = network.maximize_output(2, 200, 0.1, 0.01, &mut rng).unwrap();
+
+		assert!(network.predict(&maximized).unwrap()[2] > network.predict(&start).unwrap()[2]);
+
+		assert!(network.maximize_output(3, 1, 0.1, 0.0, &mut rng).is_err());
+	}
+
+	#[test]
+	fn mae_loss() {
+		let mut network = identity_network(1);
+		network.set_loss(Loss::MAE);
+
+		let data = vec![
+			DataValue { input: vec![1.0], expected_output: vec![2.0] },
+			DataValue { input: vec![3.0], expected_output: vec![0.0] },
+		];
+		assert_eq!(network.loss(&data).unwrap(), 2.0);
+
+		// The gradient only depends on the sign of the error
+		network.update_all_gradients(&data[0]).unwrap();
+		assert_eq!(network.get_layer(0).unwrap().get_neuron(0).unwrap().get_loss_gradient().loss_gradient_bias, -1.0);
+	}
+
+	#[test]
+	fn set_activations() {
+		let mut network = NeuralNetwork::new(&[4, 4, 2], 3, vec![Activation::Sigmoid, Activation::Sigmoid, Activation::Softmax]).unwrap();
+		let activation = |network: &NeuralNetwork, layeridx: usize| network.get_layer(layeridx).unwrap().get_neuron(0).unwrap().get_activation().clone();
+
+		network.set_hidden_activation(Activation::ReLU);
+		assert!(matches!(activation(&network, 0), Activation::ReLU));
+		assert!(matches!(activation(&network, 1), Activation::ReLU));
+		assert!(matches!(activation(&network, 2), Activation::Softmax));
+
+		network.set_output_activation(Activation::Linear);
+		assert!(matches!(activation(&network, 1), Activation::ReLU));
+		assert!(matches!(activation(&network, 2), Activation::Linear));
+
+		network.set_layer_activation(1, Activation::HyperTan).unwrap();
+		assert!(matches!(activation(&network, 0), Activation::ReLU));
+		assert!(matches!(activation(&network, 1), Activation::HyperTan));
+		assert!(network.set_layer_activation(3, Activation::Linear).is_err());
+	}
+
+	#[test]
+	fn huber_loss() {
+		// y = 2x, with one large outlier
+		let mut data: Vec<DataValue> = (-4..=4).map(|x| DataValue { input: vec![x as f64 / 2.0], expected_output: vec![x as f64] }).collect();
+		data.push(DataValue { input: vec![0.0], expected_output: vec![50.0] });
+
+		let fit_error = |loss: Loss| {
+			let mut network = identity_network(1);
+			network.set_loss(loss);
+			for _ in 0..2000 {
+				network.learn(&data, 0.05).unwrap();
+			}
+			(network.predict(&[1.0]).unwrap()[0] - 2.0).abs()
+		};
+
+		assert!(fit_error(Loss::huber_default()) < fit_error(Loss::MSE) / 4.0);
+	}
+
+	#[test]
+	fn effective_rank() {
+		let mut network = identity_network(3);
+
+		// Activations spanning a 2D subspace, with equal spread in both directions
+		let data: Vec<DataValue> = [[1.0, 1.0, 0.0], [-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, 1.0, 0.0]].iter()
+			.map(|input| DataValue { input: input.to_vec(), expected_output: vec![0.0; 3] })
+			.collect();
+		assert!((network.effective_rank(&data, 0).unwrap() - 2.0).abs() < 1e-6);
+
+		// Collapsed onto a single direction
+		let collapsed: Vec<DataValue> = (1..5)
+			.map(|x| DataValue { input: vec![x as f64, -2.0 * x as f64, 0.5 * x as f64], expected_output: vec![0.0; 3] })
+			.collect();
+		assert!((network.effective_rank(&collapsed, 0).unwrap() - 1.0).abs() < 1e-6);
+
+		assert!(network.effective_rank(&data, 1).is_err());
+		assert!(network.effective_rank(&[], 0).is_err());
+	}
+
+	#[test]
+	fn momentum() {
+		let data: Vec<DataValue> = (-4..=4).map(|x| DataValue { input: vec![x as f64 / 4.0], expected_output: vec![3.0 * x as f64 / 4.0 + 1.0] }).collect();
+
+		let loss_after = |optimizer: Optimizer| {
+			let mut network = identity_network(1);
+			network.set_optimizer(optimizer).unwrap();
+			for _ in 0..100 {
+				network.learn(&data, 0.02).unwrap();
+			}
+			network.loss(&data).unwrap()
+		};
+
+		assert!(loss_after(Optimizer::momentum_default()) < loss_after(Optimizer::SGD) / 10.0);
+	}
+
+	#[test]
+	fn gradient_norm_history() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let data = [
+			DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![-0.2, 0.3], expected_output: vec![0.0, 2.0] },
+		];
+		assert!(network.gradient_norm_history().is_empty());
+
+		for _ in 0..5 {
+			network.learn(&data, 0.1).unwrap();
+		}
+		assert_eq!(network.gradient_norm_history().len(), 5);
+		assert!(network.gradient_norm_history().iter().all(|norm| *norm >= 0.0));
+
+		// A perfect network has no gradient
+		let mut network = identity_network(1);
+		network.learn(&[DataValue { input: vec![2.0], expected_output: vec![2.0] }], 0.1).unwrap();
+		assert_eq!(network.gradient_norm_history(), &[0.0]);
+	}
+
+	#[test]
+	fn adam() {
+		// The input is badly scaled, so plain SGD learns the weight very slowly
+		let data: Vec<DataValue> = (-4..=4).map(|x| DataValue { input: vec![x as f64 / 40.0], expected_output: vec![3.0 * x as f64 / 4.0 + 1.0] }).collect();
+
+		let loss_after = |optimizer: Optimizer| {
+			let mut network = identity_network(1);
+			network.set_optimizer(optimizer).unwrap();
+			for _ in 0..300 {
+				network.learn(&data, 0.3).unwrap();
+			}
+			network.loss(&data).unwrap()
+		};
+
+		assert!(loss_after(Optimizer::adam_default()) < loss_after(Optimizer::SGD) / 100.0);
+	}
+
+	#[test]
+	fn freeze_neuron() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		network.freeze_neuron(0, 1).unwrap();
+
+		let parameters = |network: &NeuralNetwork, neuronidx: usize| -> Vec<f64> {
+			let neuron = network.get_layer(0).unwrap().get_neuron(neuronidx).unwrap();
+			(0..neuron.get_weight_count()).map(|weightidx| *neuron.get_weight(weightidx).unwrap()).chain([*neuron.get_bias()]).collect()
+		};
+		let frozen = parameters(&network, 1);
+		let unfrozen = parameters(&network, 0);
+
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, -2.0] }];
+		network.learn(&data, 0.1).unwrap();
+
+		assert_eq!(parameters(&network, 1), frozen);
+		assert_ne!(parameters(&network, 0), unfrozen);
+
+		network.unfreeze_neuron(0, 1).unwrap();
+		network.learn(&data, 0.1).unwrap();
+		assert_ne!(parameters(&network, 1), frozen);
+
+		assert!(network.freeze_neuron(2, 0).is_err());
+		assert!(network.freeze_neuron(0, 3).is_err());
+	}
+
+	#[test]
+	fn rms_prop() {
+		let data = vec![DataValue { input: vec![1.0], expected_output: vec![3.0] }];
+		let bias = |network: &NeuralNetwork| *network.get_layer(0).unwrap().get_neuron(0).unwrap().get_bias();
+
+		let mut network = identity_network(1);
+		network.set_optimizer(Optimizer::rms_prop_default()).unwrap();
+
+		// The first step is about lr / sqrt(1 - rho), and the state is kept between calls to learn
+		network.learn(&data, 0.01).unwrap();
+		let first_step = bias(&network);
+		network.learn(&data, 0.01).unwrap();
+		let second_step = bias(&network) - first_step;
+		assert!(second_step < first_step);
+
+		// Resetting starts over with a full sized step
+		network.reset_optimizer_state();
+		let before = bias(&network);
+		network.learn(&data, 0.01).unwrap();
+		assert!((bias(&network) - before - first_step).abs() < 1e-3);
+	}
+
+	#[test]
+	fn loss_along_direction() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let data = [
+			DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![-0.2, 0.3], expected_output: vec![0.0, 2.0] },
+		];
+		// (2 weights + 1 bias) * 3 + (3 weights + 1 bias) * 2
+		let direction: Vec<f64> = (0..17).map(|x| (x as f64).sin()).collect();
+
+		let loss = network.loss(&data).unwrap();
+		let before = network.predict(&[1.0, 1.0]).unwrap();
+
+		let losses = network.loss_along_direction(&data, &direction, &[-1.0, 0.0, 0.5]).unwrap();
+		assert_eq!(losses.len(), 3);
+		assert_eq!(losses[1], loss);
+		assert_ne!(losses[0], loss);
+
+		// The parameters are restored
+		assert_eq!(network.predict(&[1.0, 1.0]).unwrap(), before);
+
+		assert!(network.loss_along_direction(&data, &direction[1..], &[0.0]).is_err());
+	}
+
+	#[test]
+	fn l2_regularization() {
+		let data: Vec<DataValue> = (-4..=4).map(|x| DataValue { input: vec![x as f64], expected_output: vec![3.0 * x as f64] }).collect();
+
+		let weight_after = |regularization: Regularization| {
+			let mut network = identity_network(1);
+			network.set_regularization(regularization);
+			for _ in 0..200 {
+				network.learn(&data, 0.01).unwrap();
+			}
+			*network.get_layer(0).unwrap().get_neuron(0).unwrap().get_weight(0).unwrap()
+		};
+
+		let unregularized = weight_after(Regularization::default());
+		let regularized = weight_after(Regularization { l2: 1.0, ..Default::default() });
+		assert!((unregularized - 3.0).abs() < 1e-6);
+		// The minimum of (w - 3)^2 * 20 / 3 + w^2 / 2
+		assert!((regularized - 3.0 * 40.0 / 43.0).abs() < 1e-6);
+	}
+
+	#[test]
+	fn sharpness() {
+		// The identity network is at the minimum for data that maps inputs to themselves
+		let mut network = identity_network(2);
+		let data: Vec<DataValue> = [[1.0, 0.5], [-1.0, 2.0], [0.3, -0.7]].iter()
+			.map(|input| DataValue { input: input.to_vec(), expected_output: input.to_vec() })
+			.collect();
+
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(3);
+		let small = network.sharpness(&data, 0.01, 10, &mut rng).unwrap();
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(3);
+		let large = network.sharpness(&data, 0.1, 10, &mut rng).unwrap();
+
+		assert!(small >= 0.0);
+		assert!(large > small);
+		// The parameters are restored
+		assert_eq!(network.loss(&data).unwrap(), 0.0);
+
+		assert!(network.sharpness(&data, 0.1, 0, &mut rng).is_err());
+	}
+
+	#[test]
+	fn l1_regularization() {
+		// The second input barely matters
+		let data: Vec<DataValue> = (-4..=4)
+			.map(|x| DataValue { input: vec![x as f64, (x * x) as f64 - 20.0 / 3.0], expected_output: vec![2.0 * x as f64 + 0.01 * ((x * x) as f64 - 20.0 / 3.0)] })
+			.collect();
+
+		let weights_after = |regularization: Regularization| {
+			let mut network = NeuralNetwork::new(&[1], 2, vec![Activation::Linear]).unwrap();
+			let neuron = network.get_layer_mut(0).unwrap().get_neuron_mut(0).unwrap();
+			*neuron.get_weight_mut(0).unwrap() = 1.0;
+			*neuron.get_weight_mut(1).unwrap() = 1.0;
+
+			network.set_regularization(regularization);
+			for _ in 0..1000 {
+				network.learn(&data, 0.005).unwrap();
+			}
+			let neuron = network.get_layer(0).unwrap().get_neuron(0).unwrap();
+			(*neuron.get_weight(0).unwrap(), *neuron.get_weight(1).unwrap())
+		};
+
+		let (_, unregularized) = weights_after(Regularization::default());
+		let (important, unimportant) = weights_after(Regularization { l1: 1.0, ..Default::default() });
+
+		assert!((unregularized - 0.01).abs() < 1e-3);
+		// The unimportant weight ends up oscillating around 0, but the important one stays
+		assert!(unimportant.abs() < 1e-3);
+		assert!(important > 1.9);
+	}
+
+	#[test]
+	fn dropout() {
+		let mut network = NeuralNetwork::new(&[20, 1], 3, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let inputs = [0.5, -1.0, 0.25];
+		let before = network.activate(&inputs).unwrap();
+		network.set_dropout(0, 0.5).unwrap();
+
+		// Running the network isn't affected
+		assert_eq!(network.activate(&inputs).unwrap(), before);
+		assert_eq!(network.predict(&inputs).unwrap(), before);
+
+		// Dropped neurons don't get gradients, and neither do the weights reading from them
+		let value = DataValue { input: inputs.to_vec(), expected_output: vec![3.0] };
+		network.accumulate_gradients(&value, true, &mut rand::rng()).unwrap();
+
+		let output_gradients = network.get_layer(1).unwrap().get_neuron(0).unwrap().get_loss_gradient().loss_gradient_weight.clone();
+		let mut dropped = 0;
+		for (neuronidx, output_gradient) in output_gradients.iter().enumerate() {
+			let hidden_gradient = network.get_layer(0).unwrap().get_neuron(neuronidx).unwrap().get_loss_gradient().loss_gradient_bias;
+			assert_eq!(hidden_gradient == 0.0, *output_gradient == 0.0);
+			if hidden_gradient == 0.0 {
+				dropped += 1;
+			}
+		}
+		assert!(dropped > 0 && dropped < 20);
+
+		assert!(network.set_dropout(1, 0.5).is_err());
+		assert!(network.set_dropout(0, 1.0).is_err());
+	}
+
+	#[test]
+	fn predict_class() {
+		let mut network = identity_network(3);
+
+		assert_eq!(network.predict_class(&[0.1, 0.7, 0.2]).unwrap(), 1);
+		assert_eq!(network.predict_class(&[0.9, -1.0, 0.2]).unwrap(), 0);
+		// Ties resolve to the lowest index
+		assert_eq!(network.predict_class(&[0.0, 0.5, 0.5]).unwrap(), 1);
+
+		assert!(network.predict_class(&[0.0]).is_err());
+	}
+
+	#[test]
+	fn accuracy() {
+		let mut network = identity_network(2);
+		let data = vec![
+			DataValue { input: vec![0.9, 0.1], expected_output: vec![1.0, 0.0] },
+			DataValue { input: vec![0.2, 0.8], expected_output: vec![0.0, 1.0] },
+			DataValue { input: vec![0.6, 0.4], expected_output: vec![0.0, 1.0] },
+			DataValue { input: vec![0.3, 0.7], expected_output: vec![0.0, 1.0] },
+		];
+
+		assert_eq!(network.accuracy(&data).unwrap(), 0.75);
+
+		assert!(network.accuracy(&[]).is_err());
+		assert!(network.accuracy(&[DataValue { input: vec![0.0, 1.0], expected_output: vec![1.0] }]).is_err());
+	}
+
+	#[test]
+	fn predict_shared() {
+		let mut network = NeuralNetwork::new(&[4, 2], 3, vec![Activation::Sigmoid, Activation::Softmax]).unwrap();
+		let inputs = [[0.5, -1.0, 0.25], [1.0, 2.0, -3.0], [0.0, 0.0, 0.0]];
+		let expected: Vec<Vec<f64>> = inputs.iter().map(|input| network.activate(input).unwrap()).collect();
+
+		let network = &network;
+		std::thread::scope(|scope| {
+			let handles: Vec<_> = inputs.iter().map(|input| scope.spawn(move || network.predict(input).unwrap())).collect();
+			for (handle, expected) in handles.into_iter().zip(&expected) {
+				assert_eq!(&handle.join().unwrap(), expected);
+			}
+		});
+
+		assert!(network.predict(&[0.0]).is_err());
+	}
+
+	#[test]
+	fn activate_batch() {
+		let mut network = NeuralNetwork::new(&[4, 2], 3, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let inputs = vec![vec![0.5, -1.0, 0.25], vec![1.0, 2.0, -3.0], vec![0.0, 0.0, 0.0]];
+
+		let outputs = network.activate_batch(&inputs).unwrap();
+		assert_eq!(outputs.len(), 3);
+		for (input, output) in inputs.iter().zip(&outputs) {
+			assert_eq!(output, &network.activate(input).unwrap());
+		}
+
+		assert!(network.activate_batch(&[vec![0.0; 3], vec![0.0; 2]]).is_err());
+		assert!(network.activate_batch(&[]).unwrap().is_empty());
+	}
+
+	#[test]
+	fn layer_parameters() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let neuron = network.get_layer_mut(1).unwrap().get_neuron_mut(0).unwrap();
+		*neuron.get_weight_mut(2).unwrap() = 0.75;
+		*neuron.get_bias_mut() = -0.5;
+
+		let weights = network.layer_weights(1).unwrap();
+		assert_eq!(weights.len(), 2);
+		assert!(weights.iter().all(|neuron| neuron.len() == 3));
+		assert_eq!(weights[0][2], 0.75);
+		assert_eq!(network.layer_weights(0).unwrap().len(), 3);
+
+		let biases = network.layer_biases(1).unwrap();
+		assert_eq!(biases, vec![-0.5, 0.0]);
+
+		assert!(network.layer_weights(2).is_none());
+		assert!(network.layer_biases(2).is_none());
+	}
+
+	#[test]
+	fn num_parameters() {
+		let network = NeuralNetwork::new(&[4, 3, 2], 5, vec![Activation::ReLU, Activation::ReLU, Activation::Softmax]).unwrap();
+		// (5*4 + 4) + (4*3 + 3) + (3*2 + 2)
+		assert_eq!(network.num_parameters(), 47);
+
+		let summary = network.summary();
+		assert_eq!(summary.lines().count(), 5);
+		assert!(summary.contains("Softmax"));
+		assert!(summary.ends_with("Total parameters: 47"));
+	}
+
+	#[cfg(feature = "rayon")]
+	#[test]
+	fn parallel_gradients_match_serial() {
+		let mut serial = NeuralNetwork::new(&[6, 3], 4, vec![Activation::HyperTan, Activation::Softmax]).unwrap();
+		serial.set_loss(Loss::CrossEntropy);
+		let mut parallel = NeuralNetwork::new(&[6, 3], 4, vec![Activation::HyperTan, Activation::Softmax]).unwrap();
+		parallel.set_loss(Loss::CrossEntropy);
+		parallel.set_flat_parameters(&serial.flat_parameters());
+
+		let data: Vec<DataValue> = (0..50)
+			.map(|i| DataValue::from_label(vec![i as f64 / 50.0, (i % 7) as f64, -0.5, (i % 3) as f64], i % 3, 3).unwrap())
+			.collect();
+
+		let one_thread = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+		let four_threads = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+		for _ in 0..5 {
+			assert!(one_thread.install(|| serial.learn(&data, 0.1).is_ok()));
+			assert!(four_threads.install(|| parallel.learn(&data, 0.1).is_ok()));
+		}
+
+		for (a, b) in serial.flat_parameters().iter().zip(parallel.flat_parameters()) {
+			assert!((a - b).abs() < 1e-10);
+		}
+
+		let mut bad_data = data.clone();
+		bad_data.push(DataValue { input: vec![0.0; 3], expected_output: vec![0.0; 3] });
+		assert!(four_threads.install(|| parallel.learn(&bad_data, 0.1).is_err()));
+	}
+
+	#[test]
+	fn gradient_clipping() {
+		let mut network = identity_network(3);
+		assert!(network.set_max_gradient_norm(Some(0.0)).is_err());
+		assert!(network.set_max_gradient_norm(Some(-1.0)).is_err());
+		network.set_max_gradient_norm(Some(5.0)).unwrap();
+		assert_eq!(network.get_max_gradient_norm(), Some(5.0));
+
+		// An output that is far off gives a huge gradient
+		let value = DataValue { input: vec![100.0, -50.0, 80.0], expected_output: vec![-1000.0, 1000.0, 0.0] };
+		network.accumulate_gradients(&value, false, &mut rand::rng()).unwrap();
+		let gradient_norm = network.layers.iter().map(Layer::gradient_norm_squared).sum::<f64>().sqrt();
+		assert!(gradient_norm > 5.0);
+
+		network.clip_gradients(gradient_norm);
+		let clipped_norm = network.layers.iter().map(Layer::gradient_norm_squared).sum::<f64>().sqrt();
+		assert!((clipped_norm - 5.0).abs() < 1e-9);
+
+		// Small gradients are left alone
+		network.clip_gradients(1.0);
+		let unclipped_norm = network.layers.iter().map(Layer::gradient_norm_squared).sum::<f64>().sqrt();
+		assert_eq!(unclipped_norm, clipped_norm);
+	}
+
+	#[test]
+	fn fit() {
+		let mut network = NeuralNetwork::new(&[1], 1, vec![Activation::Linear]).unwrap();
+		let train: Vec<DataValue> = (0..20).map(|i| DataValue { input: vec![i as f64 / 10.0], expected_output: vec![2.0 * i as f64 / 10.0 + 1.0] }).collect();
+		let validation = vec![DataValue { input: vec![0.55], expected_output: vec![2.1] }];
+
+		let config = TrainConfig { learn_rate: 0.1, epochs: 30, seed: Some(7), batch_size: Some(4), lr_schedule: LrSchedule::Constant };
+		let history = network.fit(&train, config.clone(), Some(&validation)).unwrap();
+
+		assert_eq!(history.train_loss.len(), 30);
+		assert_eq!(history.validation_loss.len(), 30);
+		assert!(history.train_loss[29] < history.train_loss[0]);
+		assert!(history.validation_loss[29] < 1e-2);
+		assert_eq!(network.training_metadata(), Some(&config));
+
+		let no_validation = network.fit(&train, TrainConfig { batch_size: None, ..config.clone() }, None).unwrap();
+		assert!(no_validation.validation_loss.is_empty());
+
+		assert!(network.fit(&[], config.clone(), None).is_err());
+		assert!(network.fit(&train, config.clone(), Some(&[])).is_err());
+		assert!(network.fit(&train, TrainConfig { batch_size: Some(0), ..config }, None).is_err());
+	}
+
+	#[test]
+	fn fit_with_callback() {
+		let train: Vec<DataValue> = (0..20).map(|i| DataValue { input: vec![i as f64 / 10.0], expected_output: vec![2.0 * i as f64 / 10.0 + 1.0] }).collect();
+		let validation = vec![DataValue { input: vec![0.55], expected_output: vec![2.1] }];
+		let config = TrainConfig { learn_rate: 0.1, epochs: 5, seed: Some(7), batch_size: Some(4), lr_schedule: LrSchedule::Constant };
+
+		let mut network = NeuralNetwork::new_seeded(&[1], 1, vec![Activation::Linear], 1).unwrap();
+		let mut infos = Vec::new();
+		let history = network.fit_with_callback(&train, config.clone(), Some(&validation), |info| infos.push(info)).unwrap();
+
+		assert_eq!(infos.len(), 5);
+		for (epoch, info) in infos.iter().enumerate() {
+			assert_eq!(info.epoch, epoch);
+			assert_eq!(info.epochs, 5);
+			assert_eq!(info.train_loss, history.train_loss[epoch]);
+			assert_eq!(info.validation_loss, Some(history.validation_loss[epoch]));
+		}
+
+		// The callback doesn't change training
+		let mut same = NeuralNetwork::new_seeded(&[1], 1, vec![Activation::Linear], 1).unwrap();
+		assert_eq!(same.fit(&train, config.clone(), Some(&validation)).unwrap(), history);
+
+		let mut calls = 0;
+		network.fit_with_callback(&train, config, None, |info| {
+			assert_eq!(info.validation_loss, None);
+			calls += 1;
+		}).unwrap();
+		assert_eq!(calls, 5);
+	}
+
+	#[test]
+	fn minibatches() {
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(3);
+		let batches = super::minibatches(23, 5, &mut rng);
+
+		assert_eq!(batches.len(), 5);
+		assert!(batches[..4].iter().all(|batch| batch.len() == 5));
+		assert_eq!(batches[4].len(), 3);
+
+		let mut covered: Vec<usize> = batches.into_iter().flatten().collect();
+		covered.sort_unstable();
+		assert_eq!(covered, (0..23).collect::<Vec<usize>>());
+
+		let mut network = identity_network(2);
+		let data: Vec<DataValue> = (0..7).map(|i| DataValue { input: vec![i as f64, 1.0], expected_output: vec![i as f64, 1.0] }).collect();
+		network.learn_minibatches(&data, 0.01, 3).unwrap();
+		assert_eq!(network.gradient_norm_history().len(), 3);
+		assert!(network.learn_minibatches(&data, 0.01, 0).is_err());
+	}
+
+	#[test]
+	fn seeded() {
+		let network_a = NeuralNetwork::new_seeded(&[5, 2], 3, vec![Activation::Sigmoid, Activation::Linear], 11).unwrap();
+		let network_b = NeuralNetwork::new_seeded(&[5, 2], 3, vec![Activation::Sigmoid, Activation::Linear], 11).unwrap();
+		let network_c = NeuralNetwork::new_seeded(&[5, 2], 3, vec![Activation::Sigmoid, Activation::Linear], 12).unwrap();
+		assert_eq!(network_a.flat_parameters(), network_b.flat_parameters());
+		assert_ne!(network_a.flat_parameters(), network_c.flat_parameters());
+
+		let data: Vec<DataValue> = (0..30).map(|i| DataValue { input: vec![i as f64 / 30.0, 1.0, -0.5], expected_output: vec![(i % 2) as f64, 0.5] }).collect();
+		let mut losses = Vec::new();
+		let mut parameters = Vec::new();
+		for mut network in [network_a, network_b] {
+			network.set_dropout(0, 0.3).unwrap();
+			let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(5);
+			for _ in 0..10 {
+				network.learn_randomly_with_rng(&data, 0.1, 8, &mut rng).unwrap();
+			}
+			losses.push(network.loss(&data).unwrap());
+			parameters.push(network.flat_parameters());
+		}
+
+		assert_eq!(losses[0], losses[1]);
+		assert_eq!(parameters[0], parameters[1]);
+	}
+
+	#[test]
+	fn new_with_init() {
+		let network = NeuralNetwork::new_with_init(&[4, 2], 3, vec![Activation::Sigmoid, Activation::Sigmoid], Init::Constant { value: 0.25 }, Init::Zeros).unwrap();
+		assert_eq!(network.layer_weights(1).unwrap(), vec![vec![0.25; 4]; 2]);
+		assert_eq!(network.layer_biases(1).unwrap(), vec![0.0; 2]);
+
+		let network = NeuralNetwork::new_with_init(&[4, 2], 3, vec![Activation::ReLU, Activation::Linear], Init::He, Init::Constant { value: 0.01 }).unwrap();
+		assert_eq!(network.layer_biases(0).unwrap(), vec![0.01; 4]);
+		assert_eq!(network.layer_biases(1).unwrap(), vec![0.01; 2]);
+	}
+
+	#[test]
+	fn clone() {
+		let mut network = NeuralNetwork::new(&[4, 2], 3, vec![Activation::HyperTan, Activation::Softmax]).unwrap();
+		network.set_optimizer(Optimizer::adam_default()).unwrap();
+		let data = vec![DataValue { input: vec![0.5, -1.0, 2.0], expected_output: vec![1.0, 0.0] }];
+		network.learn(&data, 0.1).unwrap();
+
+		let mut cloned = network.clone();
+		assert_eq!(cloned.activate(&data[0].input).unwrap(), network.activate(&data[0].input).unwrap());
+		assert_eq!(cloned.flat_parameters(), network.flat_parameters());
+
+		// Training the clone doesn't touch the original, and both train the same way
+		let before = network.flat_parameters();
+		cloned.learn(&data, 0.1).unwrap();
+		assert_eq!(network.flat_parameters(), before);
+		network.learn(&data, 0.1).unwrap();
+		assert_eq!(cloned.flat_parameters(), network.flat_parameters());
+	}
+
+	#[test]
+	fn reset_training_state() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, 3.0] }];
+		network.learn(&data, 0.1).unwrap();
+		network.accumulate_gradients(&data[0], false, &mut rand::rng()).unwrap();
+		assert!(network.layers.iter().map(Layer::gradient_norm_squared).sum::<f64>() > 0.0);
+
+		let parameters = network.flat_parameters();
+		network.reset_training_state();
+
+		assert_eq!(network.layers.iter().map(Layer::gradient_norm_squared).sum::<f64>(), 0.0);
+		assert!(network.gradient_norm_history().is_empty());
+		let neuron = network.get_layer(1).unwrap().get_neuron(0).unwrap();
+		assert_eq!(neuron.get_last_output(), 0.0);
+		assert_eq!(neuron.get_last_deriv(), 0.0);
+		assert_eq!(network.flat_parameters(), parameters);
+	}
+
+	#[test]
+	fn confusion_matrix() {
+		let mut network = identity_network(3);
+		let data = vec![
+			DataValue::from_label(vec![1.0, 0.0, 0.0], 0, 3).unwrap(),
+			DataValue::from_label(vec![1.0, 0.0, 0.0], 0, 3).unwrap(),
+			DataValue::from_label(vec![0.0, 0.0, 1.0], 1, 3).unwrap(),
+			DataValue::from_label(vec![0.0, 1.0, 0.0], 1, 3).unwrap(),
+			DataValue::from_label(vec![1.0, 0.0, 0.0], 2, 3).unwrap(),
+		];
+
+		assert_eq!(network.confusion_matrix(&data, 3).unwrap(), vec![
+			vec![2, 0, 0],
+			vec![0, 1, 1],
+			vec![1, 0, 0],
+		]);
+		assert!(network.confusion_matrix(&data, 2).is_err());
+	}
+
+	#[test]
+	fn top_k_accuracy() {
+		let mut network = identity_network(3);
+		// Class 1 has the second largest output
+		let data = vec![DataValue::from_label(vec![0.5, 0.3, 0.1], 1, 3).unwrap()];
+
+		assert_eq!(network.top_k_accuracy(&data, 1).unwrap(), 0.0);
+		assert_eq!(network.top_k_accuracy(&data, 2).unwrap(), 1.0);
+		assert_eq!(network.top_k_accuracy(&data, 3).unwrap(), 1.0);
+		assert_eq!(network.top_k_accuracy(&data, 1).unwrap(), network.accuracy(&data).unwrap());
+
+		assert!(network.top_k_accuracy(&data, 0).is_err());
+		assert!(network.top_k_accuracy(&data, 4).is_err());
+		assert!(network.top_k_accuracy(&[], 1).is_err());
+	}
+
+	#[test]
+	fn builder() {
+		let mut network = NetworkBuilder::new(3)
+			.layer(5, Activation::ReLU)
+			.layer(2, Activation::Softmax)
+			.bias_init(Init::Constant { value: 0.01 })
+			.loss(Loss::CrossEntropy)
+			.seed(4)
+			.build()
+			.unwrap();
+
+		assert_eq!(network.get_layer_count(), 2);
+		assert_eq!(network.activate(&[1.0, 2.0, 3.0]).unwrap().len(), 2);
+		assert_eq!(network.get_loss(), &Loss::CrossEntropy);
+		assert_eq!(network.layer_biases(0).unwrap(), vec![0.01; 5]);
+		assert_eq!(network.layer_weights(1).unwrap()[0].len(), 5);
+
+		let seeded = NeuralNetwork::new_seeded(&[5, 2], 3, vec![Activation::ReLU, Activation::Softmax], 4).unwrap();
+		assert_eq!(NetworkBuilder::new(3).layer(5, Activation::ReLU).layer(2, Activation::Softmax).seed(4).build().unwrap().flat_parameters(), seeded.flat_parameters());
+
+		assert!(matches!(NetworkBuilder::new(3).build(), Err(NeuralibError::NoLayers(_))));
+	}
+
+	#[test]
+	fn per_neuron_activations() {
+		let mut network = NetworkBuilder::new(1)
+			.layer_per_neuron(vec![Activation::Linear, Activation::ReLU, Activation::Sigmoid])
+			.weight_init(Init::Constant { value: 1.0 })
+			.build()
+			.unwrap();
+
+		assert_eq!(network.activate(&[-2.0]).unwrap(), vec![-2.0, 0.0, Activation::Sigmoid.call(-2.0)]);
+		assert_eq!(network.predict(&[3.0]).unwrap(), vec![3.0, 3.0, Activation::Sigmoid.call(3.0)]);
+		assert!(network.summary().contains("Mixed"));
+
+		let data = vec![DataValue { input: vec![0.5], expected_output: vec![1.0, 2.0, 0.2] }];
+		let before = network.loss(&data).unwrap();
+		network.learn(&data, 0.1).unwrap();
+		assert!(network.loss(&data).unwrap() < before);
+
+		assert!(NetworkBuilder::new(1).layer_per_neuron(vec![Activation::Softmax, Activation::Linear]).build().is_err());
+	}
+
+	struct WrongCube;
+
+	impl crate::activation::ActivationFn for WrongCube {
+		fn call(&self, x: f64) -> f64 {
+			x * x * x
+		}
+
+		fn derivative(&self, x: f64) -> f64 {
+			2.0 * x * x
+		}
+	}
+
+	#[test]
+	fn gradient_check() {
+		let data = [
+			DataValue { input: vec![0.5, -1.0], expected_output: vec![0.2, 0.7, 0.1] },
+			DataValue { input: vec![-0.3, 0.8], expected_output: vec![0.0, 0.0, 1.0] },
+		];
+
+		let mut network = NetworkBuilder::new(2)
+			.layer_per_neuron(vec![Activation::Sigmoid, Activation::HyperTan, Activation::custom(Cube), Activation::LeakyReLU { slope: 0.1 }])
+			.layer(3, Activation::Softmax)
+			.loss(Loss::CrossEntropy)
+			.seed(1)
+			.build()
+			.unwrap();
+		let before = network.predict(&[1.0, 1.0]).unwrap();
+
+		assert!(network.gradient_check(&data, 1e-5).unwrap() < 1e-6);
+		// The parameters are restored
+		assert_eq!(network.predict(&[1.0, 1.0]).unwrap(), before);
+
+		let mut network = NeuralNetwork::new_seeded(&[3, 1], 2, vec![Activation::custom(WrongCube), Activation::Linear], 1).unwrap();
+		let wrong_data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0] }];
+		assert!(network.gradient_check(&wrong_data, 1e-5).unwrap() > 1e-2);
+
+		assert!(network.gradient_check(&wrong_data, 0.0).is_err());
+		assert!(network.gradient_check(&[], 1e-5).is_err());
+	}
+
+	#[test]
+	fn regression_metrics() {
+		let data: Vec<DataValue> = (-5..=5)
+			.map(|x| x as f64 / 5.0)
+			.map(|x| DataValue { input: vec![x], expected_output: vec![2.0 * x + 1.0, 0.5 - x] })
+			.collect();
+
+		let mut network = NeuralNetwork::new_seeded(&[2], 1, vec![Activation::Linear], 1).unwrap();
+		for _ in 0..500 {
+			network.learn(&data, 0.1).unwrap();
+		}
+
+		let metrics = network.regression_metrics(&data).unwrap();
+		assert!(metrics.mae < 1e-3);
+		assert!(metrics.rmse < 1e-3);
+		assert!(metrics.r_squared > 0.9999);
+
+		// Predicting 0 for everything: every error is the expected output itself
+		let mut zeros = NetworkBuilder::new(1).layer(2, Activation::Linear).weight_init(Init::Zeros).build().unwrap();
+		let metrics = zeros.regression_metrics(&data).unwrap();
+		let expected: Vec<f64> = data.iter().flat_map(|value| value.expected_output.clone()).collect();
+		assert!((metrics.mae - expected.iter().map(|x| x.abs()).sum::<f64>() / 22.0).abs() < 1e-12);
+		assert!((metrics.rmse - (expected.iter().map(|x| x * x).sum::<f64>() / 22.0).sqrt()).abs() < 1e-12);
+		assert!(metrics.r_squared < 0.0);
+
+		assert!(network.regression_metrics(&[]).is_err());
+		assert!(network.regression_metrics(&[DataValue { input: vec![0.0], expected_output: vec![0.0] }]).is_err());
+	}
+
+	#[test]
+	fn fused_softmax_cross_entropy() {
+		let mut network = NetworkBuilder::new(2).layer(2, Activation::Softmax).loss(Loss::CrossEntropy).build().unwrap();
+		// The weighted sums are the inputs
+		network.set_flat_parameters(&[1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+
+		let value = DataValue { input: vec![0.5, -1.5], expected_output: vec![0.25, 0.75] };
+		let manual = -(0.25 * (0.5 - (0.5_f64.exp() + (-1.5_f64).exp()).ln()) + 0.75 * (-1.5 - (0.5_f64.exp() + (-1.5_f64).exp()).ln()));
+		assert!((network.loss_with_value(&value).unwrap() - manual).abs() < 1e-12);
+
+		// The first output rounds to 0, which would cap the unfused loss at -ln(1e-15)
+		let confident = DataValue { input: vec![0.0, 1000.0], expected_output: vec![1.0, 0.0] };
+		assert!((network.loss_with_value(&confident).unwrap() - 1000.0).abs() < 1e-9);
+
+		// The gradient is output - expected
+		network.update_all_gradients(&value).unwrap();
+		let output = network.predict(&value.input).unwrap();
+		for (neuronidx, (output, expected)) in output.iter().zip(&value.expected_output).enumerate() {
+			let gradient = network.get_layer(0).unwrap().get_neuron(neuronidx).unwrap().get_loss_gradient().loss_gradient_bias;
+			assert!((gradient - (output - expected)).abs() < 1e-12);
+		}
+	}
+
+	#[test]
+	fn learn_randomly_subset() {
+		use rand::seq::SliceRandom;
+
+		let data: Vec<DataValue> = (0..20).map(|x| DataValue { input: vec![x as f64 / 10.0], expected_output: vec![(x % 3) as f64] }).collect();
+		let mut network = NeuralNetwork::new_seeded(&[3, 1], 1, vec![Activation::HyperTan, Activation::Linear], 1).unwrap();
+		let mut expected = network.clone();
+
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(5);
+		network.learn_randomly_with_rng(&data, 0.1, 6, &mut rng).unwrap();
+
+		// Shuffling a copy of the data with the same generator picks the same subset
+		let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(5);
+		let mut shuffled = data.clone();
+		shuffled.shuffle(&mut rng);
+		expected.learn(&shuffled[..6], 0.1).unwrap();
+
+		assert_eq!(network.flat_parameters(), expected.flat_parameters());
+	}
+
+	/// The summed loss of a batch run through the network with batch normalization using the batch's statistics
+	fn batch_loss(network: &NeuralNetwork, data: &[&DataValue]) -> f64 {
+		let mut network = network.clone();
+		let mut outputs: Vec<Vec<f64>> = data.iter().map(|value| value.input.clone()).collect();
+		for layer in &mut network.layers {
+			outputs = layer.activate_batch_training(outputs, &mut rand::rng()).outputs().to_vec();
+		}
+
+		outputs.iter().zip(data)
+			.map(|(output, value)| output.iter().zip(&value.expected_output).map(|(output, expected)| network.loss.call(*output, *expected)).sum::<f64>())
+			.sum()
+	}
+
+	#[test]
+	fn batch_norm_gradients() {
+		let data = [
+			DataValue { input: vec![0.5, -1.0], expected_output: vec![0.0, 1.0, 0.0] },
+			DataValue { input: vec![-0.3, 0.8], expected_output: vec![1.0, 0.0, 0.0] },
+			DataValue { input: vec![1.2, 0.1], expected_output: vec![0.0, 0.0, 1.0] },
+			DataValue { input: vec![-0.9, -0.4], expected_output: vec![0.0, 1.0, 0.0] },
+		];
+		let data: Vec<&DataValue> = data.iter().collect();
+
+		let softmax = NetworkBuilder::new(2).layer(4, Activation::Sigmoid).layer(3, Activation::Softmax).loss(Loss::CrossEntropy).seed(1).build().unwrap();
+		let linear = NetworkBuilder::new(2).layer(4, Activation::PReLU { initial_slope: 0.2 }).layer(3, Activation::Linear).seed(2).build().unwrap();
+
+		for mut network in [softmax, linear] {
+			network.set_batch_norm(0, true).unwrap();
+			network.set_batch_norm(1, true).unwrap();
+			network.set_layer_norm(0, true).unwrap();
+
+			network.accumulate_gradients_batch(&data, &mut rand::rng()).unwrap();
+			let mut analytic = Vec::new();
+			for layer in &network.layers {
+				for neuron in (0..layer.get_neuron_count()).filter_map(|neuronidx| layer.get_neuron(neuronidx)) {
+					analytic.extend(&neuron.get_loss_gradient().loss_gradient_weight);
+					analytic.push(neuron.get_loss_gradient().loss_gradient_bias);
+				}
+			}
+
+			let original = network.flat_parameters();
+			let epsilon = 1e-6;
+			for (idx, analytic) in analytic.iter().enumerate() {
+				let mut moved = original.clone();
+				moved[idx] += epsilon;
+				network.set_flat_parameters(&moved);
+				let loss_up = batch_loss(&network, &data);
+				moved[idx] -= 2.0 * epsilon;
+				network.set_flat_parameters(&moved);
+				let loss_down = batch_loss(&network, &data);
+
+				assert!((analytic - (loss_up - loss_down) / (2.0 * epsilon)).abs() < 1e-6);
+			}
+			network.set_flat_parameters(&original);
+		}
+	}
+
+	#[test]
+	fn batch_norm() {
+		let mut network = NetworkBuilder::new(2).layer(3, Activation::ReLU).layer(1, Activation::Linear).seed(1).build().unwrap();
+		assert!(network.set_batch_norm(2, true).is_err());
+		network.set_batch_norm(0, true).unwrap();
+		assert!(network.summary().contains("ReLU + BatchNorm"));
+
+		// Inputs far from 0 with very different scales
+		let data: Vec<DataValue> = (0..16)
+			.map(|x| DataValue { input: vec![100.0 + x as f64, 0.01 * (x % 4) as f64], expected_output: vec![0.1 * x as f64] })
+			.collect();
+
+		let before = network.loss(&data).unwrap();
+		for _ in 0..200 {
+			network.learn_minibatches(&data, 0.05, 4).unwrap();
+		}
+		// The running statistics are used outside of training
+		assert!(network.loss(&data).unwrap() < before);
+		assert!(network.loss(&data).unwrap() < 0.05);
+		assert_eq!(network.activate(&[105.0, 0.02]).unwrap(), network.predict(&[105.0, 0.02]).unwrap());
+
+		// Single DataValues use the running statistics too, and still train the scale and shift
+		let affine = |network: &NeuralNetwork| -> Vec<(f64, f64)> { (0..3).map(|neuronidx| network.get_layer(0).unwrap().get_batch_norm().unwrap().affine(neuronidx)).collect() };
+		let original = affine(&network);
+		let before = network.loss(&data[15..]).unwrap();
+		network.learn(&data[15..], 0.001).unwrap();
+		assert_ne!(affine(&network), original);
+		assert!(network.loss(&data[15..]).unwrap() < before);
+
+		#[cfg(feature = "serde")]
+		{
+			let mut saved = Vec::new();
+			network.save(&mut saved).unwrap();
+			let loaded = NeuralNetwork::load(saved.as_slice()).unwrap();
+			assert_eq!(loaded.predict(&[103.0, 0.03]).unwrap(), network.predict(&[103.0, 0.03]).unwrap());
+		}
+
+		network.set_batch_norm(0, false).unwrap();
+		assert!(!network.summary().contains("BatchNorm"));
+	}
+
+	#[test]
+	fn layer_norm() {
+		let mut network = NetworkBuilder::new(2).layer(4, Activation::Linear).layer(1, Activation::Linear).seed(1).build().unwrap();
+		assert!(network.set_layer_norm(2, true).is_err());
+		network.set_layer_norm(0, true).unwrap();
+		assert!(network.summary().contains("Linear + LayerNorm"));
+
+		// The hidden layer's outputs have a mean of 0 for every input before training
+		for input in [[100.0, -3.0], [0.5, 0.2], [-7.0, 40.0]] {
+			let outputs = network.get_layer(0).unwrap().predict(&input).unwrap();
+			assert!((outputs.iter().sum::<f64>() / 4.0).abs() < 1e-12);
+		}
+
+		let data: Vec<DataValue> = (0..16)
+			.map(|x| DataValue { input: vec![x as f64, (x % 4) as f64], expected_output: vec![0.1 * x as f64] })
+			.collect();
+
+		let before = network.loss(&data).unwrap();
+		for _ in 0..100 {
+			network.learn(&data, 0.05).unwrap();
+		}
+		assert!(network.loss(&data).unwrap() < before);
+
+		// The gain and bias trained, so the outputs no longer have a mean of 0
+		let outputs = network.get_layer(0).unwrap().predict(&[3.0, 1.0]).unwrap();
+		assert!((outputs.iter().sum::<f64>() / 4.0).abs() > 1e-6);
+		assert_eq!(network.activate(&[3.0, 1.0]).unwrap(), network.predict(&[3.0, 1.0]).unwrap());
+
+		#[cfg(feature = "serde")]
+		{
+			let mut saved = Vec::new();
+			network.save(&mut saved).unwrap();
+			let loaded = NeuralNetwork::load(saved.as_slice()).unwrap();
+			assert_eq!(loaded.predict(&[3.0, 1.0]).unwrap(), network.predict(&[3.0, 1.0]).unwrap());
+		}
+	}
+
+	#[test]
+	fn layer_norm_gradients() {
+		// Layer normalization mixes the weighted sums of a layer, which the per-DataValue gradients have to account for
+		let mut network = NetworkBuilder::new(3).layer(4, Activation::PReLU { initial_slope: 0.2 }).layer(3, Activation::Linear).seed(3).build().unwrap();
+		network.set_layer_norm(0, true).unwrap();
+		network.set_layer_norm(1, true).unwrap();
+
+		let data = [DataValue { input: vec![0.4, -1.3, 2.0], expected_output: vec![0.2, 0.9, -1.5] }];
+		assert!(network.gradient_check(&data, 1e-5).unwrap() < 1e-6);
+	}
+
+	#[test]
+	fn activate_with_intermediates() {
+		let mut network = NeuralNetwork::new(&[3, 2], 4, vec![Activation::ReLU, Activation::Sigmoid]).unwrap();
+
+		let outputs = network.activate_with_intermediates(&[0.5, -1.0, 2.0, 0.1]).unwrap();
+		assert_eq!(outputs.len(), 2);
+		assert_eq!(outputs[0].len(), 3);
+		assert_eq!(outputs[1].len(), 2);
+		assert_eq!(outputs[0], network.get_layer(0).unwrap().predict(&[0.5, -1.0, 2.0, 0.1]).unwrap());
+		assert_eq!(outputs[1], network.predict(&[0.5, -1.0, 2.0, 0.1]).unwrap());
+
+		assert!(network.activate_with_intermediates(&[1.0]).is_err());
+	}
+
+	#[test]
+	fn max_weight_norm() {
+		let mut network = identity_network(3);
+		assert!(network.set_max_weight_norm(Some(0.0)).is_err());
+		assert!(network.set_max_weight_norm(Some(f64::NAN)).is_err());
+		network.set_max_weight_norm(Some(3.0)).unwrap();
+		assert_eq!(network.get_max_weight_norm(), Some(3.0));
+
+		// A far off output pushes the weights way past the cap
+		let data = [DataValue { input: vec![10.0, -5.0, 8.0], expected_output: vec![-100.0, 100.0, 50.0] }];
+		network.learn(&data, 0.1).unwrap();
+
+		for neuronidx in 0..3 {
+			let neuron = network.get_layer(0).unwrap().get_neuron(neuronidx).unwrap();
+			let norm = (0..3).map(|weightidx| neuron.get_weight(weightidx).unwrap().powi(2)).sum::<f64>().sqrt();
+			assert!((norm - 3.0).abs() < 1e-10);
+		}
+
+		// Weights under the cap are left alone
+		let mut network = identity_network(3);
+		let mut unconstrained = network.clone();
+		network.set_max_weight_norm(Some(3.0)).unwrap();
+		let data = [DataValue { input: vec![1.0, 0.0, 0.0], expected_output: vec![1.1, 0.0, 0.0] }];
+		network.learn(&data, 0.1).unwrap();
+		unconstrained.learn(&data, 0.1).unwrap();
+		assert_eq!(network.flat_parameters(), unconstrained.flat_parameters());
+	}
+
+	#[test]
+	fn load_parameters() {
+		let network = NeuralNetwork::new(&[4, 2], 3, vec![Activation::HyperTan, Activation::Sigmoid]).unwrap();
+		let parameters = network.export_parameters();
+		assert_eq!(parameters.len(), network.num_parameters());
+		// The first neuron's weights come first, then its bias
+		assert_eq!(parameters[..3], network.layer_weights(0).unwrap()[0]);
+		assert_eq!(parameters[3], network.layer_biases(0).unwrap()[0]);
+
+		let mut fresh = NeuralNetwork::new(&[4, 2], 3, vec![Activation::HyperTan, Activation::Sigmoid]).unwrap();
+		assert!(matches!(fresh.load_parameters(&parameters[1..]), Err(NeuralibError::InvalidArgument(_))));
+		fresh.load_parameters(&parameters).unwrap();
+		assert_eq!(fresh.export_parameters(), parameters);
+		assert_eq!(fresh.predict(&[0.5, -1.0, 2.0]).unwrap(), network.predict(&[0.5, -1.0, 2.0]).unwrap());
+
+		// PReLU slopes and the normalization parameters and running statistics are included too
+		let build = || {
+			let mut network = NeuralNetwork::new(&[4, 2], 3, vec![Activation::PReLU { initial_slope: 0.25 }, Activation::Linear]).unwrap();
+			network.set_batch_norm(0, true).unwrap();
+			network.set_layer_norm(1, true).unwrap();
+			network
+		};
+		let mut network = build();
+		let data: Vec<DataValue> = (0..4).map(|i| DataValue { input: vec![i as f64, -1.0 - i as f64, 0.5], expected_output: vec![1.0 - i as f64, 0.3 * i as f64] }).collect();
+		for _ in 0..5 {
+			network.learn(&data, 0.1).unwrap();
+		}
+		// Weights and biases, 4 slopes, 2 * 4 batch normalization parameters, and 2 * 2 layer normalization parameters
+		assert_eq!(network.num_parameters(), 4 * 4 + 2 * 5 + 4 + 8 + 4);
+		let parameters = network.export_parameters();
+		// Plus the 2 * 4 running statistics
+		assert_eq!(parameters.len(), network.num_parameters() + 8);
+
+		let mut fresh = build();
+		assert!(matches!(fresh.load_parameters(&parameters[1..]), Err(NeuralibError::InvalidArgument(_))));
+		fresh.load_parameters(&parameters).unwrap();
+		assert_eq!(fresh.export_parameters(), parameters);
+		for input in [[0.5, -1.0, 2.0], [-3.0, -2.0, -0.5]] {
+			assert_eq!(fresh.predict(&input).unwrap(), network.predict(&input).unwrap());
+		}
+	}
+
+	#[test]
+	fn set_layer_weights() {
+		let mut network = NeuralNetwork::new(&[2], 3, vec![Activation::Linear]).unwrap();
+		let weights = vec![vec![1.0, 2.0, 3.0], vec![-1.0, 0.0, 0.5]];
+
+		assert!(network.set_layer_weights(1, weights.clone(), vec![0.0, 0.0]).is_err());
+		assert!(network.set_layer_weights(0, weights[..1].to_vec(), vec![0.0, 0.0]).is_err());
+		assert!(network.set_layer_weights(0, vec![vec![1.0, 2.0], vec![3.0, 4.0]], vec![0.0, 0.0]).is_err());
+		assert!(network.set_layer_weights(0, weights.clone(), vec![0.0]).is_err());
+
+		network.set_layer_weights(0, weights.clone(), vec![0.5, -1.0]).unwrap();
+		assert_eq!(network.layer_weights(0).unwrap(), weights);
+		assert_eq!(network.layer_biases(0).unwrap(), vec![0.5, -1.0]);
+		assert_eq!(network.predict(&[1.0, 1.0, 2.0]).unwrap(), vec![9.5, -1.0]);
+	}
+
+	#[test]
+	fn class_weights() {
+		let mut network = identity_network(3);
+		assert!(network.set_class_weights(Some(vec![1.0, 5.0])).is_err());
+		assert!(network.set_class_weights(Some(vec![1.0, -5.0, 1.0])).is_err());
+		assert!(network.set_class_weights(Some(vec![1.0, f64::NAN, 1.0])).is_err());
+
+		let value = DataValue { input: vec![1.0, 2.0, 3.0], expected_output: vec![0.0, 0.0, 0.0] };
+		let mut weighted = network.clone();
+		weighted.set_class_weights(Some(vec![1.0, 5.0, 1.0])).unwrap();
+		assert_eq!(weighted.get_class_weights(), Some([1.0, 5.0, 1.0].as_slice()));
+
+		network.accumulate_gradients(&value, false, &mut rand::rng()).unwrap();
+		weighted.accumulate_gradients(&value, false, &mut rand::rng()).unwrap();
+		for neuronidx in 0..3 {
+			let gradient = &network.get_layer(0).unwrap().get_neuron(neuronidx).unwrap().get_loss_gradient().loss_gradient_weight;
+			let weighted_gradient = &weighted.get_layer(0).unwrap().get_neuron(neuronidx).unwrap().get_loss_gradient().loss_gradient_weight;
+			// Only the output with a weight of 5 gets a bigger gradient
+			let scale = if neuronidx == 1 { 5.0 } else { 1.0 };
+			for (gradient, weighted_gradient) in gradient.iter().zip(weighted_gradient) {
+				assert!((gradient * scale - weighted_gradient).abs() < 1e-10);
+			}
+		}
+		assert!((weighted.loss_with_value(&value).unwrap() - network.loss_with_value(&value).unwrap() - 4.0 * 4.0).abs() < 1e-10);
+
+		// The weighted loss matches its gradients, including the fused softmax with cross-entropy
+		let mut network = NetworkBuilder::new(3).layer(4, Activation::HyperTan).layer(3, Activation::Softmax).loss(Loss::CrossEntropy).seed(2).build().unwrap();
+		network.set_class_weights(Some(vec![0.5, 4.0, 1.0])).unwrap();
+		let data = [DataValue { input: vec![0.4, -1.3, 2.0], expected_output: vec![0.0, 1.0, 0.0] }, DataValue { input: vec![1.0, 0.3, -0.2], expected_output: vec![1.0, 0.0, 0.0] }];
+		assert!(network.gradient_check(&data, 1e-5).unwrap() < 1e-6);
+	}
+
+	#[test]
+	fn zero_size_layers() {
+		let Err(NeuralibError::ZeroSize(err)) = NeuralNetwork::new(&[0, 10], 5, vec![Activation::ReLU, Activation::Sigmoid]) else {
+			panic!("Expected a ZeroSizeError");
+		};
+		assert_eq!(err.layer, Some(0));
+		assert!(err.to_string().contains("Layer 0"));
+
+		let Err(NeuralibError::ZeroSize(err)) = NeuralNetwork::new(&[3, 2], 0, vec![Activation::ReLU, Activation::Sigmoid]) else {
+			panic!("Expected a ZeroSizeError");
+		};
+		assert_eq!(err.layer, None);
+
+		assert!(matches!(NetworkBuilder::new(3).layer(4, Activation::ReLU).layer(0, Activation::Linear).build(), Err(NeuralibError::ZeroSize(_))));
+	}
+
+	#[test]
+	fn freeze_layer() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		network.freeze_layer(0).unwrap();
+		assert_eq!(network.is_layer_frozen(0), Some(true));
+		assert_eq!(network.is_layer_frozen(1), Some(false));
+		assert_eq!(network.is_layer_frozen(2), None);
+
+		let frozen = (network.layer_weights(0).unwrap(), network.layer_biases(0).unwrap());
+		let head = network.layer_weights(1).unwrap();
+
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, -2.0] }];
+		network.learn(&data, 0.1).unwrap();
+		network.learn_minibatches(&data, 0.1, 1).unwrap();
+
+		assert_eq!((network.layer_weights(0).unwrap(), network.layer_biases(0).unwrap()), frozen);
+		assert_ne!(network.layer_weights(1).unwrap(), head);
+		// Frozen layers don't accumulate gradients
+		network.update_all_gradients(&data[0]).unwrap();
+		assert!(network.get_layer(0).unwrap().get_neuron(0).unwrap().get_loss_gradient().loss_gradient_weight.iter().all(|gradient| *gradient == 0.0));
+
+		network.unfreeze_layer(0).unwrap();
+		network.learn(&data, 0.1).unwrap();
+		assert_ne!(network.layer_weights(0).unwrap(), frozen.0);
+
+		assert!(network.freeze_layer(2).is_err());
+		assert!(network.unfreeze_layer(2).is_err());
+
+		// The running statistics of frozen batch normalization stay the same too
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Linear]).unwrap();
+		network.set_batch_norm(0, true).unwrap();
+		network.freeze_layer(0).unwrap();
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: vec![1.0, -2.0] }, DataValue { input: vec![3.0, 2.0], expected_output: vec![0.0, 1.0] }];
+		let hidden = network.get_layer(0).unwrap().predict(&[1.0, 1.0]).unwrap();
+		network.learn(&data, 0.1).unwrap();
+		assert_eq!(network.get_layer(0).unwrap().predict(&[1.0, 1.0]).unwrap(), hidden);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn save_architecture() {
+		let mut network = NeuralNetwork::new(&[4, 3, 2], 5, vec![Activation::ReLU, Activation::HyperTan, Activation::Softmax]).unwrap();
+		let inputs = [0.3, -1.2, 0.8, 2.0, -0.5];
+
+		let mut buf = Vec::new();
+		network.save(&mut buf).unwrap();
+
+		// The topology comes from the save, without knowing the arguments to `new`
+		let mut loaded = NeuralNetwork::load(&buf[..]).unwrap();
+		let architecture = loaded.architecture();
+		assert_eq!(architecture.input_size, 5);
+		assert_eq!(architecture.layer_sizes(), vec![4, 3, 2]);
+		assert!(matches!(architecture.layer_activations[1][2], Activation::HyperTan));
+		assert_eq!(network.activate(&inputs).unwrap(), loaded.activate(&inputs).unwrap());
+
+		let mut rebuilt = NeuralNetwork::from_architecture(&architecture).unwrap();
+		assert_eq!(rebuilt.summary(), network.summary());
+		assert_eq!(rebuilt.activate(&inputs).unwrap().len(), 2);
+
+		let mut json = Vec::new();
+		network.save_json(&mut json).unwrap();
+		assert_eq!(NeuralNetwork::load_json(&json[..]).unwrap().architecture().layer_sizes(), vec![4, 3, 2]);
+	}
+
+	#[test]
+	fn loss_sum() {
+		let mut network = NeuralNetwork::new_seeded(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Softmax], 4).unwrap();
+		network.set_loss(Loss::CrossEntropy);
+		let data: Vec<DataValue> = (0..5).map(|i| DataValue { input: vec![i as f64 * 0.4, 1.0 - i as f64], expected_output: DataValue::one_hot(i % 2, 2).unwrap() }).collect();
+
+		let sum = network.loss_sum(&data).unwrap();
+		let mean = network.loss(&data).unwrap();
+		assert!((sum - mean * data.len() as f64).abs() < 1e-12);
+		assert!((sum - data.iter().map(|value| network.loss_with_value(value).unwrap()).sum::<f64>()).abs() < 1e-12);
+
+		assert_eq!(network.loss_sum(&[]).unwrap(), 0.0);
+		assert!(network.loss_sum(&[DataValue { input: vec![1.0, 2.0], expected_output: vec![1.0] }]).is_err());
+	}
+
+	#[test]
+	fn input_and_output_size() {
+		let network = NeuralNetwork::new(&[4, 3, 2], 5, vec![Activation::ReLU, Activation::HyperTan, Activation::Softmax]).unwrap();
+		assert_eq!(network.input_size(), 5);
+		assert_eq!(network.output_size(), 2);
+		assert_eq!(network.predict(&[0.0; 5]).unwrap().len(), network.output_size());
+
+		let network = NeuralNetwork::new(&[7], 1, vec![Activation::Linear]).unwrap();
+		assert_eq!(network.input_size(), 1);
+		assert_eq!(network.output_size(), 7);
+	}
+
+	#[test]
+	fn replace_output_layer_with_class_weights() {
+		let mut network = NeuralNetwork::new(&[3, 2], 2, vec![Activation::Sigmoid, Activation::Softmax]).unwrap();
+		network.set_class_weights(Some(vec![1.0, 2.0])).unwrap();
+		network.replace_output_layer(4, Activation::Softmax).unwrap();
+		assert_eq!(network.get_class_weights(), None);
+
+		let data = vec![DataValue { input: vec![0.5, -1.0], expected_output: DataValue::one_hot(3, 4).unwrap() }];
+		network.loss(&data).unwrap();
+		network.learn(&data, 0.1).unwrap();
+		network.set_class_weights(Some(vec![1.0, 2.0, 1.0, 0.5])).unwrap();
+		network.loss(&data).unwrap();
+	}
+
+	#[test]
+	fn mixed_custom_activations() {
+		let cube = Activation::custom(Cube);
+		let network = NetworkBuilder::new(1).layer_per_neuron(vec![cube.clone(), cube.clone()]).build().unwrap();
+		assert!(!network.summary().contains("Mixed"));
+
+		let network = NetworkBuilder::new(1).layer_per_neuron(vec![cube, Activation::custom(WrongCube)]).build().unwrap();
+		assert!(network.summary().contains("Mixed"));
+
+		let network = NetworkBuilder::new(1).layer_per_neuron(vec![Activation::ELU { alpha: 1.0 }, Activation::ELU { alpha: 0.5 }]).build().unwrap();
+		assert!(network.summary().contains("Mixed"));
+	}
+
+	#[test]
+	fn fused_loss_with_normalized_output() {
+		let data: Vec<DataValue> = (0..6).map(|i| DataValue { input: vec![i as f64 * 0.3, 1.0 - i as f64 * 0.5], expected_output: DataValue::one_hot(i % 3, 3).unwrap() }).collect();
+
+		for (batch_norm, layer_norm) in [(true, false), (false, true)] {
+			let mut network = NeuralNetwork::new_seeded(&[4, 3], 2, vec![Activation::HyperTan, Activation::Softmax], 3).unwrap();
+			network.set_loss(Loss::CrossEntropy);
+			network.set_batch_norm(1, batch_norm).unwrap();
+			network.set_layer_norm(1, layer_norm).unwrap();
+			network.learn(&data, 0.1).unwrap();
+
+			let expected = data.iter()
+				.map(|value| -network.predict(&value.input).unwrap().iter().zip(&value.expected_output).map(|(output, expected)| expected * output.ln()).sum::<f64>())
+				.sum::<f64>() / data.len() as f64;
+			assert!((network.loss(&data).unwrap() - expected).abs() < 1e-10);
+		}
+	}
+
+	#[test]
+	fn invalid_variance_scale() {
+		use crate::init::FanMode;
+
+		for scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+			for uniform in [false, true] {
+				let init = Init::VarianceScaling { scale, mode: FanMode::FanIn, uniform };
+				let network = NeuralNetwork::new_with_init(&[3, 2], 2, vec![Activation::ReLU, Activation::Linear], init.clone(), Init::Zeros);
+				assert!(matches!(network, Err(NeuralibError::InvalidArgument(_))));
+				let network = NetworkBuilder::new(2).layer(3, Activation::ReLU).bias_init(init).build();
+				assert!(matches!(network, Err(NeuralibError::InvalidArgument(_))));
+			}
+		}
+
+		let init = Init::VarianceScaling { scale: 0.5, mode: FanMode::FanAvg, uniform: true };
+		assert!(NetworkBuilder::new(2).layer(3, Activation::ReLU).weight_init(init).build().is_ok());
+	}
+
+	#[test]
+	fn fold_batchnorm() {
+		let mut network = NeuralNetwork::new_seeded(&[5, 4, 3], 3, vec![Activation::ReLU, Activation::HyperTan, Activation::Softmax], 2).unwrap();
+		network.set_batch_norm(0, true).unwrap();
+		network.set_batch_norm(2, true).unwrap();
+		network.set_layer_norm(2, true).unwrap();
+		// Move the parameters and running statistics away from the identity
+		let data: Vec<DataValue> = (0..6).map(|i| DataValue { input: vec![i as f64, 1.0 - 0.5 * i as f64, 0.3], expected_output: DataValue::one_hot(i % 3, 3).unwrap() }).collect();
+		for _ in 0..5 {
+			network.learn(&data, 0.1).unwrap();
+		}
+
+		let inputs: Vec<Vec<f64>> = (0..5).map(|i| vec![0.4 * i as f64, -1.0, 2.0 - i as f64]).collect();
+		let before: Vec<Vec<f64>> = inputs.iter().map(|input| network.predict(input).unwrap()).collect();
+
+		network.fold_batchnorm().unwrap();
+		assert!((0..3).all(|layeridx| network.get_layer(layeridx).unwrap().get_batch_norm().is_none()));
+		assert!(network.get_layer(2).unwrap().get_layer_norm().is_some());
+
+		for (input, before) in inputs.iter().zip(before) {
+			for (a, b) in network.predict(input).unwrap().iter().zip(before) {
+				assert!((a - b).abs() < 1e-10);
+			}
+		}
+	}
+
+	#[test]
+	fn invalid_ada_grad_epsilon() {
+		for epsilon in [0.0, -1e-8, f64::NAN, f64::INFINITY] {
+			let mut network = identity_network(1);
+			assert!(matches!(network.set_optimizer(Optimizer::AdaGrad { epsilon }), Err(NeuralibError::InvalidArgument(_))));
+			assert_eq!(network.get_optimizer(), &Optimizer::SGD);
+
+			let network = NetworkBuilder::new(2).layer(3, Activation::ReLU).optimizer(Optimizer::AdaGrad { epsilon }).build();
+			assert!(matches!(network, Err(NeuralibError::InvalidArgument(_))));
+		}
+
+		let mut network = identity_network(1);
+		assert!(network.set_optimizer(Optimizer::ada_grad_default()).is_ok());
+		assert!(NetworkBuilder::new(2).layer(3, Activation::ReLU).optimizer(Optimizer::ada_grad_default()).build().is_ok());
 	}
 }
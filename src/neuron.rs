@@ -1,7 +1,13 @@
-use rand::prelude::*;
-use rand_distr::StandardNormal;
 use crate::activation::Activation;
-use crate::layer::Layer;
+use crate::optimizer::ParamState;
+
+#[cfg(feature = "training")]
+use {
+    rand::prelude::*,
+    crate::init::Init,
+    crate::loss::Loss,
+    crate::optimizer::{Optimizer, Regularization},
+};
 
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
@@ -10,6 +16,7 @@ use serde::{Serialize, Deserialize};
 pub struct LossGradient {
     pub loss_gradient_weight: Vec<f64>,
     pub loss_gradient_bias: f64,
+    pub loss_gradient_activation_param: f64,
 }
 
 #[derive(Debug, Default)]
@@ -18,34 +25,67 @@ struct DataCache {
     last_bias: f64,
     last_inputs: Vec<f64>,
     last_deriv: f64,
+    // The derivative of the loss with respect to the output (before the activation derivative is applied)
+    last_output_deriv: f64,
+    // What the output was multiplied by for dropout (0 if it was dropped)
+    dropout_scale: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Neuron {
     weights: Vec<f64>,
     bias: f64,
     input_size: usize,
     activation: Activation,
-    #[serde(skip)]
+    // The learnable parameter of the activation function (the slope for PReLU)
+    #[cfg_attr(feature = "serde", serde(default))]
+    activation_param: f64,
+    // Frozen neurons still pass gradients back, but don't update their own parameters
+    #[cfg_attr(feature = "serde", serde(default))]
+    frozen: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
     loss_gradient: LossGradient,
+    // The optimizer state for the weights, then the bias, then the activation parameter. Empty until the first update
+    #[cfg_attr(feature = "serde", serde(skip))]
+    optimizer_state: Vec<ParamState>,
     // Needed for training
-    #[serde(skip)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     cache: DataCache,
 }
 
+impl DataCache {
+    fn new(input_size: usize) -> DataCache {
+        DataCache {last_output: 0.0, last_bias: 0.0, last_inputs: vec![0.0; input_size], last_deriv: 0.0, last_output_deriv: 0.0, dropout_scale: 1.0}
+    }
+}
+
+/// Clones copy the parameters and optimizer state. The accumulated gradients and the training cache start empty
+impl Clone for Neuron {
+    fn clone(&self) -> Neuron {
+        Neuron {
+            optimizer_state: self.optimizer_state.clone(),
+            ..self.without_training_state()
+        }
+    }
+}
+
 impl Neuron {
-    pub fn new(input_size: usize, activation: Activation) -> Neuron {
-        // Initalize weights based off of https://cs231n.github.io/neural-networks-2/#init
-        let divi = (2.0 / (input_size as f64)).sqrt();
-        let weights: Vec<f64> = rand::rng().sample_iter(StandardNormal).take(input_size).map(|x: f64| {x * divi}).collect();
+    /// Create a neuron with weights from `weight_init` and a bias from `bias_init`. `fan_out` is the size of the neuron's layer
+    #[cfg(feature = "training")]
+    pub fn new(input_size: usize, activation: Activation, weight_init: &Init, bias_init: &Init, fan_out: usize, rng: &mut impl Rng) -> Neuron {
+        let weights = weight_init.weights(input_size, fan_out, rng);
         
         Neuron {
             weights,
-            bias: 0.0,
+            bias: bias_init.sample(input_size, fan_out, rng),
             input_size,
+            activation_param: activation.initial_param(),
             activation,
-            loss_gradient: LossGradient {loss_gradient_weight: vec![0.0; input_size], loss_gradient_bias: 0.0},
-            cache: DataCache {last_output: 0.0, last_bias: 0.0, last_inputs: vec![0.0; input_size], last_deriv: 0.0},
+            frozen: false,
+            loss_gradient: LossGradient {loss_gradient_weight: vec![0.0; input_size], loss_gradient_bias: 0.0, loss_gradient_activation_param: 0.0},
+            optimizer_state: Vec::new(),
+            cache: DataCache::new(input_size),
         }
     }
     // TODO: Consider making this into a seperate "activate_for_training" method
@@ -59,8 +99,75 @@ impl Neuron {
             );
         }
 
-        self.cache.last_inputs = inputs.to_vec();
+        let biased = self.weigh(inputs);
+
+        let activated = self.activation.call_with_param(biased, self.activation_param);
+
+        self.cache.last_output = activated;
+        self.cache.dropout_scale = 1.0;
         
+        Ok(activated)
+    }
+
+    /// Calculate the weighted sum (pre-activation) and cache it for training.
+    /// Used by layers that activate all of their neurons jointly (like softmax). The layer then sets the output with `set_output`
+    pub fn weigh(&mut self, inputs: &[f64]) -> f64 {
+        self.cache.last_inputs = inputs.to_vec();
+
+        let biased = self.weighted_sum(inputs);
+
+        self.cache.last_bias = biased;
+
+        biased
+    }
+
+    /// Activate a value that was already weighed with `weigh` and then changed by the layer (like with batch normalization), caching it for training
+    pub fn activate_weighed(&mut self, pre_activation: f64) -> f64 {
+        self.cache.last_bias = pre_activation;
+        self.set_output(self.activation_output(pre_activation));
+        self.cache.last_output
+    }
+
+    /// The activation function (with its parameter) applied to a value
+    pub fn activation_output(&self, pre_activation: f64) -> f64 {
+        self.activation.call_with_param(pre_activation, self.activation_param)
+    }
+
+    /// Cache an output that was calculated by the layer
+    pub fn set_output(&mut self, output: f64) {
+        self.cache.last_output = output;
+        self.cache.dropout_scale = 1.0;
+    }
+
+    /// Record that the layer multiplied the last output by `scale` for dropout, so the gradients are masked the same way
+    #[cfg(feature = "training")]
+    pub fn set_dropout_scale(&mut self, scale: f64) {
+        self.cache.dropout_scale = scale;
+    }
+
+    pub fn get_last_output(&self) -> f64 {
+        self.cache.last_output
+    }
+
+    pub fn get_last_deriv(&self) -> f64 {
+        self.cache.last_deriv
+    }
+
+    /// Run the neuron without touching the training cache
+    pub fn predict(&self, inputs: &[f64]) -> crate::error::Result<f64> {
+        if inputs.len() != self.input_size {
+            return Err(crate::error::InputSizeError {
+                    inputted: inputs.len(),
+                    expected: self.input_size,
+                    chain_depth: "Neuron".to_owned()
+                }.into()
+            );
+        }
+
+        Ok(self.activation.call_with_param(self.weighted_sum(inputs), self.activation_param))
+    }
+
+    pub fn weighted_sum(&self, inputs: &[f64]) -> f64 {
         let weighted: f64 = inputs.iter()
                         // Combine weights and inputs
                         .zip(self.weights.iter())
@@ -69,15 +176,7 @@ impl Neuron {
                         // Sum them up
                         .sum();
         // Add the bias
-        let biased = weighted + self.bias;
-
-        self.cache.last_bias = biased;
-
-        let activated = self.activation.call(biased);
-
-        self.cache.last_output = activated;
-        
-        Ok(activated)
+        weighted + self.bias
     }
 
     #[allow(dead_code)]
@@ -90,39 +189,74 @@ impl Neuron {
         }
     }
 
-    /// Loss function for a single generic neuron
-    pub fn loss(output: &f64, expected_output: &f64) -> f64 {
-    	(output - expected_output).powi(2)
+    /// I couldn't think of a better name for this. It's kind of like the derivative for the whole neuron.
+    /// `output_deriv` is the derivative of the loss with respect to this neuron's output
+    pub fn calculate_deriv(&mut self, output_deriv: f64) {
+        // Dropped outputs didn't affect the loss
+        let output_deriv = output_deriv * self.cache.dropout_scale;
+        let activation_deriv = self.activation.derivative_with_param(self.cache.last_bias, self.activation_param);
+        self.cache.last_output_deriv = output_deriv;
+        // Cache the output for the previous node to use.
+        self.cache.last_deriv = activation_deriv * output_deriv;
     }
 
-    /// The (partial) derivative for the above loss function
-    pub fn deriv_loss(output: &f64, expected_output: &f64) -> f64 {
-    	2.0 * (output - expected_output)
+    /// Replace the derivative with respect to the weighted sum, for layers that mix the weighted sums before activating them (like layer normalization).
+    /// Unlike `set_deriv`, the output derivative is kept for the activation parameter's gradient
+    pub fn set_weighted_deriv(&mut self, deriv: f64) {
+        self.cache.last_deriv = deriv;
     }
 
-    /// I couldn't think of a better name for this. It's kind of like the derivative for the whole neuron (only for output neurons)
-    pub fn calculate_deriv_output(&mut self, expected_output: &f64) {
-        let loss_deriv = Neuron::deriv_loss(&self.cache.last_output, expected_output);
-        let activation_deriv = self.activation.derivative(self.cache.last_bias);
-        let deriv = activation_deriv * loss_deriv;
-        // Cache the output for the previous node to use.
-        self.cache.last_deriv = deriv;
-        //deriv
+    /// The derivative of the activation function (with its parameter) at a value
+    #[cfg(feature = "training")]
+    pub fn activation_deriv(&self, pre_activation: f64) -> f64 {
+        self.activation.derivative_with_param(pre_activation, self.activation_param)
     }
 
-    pub fn calculate_deriv_hidden(&mut self, next_layer: &Layer, self_idx: usize) {
-        let mut deriv = 0.0;
-        for next_neuron_idx in 0..next_layer.get_neuron_count() {
-            let next_neuron = next_layer.get_neuron(next_neuron_idx).expect("Length was already checked. This should not fail. (Neuron)");
-            let next_neuron_deriv = next_neuron.cache.last_deriv;
-            deriv += next_neuron_deriv * next_neuron.weights.get(self_idx).expect("Length was already checked. This should not fail. (Neuron)");            
-        }
-        deriv *= self.activation.derivative(self.cache.last_bias);
+    /// The derivative of the activation function with respect to its parameter at a value
+    #[cfg(feature = "training")]
+    pub fn activation_param_deriv(&self, pre_activation: f64) -> f64 {
+        self.activation.param_derivative(pre_activation)
+    }
+
+    /// The derivative of the loss with respect to this neuron's output (only for output neurons)
+    #[cfg(feature = "training")]
+    pub fn output_deriv_output(&self, expected_output: &f64, loss: &Loss) -> f64 {
+        loss.derivative(self.cache.last_output, *expected_output)
+    }
+
+    /// Set the derivative directly when the layer can calculate it more simply (like softmax with cross-entropy)
+    #[cfg(feature = "training")]
+    pub fn set_deriv(&mut self, deriv: f64) {
         self.cache.last_deriv = deriv;
-        //deriv
+        self.cache.last_output_deriv = 0.0;
+    }
+
+    /// The derivative for softmax neurons. Their output depends on every neuron in the layer, so the layer has to pass in
+    /// `softmax_sum`: the sum of output derivative * output over the whole layer.
+    pub fn calculate_deriv_softmax(&mut self, output_deriv: f64, softmax_sum: f64) {
+        // This is the softmax Jacobian multiplied by the output derivatives
+        self.cache.last_deriv = self.cache.last_output * (output_deriv - softmax_sum);
+        self.cache.last_output_deriv = output_deriv;
+    }
+
+    pub fn get_activation(&self) -> &Activation {
+        &self.activation
+    }
+
+    /// Change the activation function. The activation parameter is reset to the new function's initial value
+    pub fn set_activation(&mut self, activation: Activation) {
+        self.activation_param = activation.initial_param();
+        self.activation = activation;
+    }
+
+    pub fn get_activation_param(&self) -> f64 {
+        self.activation_param
+    }
+
+    pub fn set_activation_param(&mut self, param: f64) {
+        self.activation_param = param;
     }
 
-    #[allow(dead_code)]
     pub fn get_weight(&self, weight_idx: usize) -> Option<&f64> {
         self.weights.get(weight_idx)
     }
@@ -136,7 +270,6 @@ impl Neuron {
         self.get_bias_mut().clone_from(new_bias)
     }
 
-    #[allow(dead_code)]
     pub fn get_bias(&self) -> &f64 {
         &self.bias
     }
@@ -145,29 +278,154 @@ impl Neuron {
         &mut self.bias
     }
 
+    #[cfg(feature = "training")]
+    pub fn get_loss_gradient(&self) -> &LossGradient {
+        &self.loss_gradient
+    }
+
     #[allow(dead_code)]
     pub fn get_loss_gradient_mut(&mut self) -> &mut LossGradient {
         &mut self.loss_gradient
     }
+
+    /// Move this neuron's parameters towards another neuron's: `self = tau * source + (1 - tau) * self`
+    pub fn soft_update_from(&mut self, source: &Neuron, tau: f64) {
+        for (weight, source_weight) in self.weights.iter_mut().zip(&source.weights) {
+            *weight = tau * source_weight + (1.0 - tau) * *weight;
+        }
+        self.bias = tau * source.bias + (1.0 - tau) * self.bias;
+        self.activation_param = tau * source.activation_param + (1.0 - tau) * self.activation_param;
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    #[cfg(feature = "training")]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// The sum of the squares of the accumulated gradients
+    #[cfg(feature = "training")]
+    pub fn gradient_norm_squared(&self) -> f64 {
+        self.loss_gradient.loss_gradient_weight.iter().map(|gradient| gradient * gradient).sum::<f64>()
+            + self.loss_gradient.loss_gradient_bias.powi(2)
+            + self.loss_gradient.loss_gradient_activation_param.powi(2)
+    }
+
+    /// Multiply the accumulated gradients by `scale`
+    #[cfg(feature = "training")]
+    pub fn scale_gradients(&mut self, scale: f64) {
+        self.loss_gradient.loss_gradient_bias *= scale;
+        self.loss_gradient.loss_gradient_activation_param *= scale;
+        self.loss_gradient.loss_gradient_weight.iter_mut().for_each(|gradient| *gradient *= scale);
+    }
+
+    /// Zero the accumulated gradients without applying them
+    pub fn reset_gradients(&mut self) {
+        self.loss_gradient.loss_gradient_bias = 0.0;
+        self.loss_gradient.loss_gradient_activation_param = 0.0;
+        self.loss_gradient.loss_gradient_weight.iter_mut().for_each(|gradient| *gradient = 0.0);
+    }
     
     pub fn get_weight_count(&self) -> usize {
         self.input_size
     }
 
-    pub fn apply_gradients(&mut self, learn_rate: f64) {
+    /// Apply the gradients accumulated over `batch_size` samples with an optimizer, then reset them
+    #[cfg(feature = "training")]
+    pub fn apply_gradients(&mut self, learn_rate: f64, batch_size: usize, optimizer: &Optimizer, regularization: &Regularization) {
+        let scale = 1.0 / batch_size as f64;
+        let weight_count = self.get_weight_count();
+        // The state is skipped when saving, so this also covers loaded networks
+        self.optimizer_state.resize(weight_count + 2, ParamState::default());
+        let (weight_states, other_states) = self.optimizer_state.split_at_mut(weight_count);
+
         // Apply bias gradient
-        self.bias -= self.loss_gradient.loss_gradient_bias * learn_rate;
+        self.bias += optimizer.step(&mut other_states[0], self.loss_gradient.loss_gradient_bias * scale, learn_rate);
         // Reset bias gradient
         self.loss_gradient.loss_gradient_bias = 0.0;
+        // Apply and reset the activation parameter gradient
+        self.activation_param += optimizer.step(&mut other_states[1], self.loss_gradient.loss_gradient_activation_param * scale, learn_rate);
+        self.loss_gradient.loss_gradient_activation_param = 0.0;
         // Apply and reset weight gradients
-        for idx in 0..self.get_weight_count() {
+        for (idx, state) in weight_states.iter_mut().enumerate() {
             // Apply weight gradient
-            self.weights[idx] -= self.loss_gradient.loss_gradient_weight[idx] * learn_rate;
+            let gradient = self.loss_gradient.loss_gradient_weight[idx] * scale + regularization.gradient(self.weights[idx]);
+            self.weights[idx] += optimizer.step(state, gradient, learn_rate);
             // Reset weight gradient
             self.loss_gradient.loss_gradient_weight[idx] = 0.0;
         }
     }
 
+    /// Scale the weights down so their L2 norm is at most `max_norm` (a max-norm constraint). The bias isn't included
+    #[cfg(feature = "training")]
+    pub fn constrain_weight_norm(&mut self, max_norm: f64) {
+        let norm = self.weights.iter().map(|weight| weight * weight).sum::<f64>().sqrt();
+        if norm > max_norm {
+            for weight in &mut self.weights {
+                *weight *= max_norm / norm;
+            }
+        }
+    }
+
+    /// Recreate the gradient and cache buffers, which are skipped when saving
+    #[cfg(feature = "serde")]
+    pub fn restore_training_buffers(&mut self) {
+        self.loss_gradient.loss_gradient_weight = vec![0.0; self.input_size];
+        self.cache.last_inputs = vec![0.0; self.input_size];
+        self.cache.dropout_scale = 1.0;
+    }
+
+    /// A copy with the same parameters but empty gradients, cache, and optimizer state
+    pub fn without_training_state(&self) -> Neuron {
+        Neuron {
+            weights: self.weights.clone(),
+            bias: self.bias,
+            input_size: self.input_size,
+            activation: self.activation.clone(),
+            activation_param: self.activation_param,
+            frozen: self.frozen,
+            loss_gradient: LossGradient {loss_gradient_weight: vec![0.0; self.input_size], loss_gradient_bias: 0.0, loss_gradient_activation_param: 0.0},
+            optimizer_state: Vec::new(),
+            cache: DataCache::new(self.input_size),
+        }
+    }
+
+    /// Add the gradients accumulated by another neuron (usually a copy from `without_training_state`) to this one's
+    #[cfg(feature = "rayon")]
+    pub fn add_gradients_from(&mut self, source: &Neuron) {
+        for (gradient, source_gradient) in self.loss_gradient.loss_gradient_weight.iter_mut().zip(&source.loss_gradient.loss_gradient_weight) {
+            *gradient += source_gradient;
+        }
+        self.loss_gradient.loss_gradient_bias += source.loss_gradient.loss_gradient_bias;
+        self.loss_gradient.loss_gradient_activation_param += source.loss_gradient.loss_gradient_activation_param;
+    }
+
+    /// Zero the accumulated gradients, clear the training cache, and forget the optimizer state
+    pub fn reset_training_state(&mut self) {
+        self.reset_gradients();
+        self.reset_optimizer_state();
+        self.cache = DataCache::new(self.input_size);
+    }
+
+    /// Forget everything the optimizer remembers (like momentum) so training starts fresh
+    pub fn reset_optimizer_state(&mut self) {
+        self.optimizer_state.clear();
+    }
+
+    /// Accumulate the gradients of one sample without the cache. `deriv` is the derivative with respect to the weighted sum of `inputs`
+    #[cfg(feature = "training")]
+    pub fn add_gradients(&mut self, inputs: &[f64], deriv: f64, activation_param_gradient: f64) {
+        for (gradient, input) in self.loss_gradient.loss_gradient_weight.iter_mut().zip(inputs) {
+            *gradient += input * deriv;
+        }
+        self.loss_gradient.loss_gradient_bias += deriv;
+        self.loss_gradient.loss_gradient_activation_param += activation_param_gradient;
+    }
+
+    #[cfg(feature = "training")]
     pub fn update_gradients(&mut self) {
         let neuron_deriv = self.cache.last_deriv;
         for inputidx in 0..self.get_weight_count() {
@@ -176,22 +434,27 @@ impl Neuron {
         }
         // This will be averaged out in the learn function because the learn rate is divided by the batch size
         self.loss_gradient.loss_gradient_bias += neuron_deriv;
+        self.loss_gradient.loss_gradient_activation_param += self.cache.last_output_deriv * self.activation.param_derivative(self.cache.last_bias);
     }
 }
 
 
-#[cfg(test)]
+#[cfg(all(test, feature = "training"))]
 mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn basic_neuron() {
         let mut neuron = Neuron {
             weights: vec![1.0],
             bias: 0.0,
             input_size: 1,
             activation: Activation::Linear,
+            activation_param: 0.0,
             loss_gradient: LossGradient::default(),
+            frozen: false,
+            optimizer_state: Vec::new(),
             cache: DataCache::default(),
         };
 
@@ -205,13 +468,17 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn advanced_neuron() {
         let mut neuron = Neuron {
             weights: vec![2.0, 3.0],
             bias: -1.0,
             input_size: 2,
             activation: Activation::Linear,
+            activation_param: 0.0,
             loss_gradient: LossGradient::default(),
+            frozen: false,
+            optimizer_state: Vec::new(),
             cache: DataCache::default(),
         };
 
@@ -224,13 +491,17 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn size_matching() {
         let mut neuron1 = Neuron {
             weights: vec![1.0],
             bias: 0.0,
             input_size: 1,
             activation: Activation::Linear,
+            activation_param: 0.0,
             loss_gradient: LossGradient::default(),
+            frozen: false,
+            optimizer_state: Vec::new(),
             cache: DataCache::default(),
         };
         let mut neuron2 = Neuron {
@@ -238,7 +509,10 @@ mod tests {
             bias: 0.0,
             input_size: 2,
             activation: Activation::Linear,
+            activation_param: 0.0,
             loss_gradient: LossGradient::default(),
+            frozen: false,
+            optimizer_state: Vec::new(),
             cache: DataCache::default(),
         };
 
@@ -252,7 +526,7 @@ mod tests {
     #[test]
     fn methods() {
         // Test for new method
-        let mut neuron = Neuron::new(2, Activation::Sigmoid);
+        let mut neuron = Neuron::new(2, Activation::Sigmoid, &Init::default(), &Init::Zeros, 1, &mut rand::rng());
 
         for i in -100..=100 {
             // Bias stuff
@@ -274,4 +548,39 @@ mod tests {
 
         assert!(neuron.set_weight(neuron.get_weight_count(), &0.0).is_err());
     }
+
+    #[test]
+    fn p_re_lu() {
+        let mut neuron = Neuron {
+            weights: vec![1.0],
+            bias: 0.0,
+            input_size: 1,
+            activation: Activation::PReLU { initial_slope: 0.25 },
+            activation_param: 0.25,
+            loss_gradient: LossGradient::default(),
+            frozen: false,
+            optimizer_state: Vec::new(),
+            cache: DataCache::default(),
+        };
+        neuron.loss_gradient.loss_gradient_weight = vec![0.0];
+
+        assert_eq!(neuron.activate(&[-2.0]).unwrap(), -0.5);
+
+        // The output should be -1.0, which needs a slope of 0.5
+        neuron.calculate_deriv(neuron.output_deriv_output(&-1.0, &Loss::MSE));
+        neuron.update_gradients();
+        assert_eq!(neuron.get_loss_gradient().loss_gradient_activation_param, 2.0 * (-0.5 + 1.0) * -2.0);
+
+        neuron.reset_gradients();
+        for _ in 0..100 {
+            neuron.activate(&[-2.0]).unwrap();
+            neuron.calculate_deriv(neuron.output_deriv_output(&-1.0, &Loss::MSE));
+            neuron.update_gradients();
+            // Only train the slope
+            neuron.loss_gradient.loss_gradient_weight[0] = 0.0;
+            neuron.loss_gradient.loss_gradient_bias = 0.0;
+            neuron.apply_gradients(0.05, 1, &Optimizer::SGD, &Regularization::default());
+        }
+        assert!((neuron.get_activation_param() - 0.5).abs() < 1e-6);
+    }
 }
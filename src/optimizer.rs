@@ -0,0 +1,347 @@
+//! Optimizers for neuralib
+//!
+//! This module provides the update rules a neural network can use to apply its gradients.
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// The optimizers this library supports
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Optimizer {
+	/// Plain stochastic gradient descent: `param -= learn_rate * gradient`
+	#[default]
+	SGD,
+	/// SGD with momentum: <https://en.wikipedia.org/wiki/Stochastic_gradient_descent#Momentum>
+	///
+	/// Each parameter keeps a velocity: `velocity = momentum * velocity - learn_rate * gradient; param += velocity`.
+	/// `Optimizer::DEFAULT_MOMENTUM` (0.9) is a good default
+	Momentum {
+		momentum: f64
+	},
+	/// SGD with Nesterov momentum: <https://proceedings.mlr.press/v28/sutskever13.html>
+	///
+	/// Like `Momentum`, but the gradient is taken at the position the velocity is about to move the parameter to.
+	/// With the velocity updated like in `Momentum`, the step is `momentum * velocity - learn_rate * gradient`
+	Nesterov {
+		momentum: f64
+	},
+	/// Adam: <https://arxiv.org/abs/1412.6980>
+	///
+	/// Each parameter keeps running averages of its gradient (decay `beta1`) and squared gradient (decay `beta2`),
+	/// and the step is the bias-corrected average gradient divided by the square root of the average squared gradient (plus `epsilon`).
+	/// `Optimizer::adam_default()` uses the values from the paper
+	Adam {
+		beta1: f64,
+		beta2: f64,
+		epsilon: f64,
+	},
+	/// RMSProp: <https://en.wikipedia.org/wiki/Stochastic_gradient_descent#RMSProp>
+	///
+	/// Each parameter keeps a running average of its squared gradient (decay `rho`),
+	/// and the learn rate is divided by the square root of that average (plus `epsilon`).
+	/// `Optimizer::rms_prop_default()` uses rho = 0.9 and epsilon = 1e-8
+	RMSProp {
+		rho: f64,
+		epsilon: f64,
+	},
+	/// AdaGrad: <https://jmlr.org/papers/v12/duchi11a.html>
+	///
+	/// Each parameter keeps the sum of all of its squared gradients, and the learn rate is multiplied by `1 / sqrt(sum + epsilon)`.
+	/// The sum only grows, so parameters that get big gradients often slow down while rarely updated ones (like weights of rare inputs) keep taking big steps.
+	/// `epsilon` has to be positive so parameters that haven't had a gradient yet don't divide 0 by 0.
+	/// `Optimizer::ada_grad_default()` uses epsilon = 1e-8
+	AdaGrad {
+		epsilon: f64,
+	},
+}
+
+/// Penalties on large weights to reduce overfitting. Biases aren't penalized.
+///
+/// The penalties are added to the gradients before the optimizer uses them. All of them are off (0.0) by default
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Regularization {
+	/// L2 regularization (weight decay): `l2 * weight` is added to each weight's gradient
+	pub l2: f64,
+	/// L1 regularization: `l1 * sign(weight)` is added to each weight's gradient. This pushes unimportant weights to 0
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub l1: f64,
+}
+
+impl Regularization {
+	/// The gradient of the penalties for a weight
+	#[cfg(feature = "training")]
+	pub(crate) fn gradient(&self, weight: f64) -> f64 {
+		// signum would give 1 for a weight of 0
+		let sign = if weight == 0.0 { 0.0 } else { weight.signum() };
+		self.l2 * weight + self.l1 * sign
+	}
+}
+
+/// How the learn rate changes over the epochs of training with `NeuralNetwork::fit`
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LrSchedule {
+	/// The learn rate stays the same
+	#[default]
+	Constant,
+	/// Exponential decay: `learn_rate * gamma^epoch`
+	Exponential {
+		gamma: f64
+	},
+	/// Step decay: the learn rate is multiplied by `gamma` every `step_size` epochs
+	Step {
+		step_size: usize,
+		gamma: f64,
+	},
+	/// Cosine annealing: <https://arxiv.org/abs/1608.03983> (without restarts)
+	///
+	/// The learn rate follows half a cosine from the initial learn rate down to `min_learn_rate` over `epochs` epochs, then stays there
+	Cosine {
+		epochs: usize,
+		min_learn_rate: f64,
+	},
+}
+
+impl LrSchedule {
+	/// The learn rate to use for an epoch (counting from 0)
+	///
+	/// Arguments:
+	///
+	/// * `learn_rate` - The initial learn rate
+	/// * `epoch` - The epoch to get the learn rate for
+	pub fn learn_rate(&self, learn_rate: f64, epoch: usize) -> f64 {
+		match self {
+			LrSchedule::Constant => learn_rate,
+			LrSchedule::Exponential { gamma } => learn_rate * gamma.powi(epoch as i32),
+			LrSchedule::Step { step_size, gamma } => learn_rate * gamma.powi((epoch / (*step_size).max(1)) as i32),
+			LrSchedule::Cosine { epochs, min_learn_rate } => {
+				let progress = epoch.min(*epochs) as f64 / (*epochs).max(1) as f64;
+				min_learn_rate + 0.5 * (learn_rate - min_learn_rate) * (1.0 + (std::f64::consts::PI * progress).cos())
+			}
+		}
+	}
+}
+
+/// What an optimizer remembers about a single parameter between updates
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(not(feature = "training"), allow(dead_code))]
+pub(crate) struct ParamState {
+	// The velocity for momentum, or the average gradient for Adam
+	first_moment: f64,
+	// The average squared gradient for Adam and RMSProp, or the sum of the squared gradients for AdaGrad
+	second_moment: f64,
+	steps: i32,
+}
+
+impl Optimizer {
+	/// The usual momentum for `Optimizer::Momentum`
+	pub const DEFAULT_MOMENTUM: f64 = 0.9;
+
+	/// Momentum with the default momentum
+	pub const fn momentum_default() -> Optimizer {
+		Optimizer::Momentum { momentum: Optimizer::DEFAULT_MOMENTUM }
+	}
+
+	/// Nesterov momentum with the default momentum
+	pub const fn nesterov_default() -> Optimizer {
+		Optimizer::Nesterov { momentum: Optimizer::DEFAULT_MOMENTUM }
+	}
+
+	/// Adam with the values from the paper: beta1 = 0.9, beta2 = 0.999, epsilon = 1e-8
+	pub const fn adam_default() -> Optimizer {
+		Optimizer::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 }
+	}
+
+	/// RMSProp with rho = 0.9 and epsilon = 1e-8
+	pub const fn rms_prop_default() -> Optimizer {
+		Optimizer::RMSProp { rho: 0.9, epsilon: 1e-8 }
+	}
+
+	/// AdaGrad with epsilon = 1e-8
+	pub const fn ada_grad_default() -> Optimizer {
+		Optimizer::AdaGrad { epsilon: 1e-8 }
+	}
+
+	/// Check that the optimizer's settings can be used, returning an `InvalidArgumentError` if they can't
+	pub(crate) fn validate(&self) -> crate::error::Result<()> {
+		if let Optimizer::AdaGrad { epsilon } = self && !(epsilon.is_finite() && *epsilon > 0.0) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "optimizer".to_owned(),
+					reason: format!("AdaGrad's epsilon must be finite and positive, not {epsilon}")
+				}.into()
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Calculate how much to change a parameter by, updating its state
+	#[cfg(feature = "training")]
+	pub(crate) fn step(&self, state: &mut ParamState, gradient: f64, learn_rate: f64) -> f64 {
+		match self {
+			Optimizer::SGD => -learn_rate * gradient,
+			Optimizer::Momentum{momentum} => {
+				state.first_moment = momentum * state.first_moment - learn_rate * gradient;
+				state.first_moment
+			},
+			Optimizer::Nesterov{momentum} => {
+				// Looking ahead by the new velocity is the same as adding it to a plain gradient step
+				state.first_moment = momentum * state.first_moment - learn_rate * gradient;
+				momentum * state.first_moment - learn_rate * gradient
+			},
+			Optimizer::Adam{beta1, beta2, epsilon} => {
+				state.first_moment = beta1 * state.first_moment + (1.0 - beta1) * gradient;
+				state.second_moment = beta2 * state.second_moment + (1.0 - beta2) * gradient * gradient;
+				state.steps = state.steps.saturating_add(1);
+
+				// The averages start at 0, so they are scaled up while they warm up
+				let first_moment = state.first_moment / (1.0 - beta1.powi(state.steps));
+				let second_moment = state.second_moment / (1.0 - beta2.powi(state.steps));
+				-learn_rate * first_moment / (second_moment.sqrt() + epsilon)
+			},
+			Optimizer::RMSProp{rho, epsilon} => {
+				state.second_moment = rho * state.second_moment + (1.0 - rho) * gradient * gradient;
+				-learn_rate * gradient / (state.second_moment.sqrt() + epsilon)
+			},
+			Optimizer::AdaGrad{epsilon} => {
+				state.second_moment += gradient * gradient;
+				-learn_rate * gradient / (state.second_moment + epsilon).sqrt()
+			},
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn regularization() {
+		assert_eq!(Regularization::default().gradient(3.0), 0.0);
+		assert_eq!(Regularization { l2: 0.5, l1: 0.0 }.gradient(3.0), 1.5);
+		assert_eq!(Regularization { l2: 0.5, l1: 0.0 }.gradient(-3.0), -1.5);
+
+		assert_eq!(Regularization { l2: 0.0, l1: 0.5 }.gradient(3.0), 0.5);
+		assert_eq!(Regularization { l2: 0.0, l1: 0.5 }.gradient(-3.0), -0.5);
+		assert_eq!(Regularization { l2: 0.0, l1: 0.5 }.gradient(0.0), 0.0);
+		assert_eq!(Regularization { l2: 0.5, l1: 0.5 }.gradient(3.0), 2.0);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn sgd() {
+		let mut state = ParamState::default();
+
+		assert_eq!(Optimizer::SGD.step(&mut state, 2.0, 0.5), -1.0);
+		assert_eq!(Optimizer::SGD.step(&mut state, 2.0, 0.5), -1.0);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn momentum() {
+		let optimizer = Optimizer::Momentum { momentum: 0.5 };
+		let mut state = ParamState::default();
+
+		assert_eq!(optimizer.step(&mut state, 2.0, 0.5), -1.0);
+		// The velocity builds up over steps
+		assert_eq!(optimizer.step(&mut state, 2.0, 0.5), -1.5);
+		assert_eq!(optimizer.step(&mut state, 0.0, 0.5), -0.75);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn nesterov() {
+		let optimizer = Optimizer::Nesterov { momentum: 0.5 };
+		let mut state = ParamState::default();
+		let mut momentum_state = ParamState::default();
+
+		// The velocity is -1, and the step adds half of it to the gradient step
+		assert_eq!(optimizer.step(&mut state, 2.0, 0.5), -1.5);
+		assert_eq!(Optimizer::Momentum { momentum: 0.5 }.step(&mut momentum_state, 2.0, 0.5), -1.0);
+		// The velocity is -1.5
+		assert_eq!(optimizer.step(&mut state, 2.0, 0.5), -1.75);
+		// Without a gradient it coasts on half of the velocity of -0.75
+		assert_eq!(optimizer.step(&mut state, 0.0, 0.5), -0.375);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn adam() {
+		let optimizer = Optimizer::adam_default();
+		let mut state = ParamState::default();
+
+		// With bias correction, the first steps are about the learn rate no matter how big the gradient is
+		assert!((optimizer.step(&mut state, 1000.0, 0.1) + 0.1).abs() < 1e-6);
+		assert!((optimizer.step(&mut state, 1000.0, 0.1) + 0.1).abs() < 1e-6);
+		let mut state = ParamState::default();
+		assert!((optimizer.step(&mut state, -0.001, 0.1) - 0.1).abs() < 1e-4);
+
+		// A gradient that changes sign slows the steps down
+		let step = optimizer.step(&mut state, 0.001, 0.1);
+		assert!(step.abs() < 0.1);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn rms_prop() {
+		let optimizer = Optimizer::RMSProp { rho: 0.75, epsilon: 0.0 };
+		let mut state = ParamState::default();
+
+		// The average squared gradient is 0.25 * 4, so the step is 0.5 * 2 / 1
+		assert_eq!(optimizer.step(&mut state, 2.0, 0.5), -1.0);
+		// 0.75 * 1 + 0.25 * 4 = 1.75
+		assert!((optimizer.step(&mut state, 2.0, 0.5) + 1.0 / 1.75_f64.sqrt()).abs() < 1e-12);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn ada_grad() {
+		let optimizer = Optimizer::ada_grad_default();
+		let mut frequent = ParamState::default();
+		let mut rare = ParamState::default();
+
+		// The first step is about the learn rate times the sign of the gradient
+		assert!((optimizer.step(&mut frequent, 2.0, 0.5) + 0.5).abs() < 1e-8);
+		// The sum of squares is 8, so the step is about 0.5 * 2 / sqrt(8)
+		assert!((optimizer.step(&mut frequent, 2.0, 0.5) + 1.0 / 8.0_f64.sqrt()).abs() < 1e-8);
+		for _ in 0..10 {
+			optimizer.step(&mut frequent, 2.0, 0.5);
+		}
+
+		// A parameter that rarely gets a gradient keeps a bigger learn rate than one that always does
+		let rare_step = optimizer.step(&mut rare, 2.0, 0.5);
+		let frequent_step = optimizer.step(&mut frequent, 2.0, 0.5);
+		assert!((rare_step + 0.5).abs() < 1e-8);
+		assert!(frequent_step.abs() < rare_step.abs() / 3.0);
+
+		// Epsilon keeps the first step finite for tiny gradients
+		let mut state = ParamState::default();
+		assert!(Optimizer::ada_grad_default().step(&mut state, 1e-12, 0.5).is_finite());
+	}
+
+	#[test]
+	fn lr_schedule() {
+		assert_eq!(LrSchedule::Constant.learn_rate(0.5, 10), 0.5);
+
+		let exponential = LrSchedule::Exponential { gamma: 0.5 };
+		assert_eq!(exponential.learn_rate(0.8, 0), 0.8);
+		assert_eq!(exponential.learn_rate(0.8, 1), 0.4);
+		assert_eq!(exponential.learn_rate(0.8, 3), 0.1);
+
+		let step = LrSchedule::Step { step_size: 3, gamma: 0.1 };
+		assert_eq!(step.learn_rate(1.0, 0), 1.0);
+		assert_eq!(step.learn_rate(1.0, 2), 1.0);
+		assert!((step.learn_rate(1.0, 3) - 0.1).abs() < 1e-12);
+		assert!((step.learn_rate(1.0, 6) - 0.01).abs() < 1e-12);
+
+		let cosine = LrSchedule::Cosine { epochs: 10, min_learn_rate: 0.1 };
+		assert_eq!(cosine.learn_rate(1.0, 0), 1.0);
+		assert!((cosine.learn_rate(1.0, 5) - 0.55).abs() < 1e-12);
+		assert!((cosine.learn_rate(1.0, 10) - 0.1).abs() < 1e-12);
+		assert!((cosine.learn_rate(1.0, 20) - 0.1).abs() < 1e-12);
+	}
+}
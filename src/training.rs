@@ -1,3 +1,96 @@
+use crate::optimizer::LrSchedule;
+
+#[cfg(feature = "training")]
+use rand::Rng;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// How to train a network with `NeuralNetwork::fit`. It is also a record of how a network was trained: it can be attached to a network and is saved with it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrainConfig {
+	/// How fast the network learned
+	pub learn_rate: f64,
+	/// How many epochs the network was trained for
+	pub epochs: usize,
+	/// The seed used for training, if there was one
+	pub seed: Option<u64>,
+	/// How many DataValues are in each batch. `None` trains on all of the data as one batch
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub batch_size: Option<usize>,
+	/// How the learn rate changes from epoch to epoch
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub lr_schedule: LrSchedule,
+}
+
+/// The losses recorded by `NeuralNetwork::fit` after every epoch
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainHistory {
+	/// The average loss on the training data after each epoch
+	pub train_loss: Vec<f64>,
+	/// The average loss on the validation data after each epoch. Empty if there was no validation data
+	pub validation_loss: Vec<f64>,
+}
+
+/// What `NeuralNetwork::fit_with_callback` passes to its callback after every epoch
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochInfo {
+	/// The index of the epoch that just finished, starting at 0
+	pub epoch: usize,
+	/// How many epochs the training run has in total
+	pub epochs: usize,
+	/// The average loss on the training data after this epoch
+	pub train_loss: f64,
+	/// The average loss on the validation data after this epoch, if there is validation data
+	pub validation_loss: Option<f64>,
+}
+
+/// Metrics for regression tasks from `NeuralNetwork::regression_metrics`, computed across every output of every DataValue
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegressionMetrics {
+	/// The mean absolute error
+	pub mae: f64,
+	/// The root mean squared error
+	pub rmse: f64,
+	/// The coefficient of determination. 1 is a perfect fit and 0 is no better than predicting each output's mean
+	pub r_squared: f64,
+}
+
+/// The transform from `DataValue::scale_outputs`. Each output was mapped linearly from its range in the data to `[min, max]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputScaling {
+	/// The smallest value of each output in the data
+	pub data_mins: Vec<f64>,
+	/// The biggest value of each output in the data
+	pub data_maxs: Vec<f64>,
+	/// The value the smallest outputs were mapped to
+	pub min: f64,
+	/// The value the biggest outputs were mapped to
+	pub max: f64,
+}
+
+impl OutputScaling {
+	/// Scale outputs the same way the data was scaled (like the expected outputs of test data)
+	pub fn scale(&self, outputs: &[f64]) -> Vec<f64> {
+		outputs.iter().zip(self.data_mins.iter().zip(&self.data_maxs))
+			.map(|(output, (data_min, data_max))| if data_max == data_min {
+				// Constant outputs go to the middle of the range
+				(self.min + self.max) / 2.0
+			} else {
+				self.min + (output - data_min) / (data_max - data_min) * (self.max - self.min)
+			})
+			.collect()
+	}
+
+	/// Undo the scaling, turning the network's predictions back into the original units
+	pub fn unscale(&self, outputs: &[f64]) -> Vec<f64> {
+		outputs.iter().zip(self.data_mins.iter().zip(&self.data_maxs))
+			.map(|(output, (data_min, data_max))| data_min + (output - self.min) / (self.max - self.min) * (data_max - data_min))
+			.collect()
+	}
+}
+
 /// A struct to store data for the neural network
 #[derive(Debug, Clone)]
 pub struct DataValue {
@@ -8,62 +101,887 @@ pub struct DataValue {
 }
 
 impl DataValue {
+	/// Create a one-hot vector: all zeros except for a 1 at the index of the label
+	///
+	/// Arguments:
+	///
+	/// * `label` - The index of the class
+	/// * `num_classes` - The length of the vector
+	pub fn one_hot(label: usize, num_classes: usize) -> crate::error::Result<Vec<f64>> {
+		if label >= num_classes {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "label".to_owned(),
+					reason: format!("it must be less than the number of classes ({num_classes})")
+				}.into()
+			);
+		}
+
+		let mut one_hot = vec![0.0; num_classes];
+		one_hot[label] = 1.0;
+		Ok(one_hot)
+	}
+
+	/// Create a DataValue for classification, with a one-hot expected output
+	///
+	/// Arguments:
+	///
+	/// * `input` - The input value for the neural network
+	/// * `label` - The index of the expected class
+	/// * `num_classes` - The number of classes (the length of the expected output)
+	pub fn from_label(input: Vec<f64>, label: usize, num_classes: usize) -> crate::error::Result<DataValue> {
+		Ok(DataValue {
+			input,
+			expected_output: DataValue::one_hot(label, num_classes)?,
+		})
+	}
+
+	/// Scale every input from [min, max] to [0, 1] (like pixel values from 0 to 255).
+	/// Values outside of [min, max] end up outside of [0, 1].
+	///
+	/// Arguments:
+	///
+	/// * `data` - The data to scale in place
+	/// * `min` - The value that becomes 0
+	/// * `max` - The value that becomes 1
+	pub fn normalize_inputs(data: &mut [DataValue], min: f64, max: f64) -> crate::error::Result<()> {
+		if max <= min {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "max".to_owned(),
+					reason: "it must be greater than min".to_owned()
+				}.into()
+			);
+		}
+
+		for value in data {
+			value.input.iter_mut().for_each(|x| *x = (*x - min) / (max - min));
+		}
+
+		Ok(())
+	}
+
+	/// Scale every expected output linearly from its range in the data to [min, max], so bounded output activations can reach the targets
+	/// (like [-1, 1] for HyperTan or [0, 1] for Sigmoid). Outputs that are the same everywhere go to the middle of the range.
+	///
+	/// Returns the transform, which can turn the network's predictions back into the original units with `OutputScaling::unscale`.
+	///
+	/// Arguments:
+	///
+	/// * `data` - The data to scale in place. Every expected output must have the same length
+	/// * `min` - The value the smallest value of each output becomes
+	/// * `max` - The value the biggest value of each output becomes
+	pub fn scale_outputs(data: &mut [DataValue], min: f64, max: f64) -> crate::error::Result<OutputScaling> {
+		if max <= min {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "max".to_owned(),
+					reason: "it must be greater than min".to_owned()
+				}.into()
+			);
+		}
+
+		let outputs = data.first().map_or(0, |value| value.expected_output.len());
+		if let Some(value) = data.iter().find(|value| value.expected_output.len() != outputs) {
+			return Err(crate::error::InputSizeError {
+					inputted: value.expected_output.len(),
+					expected: outputs,
+					chain_depth: "scale_outputs".to_owned()
+				}.into()
+			);
+		}
+
+		let mut scaling = OutputScaling { data_mins: vec![f64::INFINITY; outputs], data_maxs: vec![f64::NEG_INFINITY; outputs], min, max };
+		for value in data.iter() {
+			for ((data_min, data_max), output) in scaling.data_mins.iter_mut().zip(&mut scaling.data_maxs).zip(&value.expected_output) {
+				*data_min = data_min.min(*output);
+				*data_max = data_max.max(*output);
+			}
+		}
+
+		for value in data {
+			value.expected_output = scaling.scale(&value.expected_output);
+		}
+
+		Ok(scaling)
+	}
+
+	/// Standardize every input feature to a mean of 0 and a standard deviation of 1, using statistics from the whole dataset.
+	/// Features with no variance are only centered.
+	///
+	/// Returns the means and standard deviations of each feature, so the same transform can be applied to other data (like test data) with `apply_standardization`.
+	///
+	/// Arguments:
+	///
+	/// * `data` - The data to standardize in place. Every input must have the same length
+	pub fn standardize_inputs(data: &mut [DataValue]) -> crate::error::Result<(Vec<f64>, Vec<f64>)> {
+		let features = data.first().map_or(0, |value| value.input.len());
+		if let Some(value) = data.iter().find(|value| value.input.len() != features) {
+			return Err(crate::error::InputSizeError {
+					inputted: value.input.len(),
+					expected: features,
+					chain_depth: "standardize_inputs".to_owned()
+				}.into()
+			);
+		}
+
+		let mut means = vec![0.0; features];
+		for value in data.iter() {
+			for (mean, x) in means.iter_mut().zip(&value.input) {
+				*mean += x / data.len() as f64;
+			}
+		}
+
+		let mut stds = vec![0.0; features];
+		for value in data.iter() {
+			for ((std, mean), x) in stds.iter_mut().zip(&means).zip(&value.input) {
+				*std += (x - mean).powi(2) / data.len() as f64;
+			}
+		}
+		stds.iter_mut().for_each(|std| *std = std.sqrt());
+
+		DataValue::apply_standardization(data, &means, &stds)?;
+
+		Ok((means, stds))
+	}
+
+	/// Standardize inputs with the means and standard deviations returned by `standardize_inputs`.
+	/// Features with a standard deviation of 0 are only centered.
+	///
+	/// Arguments:
+	///
+	/// * `data` - The data to standardize in place
+	/// * `means` - The mean of each feature
+	/// * `stds` - The standard deviation of each feature
+	pub fn apply_standardization(data: &mut [DataValue], means: &[f64], stds: &[f64]) -> crate::error::Result<()> {
+		if means.len() != stds.len() {
+			return Err(crate::error::InputSizeError {
+					inputted: stds.len(),
+					expected: means.len(),
+					chain_depth: "apply_standardization".to_owned()
+				}.into()
+			);
+		}
+
+		for value in data {
+			if value.input.len() != means.len() {
+				return Err(crate::error::InputSizeError {
+						inputted: value.input.len(),
+						expected: means.len(),
+						chain_depth: "apply_standardization".to_owned()
+					}.into()
+				);
+			}
+
+			for ((x, mean), std) in value.input.iter_mut().zip(means).zip(stds) {
+				*x -= mean;
+				if *std != 0.0 {
+					*x /= std;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Shuffle a dataset and split it into training and test data. Returns `(train, test)`.
+	///
+	/// The test data has `test_fraction` of the samples (rounded to the nearest whole sample).
+	///
+	/// Arguments:
+	///
+	/// * `data` - The dataset to split
+	/// * `test_fraction` - The fraction of the data to use for testing, between 0 and 1 (exclusive)
+	/// * `seed` - A seed to make the shuffle reproducible. If it is `None`, the shuffle is random
+	#[cfg(feature = "training")]
+	pub fn train_test_split(mut data: Vec<DataValue>, test_fraction: f64, seed: Option<u64>) -> crate::error::Result<(Vec<DataValue>, Vec<DataValue>)> {
+		use rand::{SeedableRng, seq::SliceRandom};
+
+		if !(test_fraction > 0.0 && test_fraction < 1.0) {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "test_fraction".to_owned(),
+					reason: "it must be between 0 and 1".to_owned()
+				}.into()
+			);
+		}
+
+		match seed {
+			Some(seed) => data.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed)),
+			None => data.shuffle(&mut rand::rng()),
+		}
+
+		let test_size = (data.len() as f64 * test_fraction).round() as usize;
+		let test = data.split_off(data.len() - test_size);
+
+		Ok((data, test))
+	}
+
 	#[cfg(feature = "idx")]
 	/// Create a Vec<DataValue> from 2 idx files. One for the inputs, and one for the labels.
 	///
 	/// Expectations:
-	///		The data is stored as vec of the MSI (Most significant index) in the idx. The rest will be flattened
-	/// 	The flattened label vec will have the same length as the data one.
+	///
+	/// * The data is stored as vec of the MSI (Most significant index) in the idx. The rest will be flattened
+	/// * The flattened label vec will have the same length as the data one. If it doesn't, an error is returned
+	///
+	/// Each expected output is one-hot, with a length of the largest label + 1.
 	///
 	/// Arguments:
-	///	* `input_idx` - The IDX file for the input data
-	///	* `label_idx` - The IDX file for the labels
+	///
+	/// * `input_idx` - The IDX file for the input data
+	/// * `label_idx` - The IDX file for the labels
 	/// * `normalize` - An optional f64 to normalize the values by
-	pub fn from_data_label_idx(input_idx: &mut (impl std::io::Read + std::io::Seek), label_idx: &mut (impl std::io::Read + std::io::Seek), normalize: Option<f64>) -> crate::error::Result<Vec<DataValue>> {
+	/// * `limit` - An optional maximum number of samples to return (the first ones are kept). Inputs after the limit aren't read
+	pub fn from_data_label_idx(input_idx: &mut (impl std::io::Read + std::io::Seek), label_idx: &mut (impl std::io::Read + std::io::Seek), normalize: Option<f64>, limit: Option<usize>) -> crate::error::Result<Vec<DataValue>> {
 		use idx_lib::*;
 
+		let invalid_label = || crate::error::InvalidArgumentError {
+			argument: "label_idx".to_owned(),
+			reason: "labels must be non-negative whole numbers".to_owned()
+		};
+
 		// Fun chained iterator shenanigans
-		let data = read_idx(input_idx)?;
-		let labels: Vec<usize> = read_idx(label_idx)?
-			// Just here to assert that labels is 1d
+		let (data, samples) = read_idx_limited(input_idx, limit).map_err(|err| idx_error("input_idx", err))?;
+		let labels: Vec<usize> = read_idx(label_idx).map_err(|err| idx_error("label_idx", err))?
+			// Labels are flattened, so they can be stored in any shape
 			.flatten()
-			// Convert to vec
-			.to_vec()
-			// Convert to f64 and back 
-			// (f64 is the only thing we can always 100% convert to. Now we convert them back)
 			.iter()
-			.map(|x| x.cast_as::<f64>().unwrap() as usize)
-			.collect();
+			// Convert to f64 and back
+			// (f64 is the only thing we can always 100% convert to. Now we convert them back)
+			.map(|x| match x.cast_as::<f64>() {
+				Some(label) if label >= 0.0 && label.fract() == 0.0 => Ok(label as usize),
+				_ => Err(invalid_label()),
+			})
+			.collect::<Result<_, _>>()?;
 
-		let output_length = *labels.iter().max().unwrap();
-		let out_vec = vec![0.0; output_length+1];
+		if samples != labels.len() {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "label_idx".to_owned(),
+					reason: format!("it has {} labels, but there are {samples} inputs", labels.len())
+				}.into()
+			);
+		}
 
-		let data_parsed = data
+		// The one-hot length still comes from every label, so it doesn't depend on the limit
+		let Some(max_label) = labels.iter().max() else {
+			return Ok(Vec::new());
+		};
+		let num_classes = max_label + 1;
+		let normalize = normalize.unwrap_or(1.0);
+
+		data
 			// Iterate through all of the actual data values
 			.outer_iter()
-			// Flatten each of them (to prep them to be inputs) and convert to a Vec
-			.map(|x| x.flatten().to_vec())
-			// Convert to f64s
-			.map(|x: Vec<_>| x.iter().map(|y| y.cast_as::<f64>().unwrap() / match normalize {
-				None => 1.0,
-				Some(val) => val
-			}).collect::<Vec<_>>())
 			// Combine them with the labels
 			.zip(labels.iter())
 			// Convert to DataValues
-			.map(|(ip, lab)| {
-				let mut out =  out_vec.clone();
-				out[*lab] = 1.0;
-				DataValue {
-					input: ip,
-					expected_output: out
-				}
+			.map(|(input, label)| {
+				// Flatten each input (to prep it to be an input) and convert it to f64s
+				let input = input.iter()
+					.map(|x| x.cast_as::<f64>().map(|x| x / normalize))
+					.collect::<Option<Vec<f64>>>()
+					.ok_or_else(|| crate::error::InvalidArgumentError {
+						argument: "input_idx".to_owned(),
+						reason: "it has missing values".to_owned()
+					})?;
+
+				DataValue::from_label(input, *label, num_classes)
 			})
-			.collect::<Vec<_>>();
+			.collect()
+	}
+
+	#[cfg(feature = "csv")]
+	/// Create a Vec<DataValue> from CSV data, with one DataValue per row.
+	///
+	/// Every used column has to be a number. Columns that aren't listed are ignored.
+	///
+	/// Arguments:
+	///
+	/// * `reader` - The CSV data
+	/// * `input_cols` - The indices of the columns to use as the input, in order
+	/// * `output_cols` - The indices of the columns to use as the expected output, in order
+	/// * `has_headers` - Whether the first row is a header that should be skipped
+	pub fn from_csv(reader: impl std::io::Read, input_cols: &[usize], output_cols: &[usize], has_headers: bool) -> crate::error::Result<Vec<DataValue>> {
+		let mut reader = csv::ReaderBuilder::new()
+			.has_headers(has_headers)
+			.from_reader(reader);
+
+		let mut data = Vec::new();
+		for (row, record) in reader.records().enumerate() {
+			let record = record?;
+
+			let column = |col: &usize| -> crate::error::Result<f64> {
+				let Some(field) = record.get(*col) else {
+					return Err(crate::error::InvalidArgumentError {
+							argument: "reader".to_owned(),
+							reason: format!("row {row} has no column {col}")
+						}.into()
+					);
+				};
+
+				field.trim().parse().map_err(|_| crate::error::InvalidArgumentError {
+						argument: "reader".to_owned(),
+						reason: format!("column {col} of row {row} isn't a number: {field:?}")
+					}.into()
+				)
+			};
+
+			data.push(DataValue {
+				input: input_cols.iter().map(column).collect::<crate::error::Result<_>>()?,
+				expected_output: output_cols.iter().map(column).collect::<crate::error::Result<_>>()?,
+			});
+		}
+
+		Ok(data)
+	}
+}
+
+/// A dataset that can be split into shuffled mini-batches without copying the DataValues
+#[derive(Debug, Clone, Default)]
+pub struct DataSet {
+	data: Vec<DataValue>,
+}
+
+impl DataSet {
+	/// Create a dataset from a Vec of DataValues
+	pub fn new(data: Vec<DataValue>) -> DataSet {
+		DataSet { data }
+	}
+
+	/// How many DataValues are in the dataset
+	pub fn len(&self) -> usize {
+		self.data.len()
+	}
+
+	/// Whether the dataset has no DataValues
+	pub fn is_empty(&self) -> bool {
+		self.data.is_empty()
+	}
+
+	/// The DataValues in their current order
+	pub fn values(&self) -> &[DataValue] {
+		&self.data
+	}
+
+	/// Take the DataValues back out of the dataset
+	pub fn into_values(self) -> Vec<DataValue> {
+		self.data
+	}
+
+	/// Shuffle the dataset in place and iterate over it in batches. One pass over the iterator is one epoch:
+	/// every DataValue is in exactly one batch. The last batch is smaller if `batch_size` doesn't divide the length
+	///
+	/// Arguments:
+	///
+	/// * `batch_size` - How many DataValues are in each batch
+	/// * `seed` - A seed to make the shuffle reproducible. If it is `None`, the shuffle is random
+	#[cfg(feature = "training")]
+	pub fn batches(&mut self, batch_size: usize, seed: Option<u64>) -> crate::error::Result<impl Iterator<Item = &[DataValue]>> {
+		use rand::{SeedableRng, seq::SliceRandom};
+
+		if batch_size == 0 {
+			return Err(crate::error::InvalidArgumentError {
+					argument: "batch_size".to_owned(),
+					reason: "batches need at least one DataValue".to_owned()
+				}.into()
+			);
+		}
+
+		match seed {
+			Some(seed) => self.data.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed)),
+			None => self.data.shuffle(&mut rand::rng()),
+		}
+
+		Ok(self.data.chunks(batch_size))
+	}
+}
+
+impl From<Vec<DataValue>> for DataSet {
+	fn from(data: Vec<DataValue>) -> DataSet {
+		DataSet::new(data)
+	}
+}
+
+/// Balance a dataset by duplicating samples of the smaller classes until every class has as many samples as the largest one.
+/// The class of a DataValue is the index of the largest value in its expected output.
+///
+/// The extra samples are picked randomly (with replacement) and appended after the original data, so shuffle the result if the order matters.
+/// Every sample is cloned, so this uses (number of classes) * (size of the largest class) DataValues of memory.
+///
+/// Arguments:
+///
+/// * `data` - The dataset to balance
+/// * `rng` - The random number generator used to pick which samples to duplicate
+#[cfg(feature = "training")]
+pub fn oversample(data: &[DataValue], rng: &mut impl Rng) -> Vec<DataValue> {
+	let mut classes: Vec<Vec<&DataValue>> = Vec::new();
+	for value in data {
+		let class = crate::network::argmax(&value.expected_output);
+		if class >= classes.len() {
+			classes.resize(class + 1, Vec::new());
+		}
+		classes[class].push(value);
+	}
+
+	let majority = classes.iter().map(Vec::len).max().unwrap_or(0);
+
+	let mut balanced = data.to_vec();
+	for class in classes.iter().filter(|class| !class.is_empty()) {
+		for _ in class.len()..majority {
+			balanced.push(class[rng.random_range(0..class.len())].clone());
+		}
+	}
+
+	balanced
+}
+
+/// Project data (usually a layer's activations) onto its top principal components (PCA).
+/// This is useful for plotting learned representations in 2D.
+///
+/// Returns one Vec of length `dims` per row of `activations`, in the same order.
+///
+/// Arguments:
+///
+/// * `activations` - The data to project. Every row must have the same length
+/// * `dims` - How many principal components to project onto
+pub fn pca_project(activations: &[Vec<f64>], dims: usize) -> crate::error::Result<Vec<Vec<f64>>> {
+	let Some(first) = activations.first() else {
+		return Err(crate::error::InvalidArgumentError {
+				argument: "activations".to_owned(),
+				reason: "at least one row is needed".to_owned()
+			}.into()
+		);
+	};
+	let features = first.len();
+
+	if dims > features {
+		return Err(crate::error::InvalidArgumentError {
+				argument: "dims".to_owned(),
+				reason: format!("can't project {features} features onto {dims} components")
+			}.into()
+		);
+	}
+
+	if let Some(row) = activations.iter().find(|row| row.len() != features) {
+		return Err(crate::error::InputSizeError {
+				inputted: row.len(),
+				expected: features,
+				chain_depth: "pca_project".to_owned()
+			}.into()
+		);
+	}
+
+	let centered = center(activations);
+	let (_, components) = principal_components(&centered);
+
+	Ok(centered.iter()
+		.map(|row| components[..dims].iter()
+			.map(|component| component.iter().zip(row).map(|(a, b)| a * b).sum())
+			.collect())
+		.collect())
+}
+
+/// Subtract the mean of every column
+pub(crate) fn center(rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+	let features = rows.first().map_or(0, Vec::len);
+	let mut means = vec![0.0; features];
+	for row in rows {
+		for (mean, value) in means.iter_mut().zip(row) {
+			*mean += value / rows.len() as f64;
+		}
+	}
+
+	rows.iter()
+		.map(|row| row.iter().zip(&means).map(|(value, mean)| value - mean).collect())
+		.collect()
+}
+
+/// Calculate the eigenvalues and eigenvectors of the covariance matrix of centered data, sorted by eigenvalue (largest first).
+/// For data that isn't centered, this is the second moment matrix instead
+pub(crate) fn principal_components(centered: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+	let features = centered.first().map_or(0, Vec::len);
+
+	let mut covariance = vec![vec![0.0; features]; features];
+	for row in centered {
+		for i in 0..features {
+			for j in 0..features {
+				covariance[i][j] += row[i] * row[j] / centered.len() as f64;
+			}
+		}
+	}
+
+	let (values, vectors) = symmetric_eigen(covariance);
+
+	let mut order: Vec<usize> = (0..features).collect();
+	order.sort_by(|a, b| values[*b].total_cmp(&values[*a]));
+
+	(
+		order.iter().map(|idx| values[*idx]).collect(),
+		order.iter().map(|idx| vectors.iter().map(|row| row[*idx]).collect()).collect(),
+	)
+}
+
+/// Calculate the eigenvalues and eigenvectors (as columns) of a symmetric matrix using the Jacobi eigenvalue algorithm:
+/// <https://en.wikipedia.org/wiki/Jacobi_eigenvalue_algorithm>
+fn symmetric_eigen(mut matrix: Vec<Vec<f64>>) -> (Vec<f64>, Vec<Vec<f64>>) {
+	let size = matrix.len();
+	let mut vectors: Vec<Vec<f64>> = (0..size).map(|i| (0..size).map(|j| if i == j {1.0} else {0.0}).collect()).collect();
+
+	for _ in 0..100 {
+		let off_diagonal: f64 = (0..size).flat_map(|i| (0..size).filter(move |j| *j != i).map(move |j| (i, j)))
+			.map(|(i, j)| matrix[i][j].powi(2))
+			.sum();
+		if off_diagonal < 1e-22 {
+			break;
+		}
+
+		for p in 0..size {
+			for q in (p + 1)..size {
+				if matrix[p][q] == 0.0 {
+					continue;
+				}
+
+				// Rotate rows/columns p and q so matrix[p][q] becomes 0
+				let theta = (matrix[q][q] - matrix[p][p]) / (2.0 * matrix[p][q]);
+				// signum is 1 for theta = 0, which gives t = 1 (a 45 degree rotation)
+				let t = theta.signum() / (theta.abs() + theta.mul_add(theta, 1.0).sqrt());
+				let c = t.mul_add(t, 1.0).sqrt().recip();
+				let s = t * c;
+
+				for row in matrix.iter_mut().chain(vectors.iter_mut()) {
+					let (kp, kq) = (row[p], row[q]);
+					row[p] = c * kp - s * kq;
+					row[q] = s * kp + c * kq;
+				}
+				let (row_p, row_q) = (matrix[p].clone(), matrix[q].clone());
+				matrix[p] = row_p.iter().zip(&row_q).map(|(pk, qk)| c * pk - s * qk).collect();
+				matrix[q] = row_p.iter().zip(&row_q).map(|(pk, qk)| s * pk + c * qk).collect();
+			}
+		}
+	}
+
+	((0..size).map(|i| matrix[i][i]).collect(), vectors)
+}
+
+/// Read the first `limit` items (along the first dimension) of an IDX file, without reading the rest.
+/// Also returns how many items the whole file has
+#[cfg(feature = "idx")]
+fn read_idx_limited(source: &mut impl std::io::Read, limit: Option<usize>) -> Result<(idx_lib::ArrayD<idx_lib::IdxData>, usize), Box<dyn std::error::Error>> {
+	use std::io::Read;
+
+	let mut header = [0u8; 4];
+	source.read_exact(&mut header)?;
+
+	let mut dimensions = Vec::with_capacity(header[3] as usize);
+	for _ in 0..header[3] {
+		let mut dimension = [0u8; 4];
+		source.read_exact(&mut dimension)?;
+		dimensions.push(u32::from_be_bytes(dimension) as usize);
+	}
+	let items = dimensions.first().copied().unwrap_or(0);
+
+	// The size of each value, from the data type. `read_idx` rejects unknown types
+	let value_size: usize = match header[2] {
+		0x08 | 0x09 => 1,
+		0x0B => 2,
+		0x0C | 0x0D => 4,
+		0x0E => 8,
+		_ => 0,
+	};
+
+	// The header is rewritten with the number of items that are kept, and only their bytes are read
+	let mut limited = header.to_vec();
+	let mut bytes = value_size;
+	if let Some((first, rest)) = dimensions.split_first_mut() {
+		*first = limit.map_or(*first, |limit| limit.min(*first));
+		bytes = rest.iter().fold(bytes.saturating_mul(*first), |bytes, size| bytes.saturating_mul(*size));
+	}
+	for dimension in &dimensions {
+		limited.extend((*dimension as u32).to_be_bytes());
+	}
+	source.take(bytes as u64).read_to_end(&mut limited)?;
+
+	Ok((idx_lib::read_idx(&mut std::io::Cursor::new(limited))?, items))
+}
+
+/// Keep IO errors from idx-lib as IO errors. Everything else means the file isn't valid IDX
+#[cfg(feature = "idx")]
+fn idx_error(argument: &str, err: Box<dyn std::error::Error>) -> crate::error::NeuralibError {
+	match err.downcast::<std::io::Error>() {
+		Ok(err) => (*err).into(),
+		Err(err) => crate::error::InvalidIdxError { reason: format!("{argument} couldn't be read ({err})") }.into(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Write an IDX file of unsigned bytes
+	#[cfg(feature = "idx")]
+	fn idx_bytes(dimensions: &[u32], values: &[u8]) -> std::io::Cursor<Vec<u8>> {
+		let mut bytes = vec![0, 0, 0x08, dimensions.len() as u8];
+		for dimension in dimensions {
+			bytes.extend(dimension.to_be_bytes());
+		}
+		bytes.extend(values);
+		std::io::Cursor::new(bytes)
+	}
+
+	#[cfg(feature = "idx")]
+	#[test]
+	fn from_data_label_idx() {
+		// 3 2x2 images
+		let mut images = idx_bytes(&[3, 2, 2], &[0, 255, 51, 102, 1, 2, 3, 4, 10, 20, 30, 40]);
+		let mut labels = idx_bytes(&[3], &[2, 0, 1]);
+
+		let data = DataValue::from_data_label_idx(&mut images, &mut labels, Some(255.0), None).unwrap();
+		assert_eq!(data.len(), 3);
+		assert_eq!(data[0].input, vec![0.0, 1.0, 0.2, 0.4]);
+		assert_eq!(data[0].expected_output, vec![0.0, 0.0, 1.0]);
+		assert_eq!(data[1].expected_output, vec![1.0, 0.0, 0.0]);
+		assert_eq!(data[2].expected_output, vec![0.0, 1.0, 0.0]);
+
+		let mut images = idx_bytes(&[1, 2], &[7, 8]);
+		let mut labels = idx_bytes(&[1], &[0]);
+		let data = DataValue::from_data_label_idx(&mut images, &mut labels, None, None).unwrap();
+		assert_eq!(data[0].input, vec![7.0, 8.0]);
+
+		let mut images = idx_bytes(&[0, 2], &[]);
+		let mut labels = idx_bytes(&[0], &[]);
+		assert!(DataValue::from_data_label_idx(&mut images, &mut labels, None, None).unwrap().is_empty());
+	}
+
+	#[cfg(feature = "idx")]
+	#[test]
+	fn from_data_label_idx_limit() {
+		let images = idx_bytes(&[4, 1], &[1, 2, 3, 4]);
+		let labels = idx_bytes(&[4], &[0, 1, 2, 3]);
+
+		let data = DataValue::from_data_label_idx(&mut images.clone(), &mut labels.clone(), None, Some(2)).unwrap();
+		assert_eq!(data.len(), 2);
+		assert_eq!(data[1].input, vec![2.0]);
+		assert_eq!(data[1].expected_output, vec![0.0, 1.0, 0.0, 0.0]);
+
+		assert_eq!(DataValue::from_data_label_idx(&mut images.clone(), &mut labels.clone(), None, Some(10)).unwrap().len(), 4);
+
+		// The inputs and labels have to match
+		let mut labels = idx_bytes(&[3], &[0, 1, 2]);
+		assert!(DataValue::from_data_label_idx(&mut images.clone(), &mut labels, None, Some(2)).is_err());
+
+		// Inputs after the limit aren't read, so a file cut off after them still loads
+		let mut images = idx_bytes(&[4, 2], &[1, 2, 3, 4]);
+		let data = DataValue::from_data_label_idx(&mut images, &mut idx_bytes(&[4], &[0, 1, 2, 3]), None, Some(2)).unwrap();
+		assert_eq!(data[1].input, vec![3.0, 4.0]);
+		assert_eq!(images.position(), 16);
+		let mut images = idx_bytes(&[4, 2], &[1, 2, 3, 4]);
+		assert!(DataValue::from_data_label_idx(&mut images, &mut idx_bytes(&[4], &[0, 1, 2, 3]), None, None).is_err());
+	}
+
+	#[cfg(feature = "csv")]
+	#[test]
+	fn from_csv() {
+		let iris = "sepal_length,sepal_width,petal_length,petal_width,setosa,versicolor,virginica\n\
+			5.1,3.5,1.4,0.2,1,0,0\n\
+			7.0,3.2,4.7,1.4,0,1,0\n\
+			6.3,3.3,6.0,2.5,0,0,1\n";
+
+		let data = DataValue::from_csv(iris.as_bytes(), &[0, 1, 2, 3], &[4, 5, 6], true).unwrap();
+		assert_eq!(data.len(), 3);
+		assert_eq!(data[0].input, vec![5.1, 3.5, 1.4, 0.2]);
+		assert_eq!(data[2].expected_output, vec![0.0, 0.0, 1.0]);
+
+		// Columns can be picked in any order
+		let data = DataValue::from_csv("1,2,3\n".as_bytes(), &[2, 0], &[1], false).unwrap();
+		assert_eq!(data[0].input, vec![3.0, 1.0]);
+		assert_eq!(data[0].expected_output, vec![2.0]);
+
+		// The header isn't a number
+		assert!(DataValue::from_csv(iris.as_bytes(), &[0, 1, 2, 3], &[4, 5, 6], false).is_err());
+		assert!(DataValue::from_csv("1,2,3\n".as_bytes(), &[3], &[0], false).is_err());
+	}
+
+	#[test]
+	fn one_hot() {
+		assert_eq!(DataValue::one_hot(0, 3).unwrap(), vec![1.0, 0.0, 0.0]);
+		assert_eq!(DataValue::one_hot(2, 3).unwrap(), vec![0.0, 0.0, 1.0]);
+		assert!(DataValue::one_hot(3, 3).is_err());
+
+		let value = DataValue::from_label(vec![0.5, -1.0], 1, 2).unwrap();
+		assert_eq!(value.input, vec![0.5, -1.0]);
+		assert_eq!(value.expected_output, vec![0.0, 1.0]);
+		assert!(DataValue::from_label(vec![0.5], 2, 2).is_err());
+	}
+
+	#[test]
+	fn normalize_inputs() {
+		let mut data = vec![
+			DataValue { input: vec![0.0, 255.0], expected_output: vec![1.0] },
+			DataValue { input: vec![51.0, 102.0], expected_output: vec![2.0] },
+		];
+
+		DataValue::normalize_inputs(&mut data, 0.0, 255.0).unwrap();
+		assert_eq!(data[0].input, vec![0.0, 1.0]);
+		assert_eq!(data[1].input, vec![0.2, 0.4]);
+		// Only the inputs are changed
+		assert_eq!(data[1].expected_output, vec![2.0]);
+
+		assert!(DataValue::normalize_inputs(&mut data, 1.0, 1.0).is_err());
+	}
+
+	#[test]
+	fn scale_outputs() {
+		let mut data = vec![
+			DataValue { input: vec![1.0], expected_output: vec![10.0, 3.0] },
+			DataValue { input: vec![2.0], expected_output: vec![30.0, 3.0] },
+			DataValue { input: vec![3.0], expected_output: vec![20.0, 3.0] },
+		];
+		let original = data.clone();
+
+		let scaling = DataValue::scale_outputs(&mut data, -1.0, 1.0).unwrap();
+		assert_eq!(scaling.data_mins, vec![10.0, 3.0]);
+		assert_eq!(scaling.data_maxs, vec![30.0, 3.0]);
+		assert_eq!(data[0].expected_output, vec![-1.0, 0.0]);
+		assert_eq!(data[1].expected_output, vec![1.0, 0.0]);
+		assert_eq!(data[2].expected_output, vec![0.0, 0.0]);
+		// Only the outputs are changed
+		assert_eq!(data[2].input, vec![3.0]);
+
+		for (value, original) in data.iter().zip(&original) {
+			assert_eq!(scaling.unscale(&value.expected_output), original.expected_output);
+		}
+		assert_eq!(scaling.unscale(&[0.5, 0.3]), vec![25.0, 3.0]);
+
+		assert!(DataValue::scale_outputs(&mut data, 1.0, 0.0).is_err());
+		data.push(DataValue { input: vec![4.0], expected_output: vec![1.0] });
+		assert!(DataValue::scale_outputs(&mut data, 0.0, 1.0).is_err());
+	}
+
+	#[test]
+	fn standardize_inputs() {
+		let mut data: Vec<DataValue> = [[1.0, 5.0, 2.0], [3.0, 5.0, 4.0], [5.0, 5.0, 12.0]].iter()
+			.map(|input| DataValue { input: input.to_vec(), expected_output: vec![] })
+			.collect();
+
+		let (means, stds) = DataValue::standardize_inputs(&mut data).unwrap();
+		assert_eq!(means, vec![3.0, 5.0, 6.0]);
+		assert!((stds[0] - (8.0_f64 / 3.0).sqrt()).abs() < 1e-12);
+		assert_eq!(stds[1], 0.0);
+
+		for feature in 0..3 {
+			let mean: f64 = data.iter().map(|value| value.input[feature]).sum::<f64>() / 3.0;
+			let variance: f64 = data.iter().map(|value| value.input[feature].powi(2)).sum::<f64>() / 3.0;
+			assert!(mean.abs() < 1e-12);
+			// The constant feature is only centered
+			assert!((variance - if feature == 1 { 0.0 } else { 1.0 }).abs() < 1e-12);
+		}
+
+		// The same transform can be applied to test data
+		let mut test = vec![DataValue { input: vec![3.0, 6.0, 6.0], expected_output: vec![] }];
+		DataValue::apply_standardization(&mut test, &means, &stds).unwrap();
+		assert_eq!(test[0].input, vec![0.0, 1.0, 0.0]);
+
+		assert!(DataValue::apply_standardization(&mut test, &means[1..], &stds[1..]).is_err());
+		data.push(DataValue { input: vec![1.0], expected_output: vec![] });
+		assert!(DataValue::standardize_inputs(&mut data).is_err());
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn train_test_split() {
+		let data: Vec<DataValue> = (0..10).map(|x| DataValue { input: vec![x as f64], expected_output: vec![] }).collect();
+
+		let (train, test) = DataValue::train_test_split(data.clone(), 0.3, Some(42)).unwrap();
+		assert_eq!(train.len(), 7);
+		assert_eq!(test.len(), 3);
+
+		// Every sample ends up in exactly one half
+		let mut inputs: Vec<f64> = train.iter().chain(&test).map(|value| value.input[0]).collect();
+		inputs.sort_by(f64::total_cmp);
+		assert_eq!(inputs, (0..10).map(|x| x as f64).collect::<Vec<_>>());
+
+		// The same seed gives the same split
+		let (_, same_test) = DataValue::train_test_split(data.clone(), 0.3, Some(42)).unwrap();
+		assert_eq!(test.iter().map(|value| value.input[0]).collect::<Vec<_>>(), same_test.iter().map(|value| value.input[0]).collect::<Vec<_>>());
+
+		assert!(DataValue::train_test_split(data.clone(), 0.0, None).is_err());
+		assert!(DataValue::train_test_split(data.clone(), 1.0, None).is_err());
+		assert!(DataValue::train_test_split(data, f64::NAN, None).is_err());
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn data_set_batches() {
+		let mut data_set = DataSet::from((0..10).map(|x| DataValue { input: vec![x as f64], expected_output: vec![] }).collect::<Vec<_>>());
+
+		for epoch in 0..3 {
+			let batches: Vec<&[DataValue]> = data_set.batches(4, Some(epoch)).unwrap().collect();
+			assert_eq!(batches.iter().map(|batch| batch.len()).collect::<Vec<_>>(), vec![4, 4, 2]);
+
+			let mut seen: Vec<f64> = batches.iter().flat_map(|batch| batch.iter().map(|value| value.input[0])).collect();
+			seen.sort_by(f64::total_cmp);
+			assert_eq!(seen, (0..10).map(|x| x as f64).collect::<Vec<_>>());
+		}
+
+		// The same seed gives the same order
+		let first: Vec<f64> = DataSet::new(data_set.values().to_vec()).batches(3, Some(1)).unwrap().flatten().map(|value| value.input[0]).collect();
+		let second: Vec<f64> = DataSet::new(data_set.values().to_vec()).batches(3, Some(1)).unwrap().flatten().map(|value| value.input[0]).collect();
+		assert_eq!(first, second);
+
+		assert!(data_set.batches(0, None).is_err());
+		assert_eq!(data_set.len(), 10);
+		assert!(DataSet::default().batches(5, None).unwrap().next().is_none());
+		assert_eq!(data_set.into_values().len(), 10);
+	}
+
+	#[cfg(feature = "training")]
+	#[test]
+	fn oversample() {
+		let mut data: Vec<DataValue> = (0..90).map(|x| DataValue { input: vec![x as f64], expected_output: vec![1.0, 0.0] }).collect();
+		data.extend((0..10).map(|x| DataValue { input: vec![-x as f64], expected_output: vec![0.0, 1.0] }));
+
+		let balanced = super::oversample(&data, &mut rand::rng());
+
+		let minority: Vec<&DataValue> = balanced.iter().filter(|value| value.expected_output[1] == 1.0).collect();
+		assert_eq!(balanced.len(), 180);
+		assert_eq!(minority.len(), 90);
+		// Every minority sample is kept, and the extras are copies of them
+		for x in 0..10 {
+			assert!(minority.iter().any(|value| value.input[0] == -x as f64));
+		}
+		assert!(minority.iter().all(|value| value.input[0] <= 0.0 && value.input[0] > -10.0));
+
+		assert!(super::oversample(&[], &mut rand::rng()).is_empty());
+	}
+
+	#[test]
+	fn pca_project() {
+		// Points in a 2D plane inside 5D space
+		let u = [1.0, 2.0, 0.0, -1.0, 0.5];
+		let v = [0.0, 1.0, 3.0, 1.0, -2.0];
+		let coefficients = [(0.0, 0.0), (1.0, 0.5), (-2.0, 1.0), (0.5, -1.5), (3.0, 2.0), (-1.0, -1.0)];
+		let points: Vec<Vec<f64>> = coefficients.iter()
+			.map(|(a, b)| (0..5).map(|i| a * u[i] + b * v[i] + 10.0).collect())
+			.collect();
+
+		let projected = super::pca_project(&points, 3).unwrap();
+		assert_eq!(projected.len(), points.len());
+
+		// Only 2 of the components have any variance
+		for row in &projected {
+			assert!(row[2].abs() < 1e-8);
+		}
+		assert!(projected.iter().any(|row| row[0].abs() > 1e-3));
+		assert!(projected.iter().any(|row| row[1].abs() > 1e-3));
 
-		//let data_parsed = data_parsed[0].flatten();
+		// The 2D projection keeps the distances between points
+		let distance = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f64>().sqrt();
+		let projected = super::pca_project(&points, 2).unwrap();
+		for i in 0..points.len() {
+			for j in 0..points.len() {
+				assert!((distance(&points[i], &points[j]) - distance(&projected[i], &projected[j])).abs() < 1e-8);
+			}
+		}
 
-		//println!("{data_parsed:#?}");
-		Ok(data_parsed)
+		assert!(super::pca_project(&points, 6).is_err());
+		assert!(super::pca_project(&[], 1).is_err());
+		assert!(super::pca_project(&[vec![1.0, 2.0], vec![1.0]], 1).is_err());
 	}
 }